@@ -6,7 +6,7 @@ use openni2::{
     Stream,
     OniRGB888Pixel,
     OniDepthPixel,
-    OniGrayscale16Pixel,
+    Gray16Pixel,
     Pixel,
     // LogLevel,
 };
@@ -70,7 +70,7 @@ fn main() -> Result<(), Status> {
 
     interrogate_stream::<OniDepthPixel>(&device, SensorType::DEPTH);
 
-    interrogate_stream::<OniGrayscale16Pixel>(&device, SensorType::IR);
+    interrogate_stream::<Gray16Pixel>(&device, SensorType::IR);
 
     openni2::shutdown();
     Ok(())
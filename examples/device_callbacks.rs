@@ -5,19 +5,19 @@ use std::{thread, time};
 fn main() -> Result<(), Status> {
     openni2::init()?;
 
-    let mut on_device_connect = |device_info: DeviceInfo| {
+    let on_device_connect = |device_info: DeviceInfo| {
         println!("{} connected", device_info.uri);
     };
 
-    let mut on_device_disconnect = |device_info: DeviceInfo| {
+    let on_device_disconnect = |device_info: DeviceInfo| {
         println!("{} disconnected", device_info.uri);
     };
 
-    let mut on_device_state_change = |device_info: DeviceInfo, state: DeviceState| {
+    let on_device_state_change = |device_info: DeviceInfo, state: DeviceState| {
         println!("{} changed state: {:?}", device_info.uri, state);
     };
 
-    let _handle = openni2::register_device_callbacks(&mut on_device_connect, &mut on_device_disconnect, &mut on_device_state_change)?;
+    let _handle = openni2::register_device_callbacks(on_device_connect, on_device_disconnect, on_device_state_change)?;
 
     let one_second = time::Duration::from_millis(1000);
     loop {
@@ -28,7 +28,8 @@ fn on_device_state_change(device_info: DeviceInfo, state: DeviceState) {
 fn main() -> Result<(), Status> {
     openni2::init()?;
 
-    openni2::register_device_callbacks(on_device_connect, on_device_disconnect, on_device_state_change)?;
+    // The callbacks stay registered until the handle falls out of scope
+    let _callbacks = openni2::register_device_callbacks(on_device_connect, on_device_disconnect, on_device_state_change)?;
 
     for device_info in openni2::get_device_list() {
         println!("{} already connected", device_info.uri);
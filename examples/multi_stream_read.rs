@@ -0,0 +1,42 @@
+// Rust implementation of the OpenNI2 C++ sample
+// https://github.com/OpenNI/OpenNI2/blob/master/Samples/SimpleRead/main.cpp
+// extended to service a depth and a color stream from the same thread.
+
+extern crate openni2;
+use std::time::Duration;
+use openni2::{
+    Status,
+    Device,
+    SensorType,
+    OniDepthPixel,
+    OniRGB888Pixel,
+};
+
+fn main() -> Result<(), Status> {
+    openni2::init()?;
+
+    let device = Device::open_default()?;
+    let depth = device.create_stream(SensorType::DEPTH)?;
+    let color = device.create_stream(SensorType::COLOR)?;
+
+    depth.start()?;
+    color.start()?;
+
+    let timeout = Duration::from_millis(2000);
+    loop {
+        match openni2::wait_for_any_stream(&[&depth, &color], timeout) {
+            Ok(0) => {
+                let frame = depth.read_frame::<OniDepthPixel>()?;
+                let middle = frame.width() as usize * frame.height() as usize / 2;
+                println!("[depth {:08}] {:8}", frame.timestamp(), frame.pixels()[middle]);
+            },
+            Ok(_) => {
+                let frame = color.read_frame::<OniRGB888Pixel>()?;
+                let middle = frame.width() as usize * frame.height() as usize / 2;
+                println!("[color {:08}] {:?}", frame.timestamp(), frame.pixels()[middle]);
+            },
+            Err(Status::TimeOut) => println!("Wait failed! (timeout is {:?})", timeout),
+            Err(status) => return Err(status),
+        }
+    }
+}
@@ -10,33 +10,9 @@ use openni2::{
     Device,
     SensorType,
     OniDepthPixel,
-    OniRGB888Pixel,
-    Frame,
+    convert,
 };
-
-pub fn depth_histogram(hist: &mut [f32], frame: &Frame<OniDepthPixel>) {
-    let pixels = frame.pixels();
-    let mut count = 0usize;
-    for h in hist.iter_mut() {
-        *h = 0f32;
-    }
-
-    for px in pixels {
-        if *px != 0 {
-            hist[*px as usize] += 1.0;
-            count += 1;
-        }
-    }
-
-    for i in 1..hist.len() {
-        hist[i] += hist[i-1];
-    }
-    if count > 0 {
-        for px in hist.iter_mut().skip(1) {
-            *px = 256f32 * (1.0f32 - (*px / count as f32));
-        }
-    }
-}
+use openni2::visualize::{self, DepthHistogram, Colormap};
 
 fn main() -> Result<(), Status> {
     openni2::init()?;
@@ -59,17 +35,18 @@ fn main() -> Result<(), Status> {
     let mut mirror = color.get_mirroring()?;
     let mut display_color = true;
     let mut display_depth = false;
-    let mut histogram = vec![0f32; 10000].into_boxed_slice();
+    let mut histogram = DepthHistogram::new();
     let mut buffer = vec![0u32; 320 * 240].into_boxed_slice();
     while window.is_open() && !window.is_key_down(Key::Escape) {
-        let color_frame = color.read_frame::<OniRGB888Pixel>().expect("Color frame not available to read.");
+        // Whatever pixel format the color stream is in, convert it to RGB for display
+        let color_frame = color.read_frame_any().expect("Color frame not available to read.");
+        let color_pixels = convert::to_rgb888(&color_frame).expect("Couldn't convert color frame.");
         let depth_frame = depth.read_frame::<OniDepthPixel>().expect("Depth frame not available to read.");
-        depth_histogram(&mut histogram, &depth_frame);
-        for (i, (color, depth)) in color_frame.pixels().iter().zip(depth_frame.pixels()).enumerate() {
+        histogram.update(&depth_frame);
+        let depth_pixels = visualize::colorize(&depth_frame, &histogram, Colormap::GRAYSCALE);
+        for (i, ((color, depth), shade)) in color_pixels.iter().zip(depth_frame.pixels()).zip(&depth_pixels).enumerate() {
             if display_depth && *depth > 0 {
-                // let brightness = (depth / 256) as u32;
-                let brightness = histogram[*depth as usize] as u32;
-                buffer[i] = brightness << 16 | brightness << 8 | brightness;
+                buffer[i] = ((shade.r as u32) << 16) | ((shade.g as u32) << 8) | (shade.b as u32);
             } else if display_color {
                 buffer[i] = ((color.r as u32) << 16) | ((color.g as u32) << 8) | (color.b as u32);
             } else {
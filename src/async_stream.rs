@@ -0,0 +1,190 @@
+//! `futures::Stream`s of frames and device events, enabled by the `async`
+//! cargo feature.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream as AsyncStream;
+
+use device::DeviceInfo;
+use frame::OwnedFrame;
+use stream::{Stream, StreamListener};
+use types::{Status, Pixel, DeviceState};
+use {DeviceCallbackHandle, register_device_callbacks};
+
+// Items pushed from an OpenNI2 callback thread, waiting to be polled.
+struct Queue<T> {
+    items: VecDeque<T>,
+    capacity: Option<usize>,
+    waker: Option<Waker>,
+}
+
+type SharedQueue<T> = Arc<Mutex<Queue<T>>>;
+
+fn shared_queue<T>(capacity: Option<usize>) -> SharedQueue<T> {
+    Arc::new(Mutex::new(Queue {
+        items: VecDeque::new(),
+        capacity,
+        waker: None,
+    }))
+}
+
+fn push<T>(queue: &Mutex<Queue<T>>, item: T) {
+    let waker = match queue.lock() {
+        Ok(mut queue) => {
+            if let Some(capacity) = queue.capacity {
+                while queue.items.len() >= capacity {
+                    queue.items.pop_front();
+                }
+            }
+            queue.items.push_back(item);
+            queue.waker.take()
+        },
+        Err(_) => return,
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+fn poll_queue<T>(queue: &Mutex<Queue<T>>, cx: &mut Context) -> Poll<Option<T>> {
+    let mut queue = queue.lock().expect("Async queue poisoned");
+    match queue.items.pop_front() {
+        Some(item) => Poll::Ready(Some(item)),
+        None => {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        },
+    }
+}
+
+/// An asynchronous stream of frames copied from a `Stream`. See
+/// `Stream::frame_stream`.
+pub struct FrameStream<'stream, P: Pixel> {
+    queue: SharedQueue<Result<OwnedFrame<P>, Status>>,
+    _listener: StreamListener<'stream>,
+    _pixel_type: PhantomData<P>,
+}
+
+// The queue is shared behind a mutex, and the listener only unregisters its
+// callback when dropped, which OpenNI2 and `StreamBackend`s allow from any
+// thread. The `Stream` itself stays where it is.
+unsafe impl<'stream, P: Pixel + Send> Send for FrameStream<'stream, P> {}
+
+impl<'stream, P: Pixel> AsyncStream for FrameStream<'stream, P> {
+    type Item = Result<OwnedFrame<P>, Status>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        poll_queue(&self.queue, cx)
+    }
+}
+
+impl<'device> Stream<'device> {
+    /// Returns a `futures::Stream` of the stream's frames, which is woken
+    /// from the stream's new frame callback instead of blocking a thread.
+    ///
+    /// At most `capacity` frames wait to be polled; when more arrive, the
+    /// oldest are dropped. A frame that can't be read, or whose pixel format
+    /// `P` doesn't support, is yielded as `Err`. The stream never ends on its
+    /// own.
+    ///
+    /// Unlike the `Stream` it comes from, a `FrameStream` is `Send`, so it can
+    /// be polled on another thread, e.g. by a spawned task, as long as the
+    /// `Stream` outlives it.
+    ///
+    /// # Example
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate openni2;
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
+    /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// let frames = stream.frame_stream::<OniDepthPixel>(2)?;
+    /// stream.start()?;
+    /// for frame in futures::executor::block_on_stream(frames).take(30) {
+    ///     println!("{}", frame?.timestamp);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn frame_stream<P: Pixel + Send + 'static>(&self, capacity: usize) -> Result<FrameStream<'_, P>, Status> {
+        let queue = shared_queue(Some(capacity.max(1)));
+        let callback_queue = queue.clone();
+        let listener = self.register_listener(move |stream| {
+            let frame = stream.read_frame_any().and_then(|frame| {
+                frame.downcast::<P>()
+                    .map(|frame| frame.to_owned())
+                    .map_err(|frame| Status::Error(format!("Frame of pixel format {:?} can't be read as the stream's pixel type", frame.pixel_format())))
+            });
+            push(&callback_queue, frame);
+        })?;
+        Ok(FrameStream {
+            queue,
+            _listener: listener,
+            _pixel_type: PhantomData,
+        })
+    }
+}
+
+/// A change in the devices connected to the system. See `device_events`.
+#[derive(Debug)]
+pub enum DeviceEvent {
+    Connected(DeviceInfo),
+    Disconnected(DeviceInfo),
+    StateChanged(DeviceInfo, DeviceState),
+}
+
+/// An asynchronous stream of hotplug events. See `device_events`.
+pub struct DeviceEvents {
+    queue: SharedQueue<DeviceEvent>,
+    _handle: DeviceCallbackHandle,
+}
+
+impl AsyncStream for DeviceEvents {
+    type Item = DeviceEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        poll_queue(&self.queue, cx)
+    }
+}
+
+/// Returns a `futures::Stream` of devices being connected, disconnected, or
+/// changing state, built on `register_device_callbacks`. The callbacks stay
+/// registered until the `DeviceEvents` is dropped.
+///
+/// # Example
+/// ```no_run
+/// # extern crate futures;
+/// # extern crate openni2;
+/// # use openni2::DeviceEvent;
+/// # fn main() -> Result<(), openni2::Status> {
+/// openni2::init()?;
+/// let events = openni2::device_events()?;
+/// for event in futures::executor::block_on_stream(events) {
+///     if let DeviceEvent::Connected(info) = event {
+///         println!("{} connected", info.uri);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn device_events() -> Result<DeviceEvents, Status> {
+    let queue = shared_queue(None);
+    let (connected, disconnected, state_changed) = (queue.clone(), queue.clone(), queue.clone());
+    let handle = register_device_callbacks(
+        move |info| push(&connected, DeviceEvent::Connected(info)),
+        move |info| push(&disconnected, DeviceEvent::Disconnected(info)),
+        move |info, state| push(&state_changed, DeviceEvent::StateChanged(info, state)),
+    )?;
+    Ok(DeviceEvents {
+        queue,
+        _handle: handle,
+    })
+}
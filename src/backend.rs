@@ -0,0 +1,165 @@
+use openni2_sys::*;
+use device::DeviceInfo;
+use stream::Cropping;
+use types::{Status, SensorType, ImageRegistrationMode, VideoMode, SensorInfo};
+
+/// A source of streams that a `Device` can wrap instead of an OpenNI2
+/// driver. See `Device::from_backend`, and `mock::MockDevice` for an
+/// implementation that needs no hardware.
+///
+/// Properties are passed as the raw bytes of the C types that OpenNI2 uses
+/// for them, e.g. an `OniVersion` for `ONI_DEVICE_PROPERTY_DRIVER_VERSION`,
+/// so the typed getters and setters of `Device` work unchanged. A backend
+/// should return `Err(Status::BadParameter)` if `data` has the wrong length
+/// for the property, and `Err(Status::NotSupported)` for properties it
+/// doesn't have.
+pub trait DeviceBackend {
+    /// Describes the device.
+    fn info(&self) -> Result<DeviceInfo, Status>;
+
+    /// Describes one of the device's sensors, or returns `None` if the
+    /// device doesn't have it.
+    fn sensor_info(&self, sensor_type: SensorType) -> Option<SensorInfo>;
+
+    /// Creates a stream of one of the device's sensors.
+    fn create_stream(&self, sensor_type: SensorType) -> Result<Box<dyn StreamBackend>, Status>;
+
+    fn is_property_supported(&self, property: OniDeviceProperty) -> bool;
+
+    /// Copies the value of a property into `data`.
+    fn get_property(&self, property: OniDeviceProperty, data: &mut [u8]) -> Result<(), Status>;
+
+    /// Sets a property from the bytes in `data`.
+    fn set_property(&self, property: OniDeviceProperty, data: &[u8]) -> Result<(), Status>;
+
+    fn is_image_registration_mode_supported(&self, _mode: ImageRegistrationMode) -> bool {
+        false
+    }
+
+    fn color_depth_sync(&self) -> bool {
+        false
+    }
+
+    fn enable_color_depth_sync(&self, _enabled: bool) -> Status {
+        Status::NotSupported
+    }
+
+    fn is_command_supported(&self, _command: OniDeviceCommand) -> bool {
+        false
+    }
+
+    /// Runs a command, with `data` holding the bytes of its C argument.
+    fn invoke_command(&self, _command: OniDeviceCommand, _data: &mut [u8]) -> Result<(), Status> {
+        Err(Status::NotSupported)
+    }
+}
+
+/// A stream of frames created by a `DeviceBackend`. Wrapped by `Stream`,
+/// whose methods forward to it.
+///
+/// # Safety
+///
+/// `Stream::listener` hands the backend callbacks that borrow the `Stream`.
+/// Once `unregister_new_frame_callback` returns, the callback must not be
+/// running and must never be called again. `unregister_new_frame_callback`
+/// may be called from any thread, including from inside the callback, where
+/// it must return without waiting for the callback; the callback may finish
+/// running then, but must never be called again.
+pub unsafe trait StreamBackend {
+    fn start(&self) -> Result<(), Status>;
+
+    fn stop(&self);
+
+    /// Describes the sensor the stream reads from.
+    fn sensor_info(&self) -> Option<SensorInfo>;
+
+    fn is_property_supported(&self, property: OniStreamProperty) -> bool;
+
+    /// Copies the value of a property into `data`. See `DeviceBackend` for
+    /// how properties are encoded.
+    fn get_property(&self, property: OniStreamProperty, data: &mut [u8]) -> Result<(), Status>;
+
+    /// Sets a property from the bytes in `data`.
+    fn set_property(&self, property: OniStreamProperty, data: &[u8]) -> Result<(), Status>;
+
+    /// Returns the next frame, blocking until it is ready.
+    fn read_frame(&self) -> Result<FrameData, Status>;
+
+    /// Returns the timestamp of the frame that `read_frame` would return
+    /// without blocking, or `None` if no frame is ready. Used by
+    /// `openni2::wait_for_any_stream`, which picks the earliest frame when
+    /// several streams are ready.
+    fn ready_frame_timestamp(&self) -> Option<u64>;
+
+    /// Calls `callback` from any thread whenever a new frame is ready,
+    /// until the callback is unregistered with the returned id.
+    fn register_new_frame_callback(&self, callback: Box<dyn FnMut() + Send>) -> Result<usize, Status>;
+
+    fn unregister_new_frame_callback(&self, id: usize);
+
+    /// Moves the stream to the frame with index `frame_index`, for
+    /// `PlaybackControl::seek`. Backends that play back recordings should
+    /// move their other streams to the frames recorded at the same time.
+    fn seek(&self, _frame_index: usize) -> Result<(), Status> {
+        Err(Status::NotSupported)
+    }
+
+    fn depth_to_world(&self, _depth: (f32, f32, f32)) -> Result<(f32, f32, f32), Status> {
+        Err(Status::NotSupported)
+    }
+
+    fn world_to_depth(&self, _world: (f32, f32, f32)) -> Result<(f32, f32, f32), Status> {
+        Err(Status::NotSupported)
+    }
+
+    /// Converts a depth pixel of this stream into the pixel coordinates of
+    /// `color`, a stream of the same backend.
+    fn depth_to_color(&self, _color: &dyn StreamBackend, _depth: (i32, i32, OniDepthPixel)) -> Result<(i32, i32), Status> {
+        Err(Status::NotSupported)
+    }
+}
+
+/// A frame produced by a `StreamBackend`. `Stream::read_frame` wraps it in
+/// a `Frame` without copying the pixel data.
+#[derive(Debug, Clone)]
+pub struct FrameData {
+    pub sensor_type: SensorType,
+    /// Pixel data, in rows of `width` pixels, in the byte layout of the
+    /// video mode's pixel format
+    pub data: Vec<u8>,
+    pub width: u16,
+    pub height: u16,
+    /// Timestamp in microseconds
+    pub timestamp: u64,
+    pub index: usize,
+    pub video_mode: VideoMode,
+    /// The crop of the original frame, if it was cropped.
+    pub cropping: Option<Cropping>,
+}
+
+impl FrameData {
+    // Describes the frame as an `OniFrame` that points into `data`.
+    pub(crate) fn oni_frame(&self) -> OniFrame {
+        let stride = if self.height > 0 { self.data.len() / self.height as usize } else { 0 };
+        let cropping = self.cropping.unwrap_or(Cropping { width: self.width, height: self.height, origin_x: 0, origin_y: 0 });
+        OniFrame {
+            dataSize: self.data.len() as i32,
+            data: self.data.as_ptr() as *mut _,
+            sensorType: self.sensor_type as OniSensorType,
+            timestamp: self.timestamp,
+            frameIndex: self.index as i32,
+            width: i32::from(self.width),
+            height: i32::from(self.height),
+            videoMode: OniVideoMode {
+                pixelFormat: self.video_mode.pixel_format as OniPixelFormat,
+                resolutionX: self.video_mode.resolution_x,
+                resolutionY: self.video_mode.resolution_y,
+                fps: self.video_mode.fps,
+            },
+            croppingEnabled: self.cropping.is_some() as i32,
+            cropOriginX: i32::from(cropping.origin_x),
+            cropOriginY: i32::from(cropping.origin_y),
+            stride: stride as i32,
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Exports the depth and color frames of a recording as images, with a CSV
+//! file per stream of frame indices and timestamps.
+//!
+//! Frames are read through `PlaybackControl` in manual mode, so none are
+//! dropped however long writing them takes. Images are named after their
+//! timestamps in seconds, like TUM RGB-D's, so the output folder can be
+//! played back again with `Device::open_image_sequence`.
+
+extern crate openni2;
+extern crate image;
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, process};
+
+use image::{ImageBuffer, Luma, Rgb};
+use image::codecs::jpeg::JpegEncoder;
+use openni2::{Device, DynFrame, PixelFormat, SensorType, Status, Stream, OniDepthPixel, DepthMmPixel, Depth100UmPixel};
+use openni2::visualize::{Colormap, DepthRange, DepthScale};
+
+const USAGE: &str = "\
+Usage: oni-export [OPTIONS] <INPUT> <OUTPUT>
+
+Exports the depth and color frames of an .oni recording, or of an image
+sequence folder, into OUTPUT:
+
+  depth/<timestamp>.png            16-bit depth in millimeters
+  rgb/<timestamp>.png or .jpg      color
+  depth_colorized/<timestamp>.png  colorized depth, with --colorize
+  depth.csv, rgb.csv               frame index, timestamp in microseconds,
+                                   and file of every exported frame
+
+Options:
+  --from <INDEX>           First depth frame to export
+  --to <INDEX>             Last depth frame to export
+  --color-format <FORMAT>  png or jpeg (default: png)
+  --jpeg-quality <1-100>   Quality of JPEG color images (default: 90)
+  --colorize <COLORMAP>    Also export depth colorized with turbo, jet,
+                           viridis or grayscale
+  --depth-range <MIN:MAX>  Depths in millimeters that the colormap spans
+                           (default: the depth stream's range)
+  --no-depth               Don't export depth frames
+  --no-color               Don't export color frames
+  -h, --help               Print this message
+
+The frame range applies to the depth stream, or to the color stream when
+there is no depth. Color frames are exported over the same stretch of time.";
+
+// How long to wait for a frame before deciding a stream has ended.
+const END_OF_STREAM: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ColorFormat {
+    Png,
+    Jpeg(u8),
+}
+
+#[derive(Debug)]
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+    from: Option<usize>,
+    to: Option<usize>,
+    color_format: ColorFormat,
+    colorize: Option<Colormap>,
+    depth_range: Option<DepthRange>,
+    depth: bool,
+    color: bool,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    fn value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+        args.next().ok_or_else(|| format!("{} needs a value", option))
+    }
+    fn number<T: ::std::str::FromStr>(value: &str, option: &str) -> Result<T, String> {
+        value.parse().map_err(|_| format!("{} isn't a valid value for {}", value, option))
+    }
+
+    let mut paths = Vec::new();
+    let mut from = None;
+    let mut to = None;
+    let mut color_format = "png".to_owned();
+    let mut jpeg_quality = 90;
+    let mut colorize = None;
+    let mut depth_range = None;
+    let (mut depth, mut color) = (true, true);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(number(&value(&mut args, &arg)?, &arg)?),
+            "--to" => to = Some(number(&value(&mut args, &arg)?, &arg)?),
+            "--color-format" => color_format = value(&mut args, &arg)?,
+            "--jpeg-quality" => jpeg_quality = number::<u8>(&value(&mut args, &arg)?, &arg)?.clamp(1, 100),
+            "--colorize" => {
+                let name = value(&mut args, &arg)?;
+                colorize = Some(match name.to_lowercase().as_str() {
+                    "turbo" => Colormap::TURBO,
+                    "jet" => Colormap::JET,
+                    "viridis" => Colormap::VIRIDIS,
+                    "grayscale" | "gray" => Colormap::GRAYSCALE,
+                    _ => return Err(format!("Unknown colormap {}", name)),
+                });
+            },
+            "--depth-range" => {
+                let range = value(&mut args, &arg)?;
+                let mut bounds = range.splitn(2, ':');
+                let (min, max) = (bounds.next().unwrap_or(""), bounds.next().unwrap_or(""));
+                depth_range = Some(DepthRange::new(number(min, &arg)?, number(max, &arg)?));
+            },
+            "--no-depth" => depth = false,
+            "--no-color" => color = false,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.len() != 2 {
+        return Err("Expected an input recording and an output folder".to_owned());
+    }
+    let color_format = match color_format.to_lowercase().as_str() {
+        "png" => ColorFormat::Png,
+        "jpeg" | "jpg" => ColorFormat::Jpeg(jpeg_quality),
+        _ => return Err(format!("Unknown color format {}", color_format)),
+    };
+    let output = paths.pop().expect("Two paths");
+    let input = paths.pop().expect("Two paths");
+    Ok(Options { input, output, from, to, color_format, colorize, depth_range, depth, color })
+}
+
+fn error<E: ::std::fmt::Display>(context: &str, path: &Path) -> impl FnOnce(E) -> Status {
+    let message = format!("{} {}", context, path.display());
+    move |err| Status::Error(format!("{}: {}", message, err))
+}
+
+// Names a frame's image after its timestamp in seconds.
+fn file_name(timestamp: u64, extension: &str) -> String {
+    format!("{}.{:06}.{}", timestamp / 1_000_000, timestamp % 1_000_000, extension)
+}
+
+// One line of a stream's CSV file.
+struct Row {
+    index: usize,
+    timestamp: u64,
+    file: String,
+}
+
+fn write_csv(path: &Path, rows: &[Row]) -> Result<(), Status> {
+    let file = File::create(path).map_err(error("Couldn't create", path))?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "index,timestamp,file").map_err(error("Couldn't write", path))?;
+    for row in rows {
+        writeln!(writer, "{},{},{}", row.index, row.timestamp, row.file).map_err(error("Couldn't write", path))?;
+    }
+    writer.flush().map_err(error("Couldn't write", path))
+}
+
+// Rounds a depth in 100 micrometer units to millimeters.
+fn depth_100_um_to_mm(depth: u16) -> OniDepthPixel {
+    ((u32::from(depth) + 5) / 10) as OniDepthPixel
+}
+
+// Converts a depth frame to millimeters.
+fn depth_mm(frame: DynFrame) -> Result<Vec<OniDepthPixel>, Status> {
+    match frame {
+        DynFrame::DEPTH_1_MM(frame) => Ok(frame.pixels().iter().map(|&DepthMmPixel(depth)| depth).collect()),
+        DynFrame::DEPTH_100_UM(frame) => Ok(frame.pixels().iter().map(|&Depth100UmPixel(depth)| depth_100_um_to_mm(depth)).collect()),
+        other => Err(Status::Error(format!("Depth frames in {:?} can't be exported", other.pixel_format()))),
+    }
+}
+
+struct Exporter {
+    options: Options,
+    depth_range: DepthRange,
+}
+
+impl Exporter {
+    // Writes a frame's images, and returns the path of the main one
+    // relative to the output folder.
+    fn write(&self, sensor_type: SensorType, frame: DynFrame) -> Result<String, Status> {
+        let (width, height, timestamp) = (u32::from(frame.width()), u32::from(frame.height()), frame.timestamp());
+        let output = &self.options.output;
+        match sensor_type {
+            SensorType::DEPTH => {
+                let depth = depth_mm(frame)?;
+                if let Some(colormap) = self.options.colorize {
+                    let file = format!("depth_colorized/{}", file_name(timestamp, "png"));
+                    let path = output.join(&file);
+                    let colors: Vec<u8> = depth.iter()
+                        .flat_map(|&depth| self.depth_range.scale(depth).map_or([0; 3], |value| {
+                            let color = colormap.color(value);
+                            [color.r, color.g, color.b]
+                        }))
+                        .collect();
+                    ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, colors)
+                        .ok_or_else(|| Status::Error(format!("Frame {} has the wrong size", file)))?
+                        .save(&path)
+                        .map_err(error("Couldn't write", &path))?;
+                }
+                let file = format!("depth/{}", file_name(timestamp, "png"));
+                let path = output.join(&file);
+                ImageBuffer::<Luma<u16>, _>::from_raw(width, height, depth)
+                    .ok_or_else(|| Status::Error(format!("Frame {} has the wrong size", file)))?
+                    .save(&path)
+                    .map_err(error("Couldn't write", &path))?;
+                Ok(file)
+            },
+            _ => {
+                let pixels: Vec<u8> = openni2::convert::to_rgb888(&frame)?.iter()
+                    .flat_map(|pixel| [pixel.r, pixel.g, pixel.b])
+                    .collect();
+                let image = ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, pixels)
+                    .ok_or_else(|| Status::Error(format!("Color frame {} has the wrong size", timestamp)))?;
+                let (file, quality) = match self.options.color_format {
+                    ColorFormat::Png => (format!("rgb/{}", file_name(timestamp, "png")), None),
+                    ColorFormat::Jpeg(quality) => (format!("rgb/{}", file_name(timestamp, "jpg")), Some(quality)),
+                };
+                let path = output.join(&file);
+                match quality {
+                    Some(quality) => {
+                        let writer = BufWriter::new(File::create(&path).map_err(error("Couldn't create", &path))?);
+                        JpegEncoder::new_with_quality(writer, quality)
+                            .encode_image(&image)
+                            .map_err(error("Couldn't write", &path))?;
+                    },
+                    None => image.save(&path).map_err(error("Couldn't write", &path))?,
+                }
+                Ok(file)
+            },
+        }
+    }
+
+    // Steps through `stream` from its current frame, exporting the frames
+    // that `keep` accepts until it returns `None` or the stream ends.
+    // Returns the exported rows, and the timestamp of the frame that ended
+    // the export, if any.
+    fn export<F>(&self, stream: &Stream, sensor_type: SensorType, mut keep: F) -> Result<(Vec<Row>, Option<u64>), Status>
+        where F: FnMut(usize, u64) -> Option<bool>
+    {
+        let mut rows: Vec<Row> = Vec::new();
+        loop {
+            match openni2::wait_for_any_stream(&[stream], END_OF_STREAM) {
+                Ok(_) => {},
+                Err(Status::TimeOut) => return Ok((rows, None)),
+                Err(err) => return Err(err),
+            }
+            let frame = stream.read_frame_any()?;
+            let (index, timestamp) = (frame.index(), frame.timestamp());
+            // Stop rather than export a recording again if it repeats
+            if rows.last().is_some_and(|last| index <= last.index) {
+                return Ok((rows, None));
+            }
+            match keep(index, timestamp) {
+                None => return Ok((rows, Some(timestamp))),
+                Some(false) => continue,
+                Some(true) => {},
+            }
+            let file = self.write(sensor_type, frame)?;
+            rows.push(Row { index, timestamp, file });
+        }
+    }
+}
+
+fn run(options: Options) -> Result<(), Status> {
+    let device = if options.input.is_dir() {
+        Device::open_image_sequence(&options.input)?
+    } else {
+        openni2::init()?;
+        let uri = options.input.to_str().ok_or_else(|| Status::Error(format!("{} isn't valid UTF-8", options.input.display())))?;
+        Device::open_uri(uri)?
+    };
+    let playback = device.playback()
+        .map_err(|_| Status::Error(format!("{} isn't a recording", options.input.display())))?;
+    // Not every recording supports turning repeat off; `export` stops at
+    // the first repeated frame anyway.
+    let _ = playback.set_repeat(false);
+    playback.set_manual_stepping(true)?;
+
+    let create = |sensor_type, wanted| -> Result<Option<Stream>, Status> {
+        if wanted && device.get_sensor_info(sensor_type).is_some() {
+            let stream = device.create_stream(sensor_type)?;
+            stream.start()?;
+            Ok(Some(stream))
+        } else {
+            Ok(None)
+        }
+    };
+    let depth = create(SensorType::DEPTH, options.depth)?;
+    let color = create(SensorType::COLOR, options.color)?;
+
+    let depth_range = match (options.depth_range, &depth) {
+        (Some(range), _) => range,
+        // The stream reports its range in its own units, but frames are
+        // colorized in millimeters
+        (None, Some(depth)) => match (DepthRange::from_stream(depth), depth.get_video_mode()) {
+            (Ok(range), Ok(mode)) if mode.pixel_format == PixelFormat::DEPTH_100_UM => {
+                DepthRange::new(depth_100_um_to_mm(range.min), depth_100_um_to_mm(range.max))
+            },
+            (range, _) => range.unwrap_or_else(|_| DepthRange::new(0, 10_000)),
+        },
+        (None, None) => DepthRange::new(0, 10_000),
+    };
+    let mut streams = Vec::new();
+    if let Some(ref stream) = depth {
+        fs::create_dir_all(options.output.join("depth")).map_err(error("Couldn't create", &options.output))?;
+        if options.colorize.is_some() {
+            fs::create_dir_all(options.output.join("depth_colorized")).map_err(error("Couldn't create", &options.output))?;
+        }
+        streams.push((stream, SensorType::DEPTH, "depth"));
+    }
+    if let Some(ref stream) = color {
+        fs::create_dir_all(options.output.join("rgb")).map_err(error("Couldn't create", &options.output))?;
+        streams.push((stream, SensorType::COLOR, "rgb"));
+    }
+    if streams.is_empty() {
+        return Err(Status::Error("There are no depth or color frames to export".to_owned()));
+    }
+    let (from, to) = (options.from, options.to);
+    let exporter = Exporter { options, depth_range };
+
+    // The first stream decides which stretch of the recording is exported
+    let (primary, primary_type, primary_name) = streams[0];
+    if let Some(from) = from {
+        playback.seek(primary, from as i32)?;
+    }
+    let (rows, end) = exporter.export(primary, primary_type, |index, _| {
+        if to.is_some_and(|to| index > to) {
+            None
+        } else {
+            Some(from.is_none_or(|from| index >= from))
+        }
+    })?;
+    write_csv(&exporter.options.output.join(format!("{}.csv", primary_name)), &rows)?;
+    println!("Exported {} {} frames", rows.len(), primary_name);
+
+    for &(stream, sensor_type, name) in &streams[1..] {
+        let first = match rows.first() {
+            Some(first) => first,
+            None => break,
+        };
+        // Seeking the first stream moves the others to the same time
+        playback.seek(primary, first.index as i32)?;
+        let start = first.timestamp;
+        let (other_rows, _) = exporter.export(stream, sensor_type, |_, timestamp| {
+            if end.is_some_and(|end| timestamp >= end) {
+                None
+            } else {
+                Some(timestamp >= start)
+            }
+        })?;
+        write_csv(&exporter.options.output.join(format!("{}.csv", name)), &other_rows)?;
+        println!("Exported {} {} frames", other_rows.len(), name);
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("oni-export: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        },
+    };
+    if let Err(err) = run(options) {
+        eprintln!("oni-export: {}", err);
+        process::exit(1);
+    }
+}
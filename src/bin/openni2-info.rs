@@ -0,0 +1,372 @@
+//! Lists every OpenNI2 device with its versions, serial number, image
+//! registration support, and each sensor's video modes and supported stream
+//! properties, as text or JSON.
+//!
+//! A device that can't be opened, or a property that can't be read, is
+//! reported alongside the rest rather than ending the listing.
+
+extern crate openni2;
+extern crate openni2_sys;
+
+use std::fmt::Write as FmtWrite;
+use std::{env, process};
+
+use openni2::{Cropping, Device, DeviceInfo, ImageRegistrationMode, SensorType, Status, Stream, VideoMode};
+use openni2_sys::*;
+
+const USAGE: &str = "\
+Usage: openni2-info [OPTIONS] [URI...]
+
+Describes every connected OpenNI2 device, or only the devices (or .oni
+recordings) given by URI: their firmware, driver and hardware versions,
+serial number, image registration support, and each sensor's video modes
+and supported stream properties.
+
+Options:
+  --json      Print a JSON document instead of text
+  -h, --help  Print this message";
+
+const SENSOR_TYPES: [SensorType; 3] = [SensorType::DEPTH, SensorType::COLOR, SensorType::IR];
+
+#[derive(Debug)]
+struct Options {
+    uris: Vec<String>,
+    json: bool,
+}
+
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut options = Options { uris: Vec::new(), json: false };
+    for arg in args {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => options.uris.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+/// The value of a stream property, in the type its getter returns.
+#[derive(Debug)]
+enum Value {
+    Bool(bool),
+    Int(i32),
+    /// An angle in radians.
+    Angle(f32),
+    Mode(VideoMode),
+    Cropping(Option<Cropping>),
+}
+
+type Getter = fn(&Stream) -> Result<Value, Status>;
+type Properties = Vec<(&'static str, Result<Value, Status>)>;
+
+static STREAM_PROPERTIES: [(&str, OniStreamProperty, Getter); 13] = [
+    ("video_mode", ONI_STREAM_PROPERTY_VIDEO_MODE, |s| s.get_video_mode().map(Value::Mode)),
+    ("horizontal_fov", ONI_STREAM_PROPERTY_HORIZONTAL_FOV, |s| s.get_horizontal_fov().map(Value::Angle)),
+    ("vertical_fov", ONI_STREAM_PROPERTY_VERTICAL_FOV, |s| s.get_vertical_fov().map(Value::Angle)),
+    ("min_value", ONI_STREAM_PROPERTY_MIN_VALUE, |s| s.get_min_value().map(Value::Int)),
+    ("max_value", ONI_STREAM_PROPERTY_MAX_VALUE, |s| s.get_max_value().map(Value::Int)),
+    ("stride", ONI_STREAM_PROPERTY_STRIDE, |s| s.get_stride().map(Value::Int)),
+    ("cropping", ONI_STREAM_PROPERTY_CROPPING, |s| s.get_cropping().map(Value::Cropping)),
+    ("mirroring", ONI_STREAM_PROPERTY_MIRRORING, |s| s.get_mirroring().map(Value::Bool)),
+    ("number_of_frames", ONI_STREAM_PROPERTY_NUMBER_OF_FRAMES, |s| s.get_number_of_frames().map(Value::Int)),
+    ("auto_white_balance", ONI_STREAM_PROPERTY_AUTO_WHITE_BALANCE, |s| s.get_auto_white_balance().map(Value::Bool)),
+    ("auto_exposure", ONI_STREAM_PROPERTY_AUTO_EXPOSURE, |s| s.get_auto_exposure().map(Value::Bool)),
+    ("exposure", ONI_STREAM_PROPERTY_EXPOSURE, |s| s.get_exposure().map(Value::Int)),
+    ("gain", ONI_STREAM_PROPERTY_GAIN, |s| s.get_gain().map(Value::Int)),
+];
+
+#[derive(Debug)]
+struct DeviceReport {
+    info: DeviceInfo,
+    details: Result<DeviceDetails, Status>,
+}
+
+#[derive(Debug)]
+struct DeviceDetails {
+    firmware_version: Result<String, Status>,
+    driver_version: Result<OniVersion, Status>,
+    hardware_version: Result<i32, Status>,
+    serial_number: Result<String, Status>,
+    registration_supported: bool,
+    registration: Result<bool, Status>,
+    sensors: Vec<SensorReport>,
+}
+
+#[derive(Debug)]
+struct SensorReport {
+    sensor_type: SensorType,
+    video_modes: Vec<VideoMode>,
+    /// Supported properties and their current values, or why a stream
+    /// couldn't be created to read them.
+    properties: Result<Properties, Status>,
+}
+
+fn inspect(device: &Device, info: DeviceInfo) -> DeviceReport {
+    let sensors = SENSOR_TYPES.iter()
+        .filter_map(|&sensor_type| device.get_sensor_info(sensor_type))
+        .map(|sensor_info| SensorReport {
+            sensor_type: sensor_info.sensor_type,
+            video_modes: sensor_info.video_modes,
+            properties: device.create_stream(sensor_info.sensor_type).map(|stream| {
+                STREAM_PROPERTIES.iter()
+                    .filter(|&&(_, property, _)| stream.is_property_supported(property))
+                    .map(|&(name, _, get)| (name, get(&stream)))
+                    .collect()
+            }),
+        })
+        .collect();
+    let registration_supported = device.is_image_registration_mode_supported(ImageRegistrationMode::DEPTH_TO_COLOR);
+    DeviceReport {
+        info,
+        details: Ok(DeviceDetails {
+            firmware_version: device.get_firmware_version(),
+            driver_version: device.get_driver_version(),
+            hardware_version: device.get_hardware_version(),
+            serial_number: device.get_serial_number(),
+            registration_supported,
+            registration: device.get_image_registration(),
+            sensors,
+        }),
+    }
+}
+
+fn describe_uri(uri: &str) -> DeviceReport {
+    match Device::open_uri(uri) {
+        Ok(device) => {
+            let info = device.info().unwrap_or_else(|_| unknown_device(uri));
+            inspect(&device, info)
+        },
+        Err(err) => DeviceReport { info: unknown_device(uri), details: Err(err) },
+    }
+}
+
+fn unknown_device(uri: &str) -> DeviceInfo {
+    DeviceInfo {
+        uri: uri.to_string(),
+        vendor: String::new(),
+        name: String::new(),
+        usb_vendor_id: 0,
+        usb_product_id: 0,
+    }
+}
+
+fn error_text(status: &Status) -> String {
+    match *status {
+        Status::Error(ref message) => message.clone(),
+        ref other => format!("{:?}", other),
+    }
+}
+
+fn version_text(version: &OniVersion) -> String {
+    format!("{}.{}.{}.{}", version.major, version.minor, version.maintenance, version.build)
+}
+
+fn mode_text(mode: &VideoMode) -> String {
+    format!("{:?} {}x{} @ {} fps", mode.pixel_format, mode.resolution_x, mode.resolution_y, mode.fps)
+}
+
+fn value_text(value: &Value) -> String {
+    match *value {
+        Value::Bool(b) => if b { "on" } else { "off" }.to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::Angle(radians) => format!("{:.4} rad ({:.1}°)", radians, radians.to_degrees()),
+        Value::Mode(ref mode) => mode_text(mode),
+        Value::Cropping(None) => "none".to_string(),
+        Value::Cropping(Some(c)) => format!("{}x{} at ({}, {})", c.width, c.height, c.origin_x, c.origin_y),
+    }
+}
+
+fn result_text<T, F: FnOnce(&T) -> String>(result: &Result<T, Status>, f: F) -> String {
+    match *result {
+        Ok(ref value) => f(value),
+        Err(ref err) => format!("unavailable ({})", error_text(err)),
+    }
+}
+
+fn print_text(version: &OniVersion, reports: &[DeviceReport]) {
+    let plural = if reports.len() == 1 { "" } else { "s" };
+    println!("OpenNI {}, {} device{}", version_text(version), reports.len(), plural);
+    for report in reports {
+        let info = &report.info;
+        println!();
+        if info.name.is_empty() {
+            println!("{}", info.uri);
+        } else {
+            println!("{} {} ({})", info.vendor, info.name, info.uri);
+            println!("  USB ID:             {:04x}:{:04x}", info.usb_vendor_id, info.usb_product_id);
+        }
+        let details = match report.details {
+            Ok(ref details) => details,
+            Err(ref err) => {
+                println!("  Couldn't open device: {}", error_text(err));
+                continue;
+            },
+        };
+        println!("  Firmware version:   {}", result_text(&details.firmware_version, |v| v.clone()));
+        println!("  Driver version:     {}", result_text(&details.driver_version, version_text));
+        println!("  Hardware version:   {}", result_text(&details.hardware_version, |v| v.to_string()));
+        println!("  Serial number:      {}", result_text(&details.serial_number, |v| v.clone()));
+        if details.registration_supported {
+            let current = result_text(&details.registration, |&on| if on { "on" } else { "off" }.to_string());
+            println!("  Image registration: supported, {}", current);
+        } else {
+            println!("  Image registration: not supported");
+        }
+        for sensor in &details.sensors {
+            println!("  {:?} sensor", sensor.sensor_type);
+            println!("    Video modes:");
+            for mode in &sensor.video_modes {
+                println!("      {}", mode_text(mode));
+            }
+            match sensor.properties {
+                Ok(ref properties) => {
+                    println!("    Properties:");
+                    for &(name, ref value) in properties {
+                        println!("      {:<20}{}", name, result_text(value, value_text));
+                    }
+                },
+                Err(ref err) => println!("    Couldn't create stream: {}", error_text(err)),
+            }
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_mode(mode: &VideoMode) -> String {
+    format!(
+        "{{\"pixel_format\": {}, \"width\": {}, \"height\": {}, \"fps\": {}}}",
+        json_string(&format!("{:?}", mode.pixel_format)), mode.resolution_x, mode.resolution_y, mode.fps,
+    )
+}
+
+fn json_value(value: &Value) -> String {
+    match *value {
+        Value::Bool(b) => b.to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::Angle(radians) if radians.is_finite() => radians.to_string(),
+        Value::Angle(_) => "null".to_string(),
+        Value::Mode(ref mode) => json_mode(mode),
+        Value::Cropping(None) => "null".to_string(),
+        Value::Cropping(Some(c)) => format!(
+            "{{\"width\": {}, \"height\": {}, \"origin_x\": {}, \"origin_y\": {}}}",
+            c.width, c.height, c.origin_x, c.origin_y,
+        ),
+    }
+}
+
+/// Errors become `{"error": "..."}` in place of the value.
+fn json_result<T, F: FnOnce(&T) -> String>(result: &Result<T, Status>, f: F) -> String {
+    match *result {
+        Ok(ref value) => f(value),
+        Err(ref err) => format!("{{\"error\": {}}}", json_string(&error_text(err))),
+    }
+}
+
+fn json_document(version: &OniVersion, reports: &[DeviceReport]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{{");
+    let _ = writeln!(out, "  \"openni_version\": {},", json_string(&version_text(version)));
+    let _ = write!(out, "  \"devices\": [");
+    for (i, report) in reports.iter().enumerate() {
+        let info = &report.info;
+        let _ = write!(out, "{}\n    {{\n", if i == 0 { "" } else { "," });
+        let _ = writeln!(out, "      \"uri\": {},", json_string(&info.uri));
+        let _ = writeln!(out, "      \"vendor\": {},", json_string(&info.vendor));
+        let _ = writeln!(out, "      \"name\": {},", json_string(&info.name));
+        let _ = writeln!(out, "      \"usb_vendor_id\": {},", info.usb_vendor_id);
+        let _ = write!(out, "      \"usb_product_id\": {}", info.usb_product_id);
+        match report.details {
+            Err(ref err) => {
+                let _ = write!(out, ",\n      \"error\": {}", json_string(&error_text(err)));
+            },
+            Ok(ref details) => {
+                let _ = write!(out, ",\n      \"firmware_version\": {}", json_result(&details.firmware_version, |v| json_string(v)));
+                let _ = write!(out, ",\n      \"driver_version\": {}", json_result(&details.driver_version, |v| json_string(&version_text(v))));
+                let _ = write!(out, ",\n      \"hardware_version\": {}", json_result(&details.hardware_version, |v| v.to_string()));
+                let _ = write!(out, ",\n      \"serial_number\": {}", json_result(&details.serial_number, |v| json_string(v)));
+                let _ = write!(out, ",\n      \"image_registration_supported\": {}", details.registration_supported);
+                let _ = write!(out, ",\n      \"image_registration\": {}", json_result(&details.registration, |v| v.to_string()));
+                let _ = write!(out, ",\n      \"sensors\": [");
+                for (j, sensor) in details.sensors.iter().enumerate() {
+                    let _ = write!(out, "{}\n        {{\n", if j == 0 { "" } else { "," });
+                    let _ = writeln!(out, "          \"sensor_type\": {},", json_string(&format!("{:?}", sensor.sensor_type)));
+                    let modes: Vec<String> = sensor.video_modes.iter()
+                        .map(|mode| format!("\n            {}", json_mode(mode)))
+                        .collect();
+                    let _ = writeln!(out, "          \"video_modes\": [{}\n          ],", modes.join(","));
+                    let properties = json_result(&sensor.properties, |properties| {
+                        let fields: Vec<String> = properties.iter()
+                            .map(|&(name, ref value)| format!("\n            {}: {}", json_string(name), json_result(value, json_value)))
+                            .collect();
+                        format!("{{{}\n          }}", fields.join(","))
+                    });
+                    let _ = write!(out, "          \"properties\": {}\n        }}", properties);
+                }
+                let _ = write!(out, "{}]", if details.sensors.is_empty() { "" } else { "\n      " });
+            },
+        }
+        let _ = write!(out, "\n    }}");
+    }
+    let _ = writeln!(out, "{}]", if reports.is_empty() { "" } else { "\n  " });
+    let _ = write!(out, "}}");
+    out
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("openni2-info: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        },
+    };
+    if let Err(err) = openni2::init() {
+        eprintln!("openni2-info: {}", err);
+        process::exit(1);
+    }
+
+    let reports: Vec<DeviceReport> = if options.uris.is_empty() {
+        openni2::get_device_list().into_iter()
+            .map(|info| match Device::open_uri(&info.uri) {
+                Ok(device) => inspect(&device, info),
+                Err(err) => DeviceReport { info, details: Err(err) },
+            })
+            .collect()
+    } else {
+        options.uris.iter().map(|uri| describe_uri(uri)).collect()
+    };
+
+    let version = openni2::get_version();
+    if options.json {
+        println!("{}", json_document(&version, &reports));
+    } else {
+        print_text(&version, &reports);
+    }
+    openni2::shutdown();
+    if reports.iter().any(|report| report.details.is_err()) {
+        process::exit(1);
+    }
+}
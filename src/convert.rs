@@ -0,0 +1,155 @@
+//! Conversion of frames in any `PixelFormat` into displayable buffers.
+//!
+//! Every function takes a `DynFrame`, so it works with whatever video mode a
+//! stream happens to be in:
+//!
+//! * `RGB888` and `GRAY8` frames are copied.
+//! * `YUV422` (UYVY byte order) and `YUYV` frames are converted with the
+//!   full-range BT.601 transform.
+//! * `GRAY16`, depth and shift frames are scaled so that the largest value in
+//!   the frame is white. See the `visualize` module for depth-specific
+//!   renderings.
+//! * `JPEG` frames are decoded if the `jpeg` cargo feature is enabled, and
+//!   return `Err(Status::NotSupported)` otherwise.
+//!
+//! # Example
+//! ```
+//! # use openni2::{Device, SensorType, convert};
+//! # fn main() -> Result<(), openni2::Status> {
+//! # /*
+//! let device = Device::open_default()?;
+//! # */
+//! # let device = Device::from_backend(openni2::mock::MockDevice::new());
+//! let stream = device.create_stream(SensorType::COLOR)?;
+//! stream.start()?;
+//! let rgb = convert::to_rgb888(&stream.read_frame_any()?)?;
+//! println!("{:?}", rgb[0]);
+//! # Ok(())
+//! # }
+//! ```
+
+use openni2_sys::*;
+use frame::DynFrame;
+use types::{Status, PixelFormat};
+
+/// Converts a frame into 24-bit RGB pixels, in row order.
+pub fn to_rgb888(frame: &DynFrame) -> Result<Vec<OniRGB888Pixel>, Status> {
+    let pixel_count = frame.width() as usize * frame.height() as usize;
+    let data = frame.data();
+    let pixels = match frame.pixel_format() {
+        PixelFormat::RGB888 => {
+            check_size(data, pixel_count * 3)?;
+            data.chunks(3).map(|rgb| OniRGB888Pixel { r: rgb[0], g: rgb[1], b: rgb[2] }).collect()
+        },
+        PixelFormat::YUV422 => yuv_to_rgb(data, pixel_count, [1, 0, 3, 2])?,
+        PixelFormat::YUYV => yuv_to_rgb(data, pixel_count, [0, 1, 2, 3])?,
+        PixelFormat::JPEG => decode_jpeg(data, pixel_count)?,
+        _ => to_gray8(frame)?.into_iter().map(|y| OniRGB888Pixel { r: y, g: y, b: y }).collect(),
+    };
+    Ok(pixels)
+}
+
+/// Converts a frame into RGBA pixels with an opaque alpha channel, in row order.
+pub fn to_rgba(frame: &DynFrame) -> Result<Vec<[u8; 4]>, Status> {
+    Ok(to_rgb888(frame)?.into_iter().map(|px| [px.r, px.g, px.b, 255]).collect())
+}
+
+/// Converts a frame into 8-bit grayscale pixels, in row order. Color frames
+/// are reduced to their luma.
+pub fn to_gray8(frame: &DynFrame) -> Result<Vec<OniGrayscale8Pixel>, Status> {
+    let pixel_count = frame.width() as usize * frame.height() as usize;
+    let data = frame.data();
+    match frame.pixel_format() {
+        PixelFormat::GRAY8 => {
+            check_size(data, pixel_count)?;
+            Ok(data.to_vec())
+        },
+        PixelFormat::GRAY16 |
+        PixelFormat::DEPTH_1_MM |
+        PixelFormat::DEPTH_100_UM |
+        PixelFormat::SHIFT_9_2 |
+        PixelFormat::SHIFT_9_3 => {
+            check_size(data, pixel_count * 2)?;
+            let values: Vec<u16> = data.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+            let max = u32::from(values.iter().cloned().max().unwrap_or(0).max(1));
+            Ok(values.into_iter().map(|value| (u32::from(value) * 255 / max) as u8).collect())
+        },
+        PixelFormat::YUV422 => yuv_luma(data, pixel_count, [1, 3]),
+        PixelFormat::YUYV => yuv_luma(data, pixel_count, [0, 2]),
+        PixelFormat::RGB888 | PixelFormat::JPEG => {
+            Ok(to_rgb888(frame)?.into_iter().map(luma).collect())
+        },
+    }
+}
+
+fn check_size(data: &[u8], expected: usize) -> Result<(), Status> {
+    if data.len() < expected {
+        Err(Status::Error(format!("Frame has {} bytes of data, expected {}", data.len(), expected)))
+    } else {
+        Ok(())
+    }
+}
+
+fn luma(px: OniRGB888Pixel) -> u8 {
+    ((299 * u32::from(px.r) + 587 * u32::from(px.g) + 114 * u32::from(px.b)) / 1000) as u8
+}
+
+// Each 4 bytes of YUV 4:2:2 data hold two pixels that share chroma. `order`
+// gives the positions of y1, u, y2, v within those 4 bytes.
+fn yuv_to_rgb(data: &[u8], pixel_count: usize, order: [usize; 4]) -> Result<Vec<OniRGB888Pixel>, Status> {
+    check_size(data, pixel_count * 2)?;
+    let mut pixels = Vec::with_capacity(pixel_count);
+    for quad in data[..pixel_count * 2].chunks(4) {
+        let (u, v) = (quad[order[1]], quad[order[3]]);
+        pixels.push(yuv_pixel(quad[order[0]], u, v));
+        pixels.push(yuv_pixel(quad[order[2]], u, v));
+    }
+    pixels.truncate(pixel_count);
+    Ok(pixels)
+}
+
+fn yuv_luma(data: &[u8], pixel_count: usize, order: [usize; 2]) -> Result<Vec<u8>, Status> {
+    check_size(data, pixel_count * 2)?;
+    let mut pixels = Vec::with_capacity(pixel_count);
+    for quad in data[..pixel_count * 2].chunks(4) {
+        pixels.push(quad[order[0]]);
+        pixels.push(quad[order[1]]);
+    }
+    pixels.truncate(pixel_count);
+    Ok(pixels)
+}
+
+fn yuv_pixel(y: u8, u: u8, v: u8) -> OniRGB888Pixel {
+    // Full-range BT.601, in 16.16 fixed point
+    let (y, u, v) = (i32::from(y) << 16, i32::from(u) - 128, i32::from(v) - 128);
+    let clamp = |value: i32| (value >> 16).clamp(0, 255) as u8;
+    OniRGB888Pixel {
+        r: clamp(y + 91_881 * v),
+        g: clamp(y - 22_554 * u - 46_802 * v),
+        b: clamp(y + 116_130 * u),
+    }
+}
+
+#[cfg(feature = "jpeg")]
+fn decode_jpeg(data: &[u8], pixel_count: usize) -> Result<Vec<OniRGB888Pixel>, Status> {
+    use jpeg_decoder::{Decoder, PixelFormat as JpegPixelFormat};
+
+    let mut decoder = Decoder::new(data);
+    let decoded = decoder.decode().map_err(|error| Status::Error(format!("Couldn't decode JPEG frame: {}", error)))?;
+    let info = decoder.info().ok_or_else(|| Status::Error(String::from("Couldn't decode JPEG frame")))?;
+    let pixels: Vec<OniRGB888Pixel> = match info.pixel_format {
+        JpegPixelFormat::RGB24 => decoded.chunks(3).map(|rgb| OniRGB888Pixel { r: rgb[0], g: rgb[1], b: rgb[2] }).collect(),
+        JpegPixelFormat::L8 => decoded.into_iter().map(|y| OniRGB888Pixel { r: y, g: y, b: y }).collect(),
+        JpegPixelFormat::L16 => decoded.chunks(2).map(|pair| OniRGB888Pixel { r: pair[0], g: pair[0], b: pair[0] }).collect(),
+        JpegPixelFormat::CMYK32 => return Err(Status::NotSupported),
+    };
+    if pixels.len() != pixel_count {
+        return Err(Status::Error(format!("Decoded JPEG frame has {} pixels, expected {}", pixels.len(), pixel_count)));
+    }
+    Ok(pixels)
+}
+
+#[cfg(not(feature = "jpeg"))]
+fn decode_jpeg(_data: &[u8], _pixel_count: usize) -> Result<Vec<OniRGB888Pixel>, Status> {
+    Err(Status::NotSupported)
+}
@@ -0,0 +1,110 @@
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError, TryLockError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, ThreadId};
+
+// A registered callback's closure, passed to OpenNI2 or a backend as the
+// callback's cookie. OpenNI2 doesn't wait for an event that's being raised
+// when a callback is unregistered, so the callback may still run afterwards,
+// and finds the cookie empty. Emptied cookies go back to a `CookiePool`.
+pub(crate) struct Cookie<T: ?Sized> {
+    closure: Mutex<Option<Box<T>>>,
+    // The thread calling the closure, if any
+    running_on: Mutex<Option<ThreadId>>,
+    // Set when the cookie is emptied from inside its own closure, which is
+    // then dropped once it returns
+    emptied: AtomicBool,
+}
+
+impl<T: ?Sized> Cookie<T> {
+    // Calls the closure, unless the cookie has been emptied.
+    pub(crate) fn call<F: FnOnce(&mut T)>(&self, call: F) {
+        let mut closure = match self.closure.lock() {
+            Ok(closure) => closure,
+            Err(_) => return,
+        };
+        if let Some(ref mut closure) = *closure {
+            *self.running_on.lock().unwrap_or_else(PoisonError::into_inner) = Some(thread::current().id());
+            // Unwinding into OpenNI2's C++ code is undefined behavior
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| call(closure)));
+            *self.running_on.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        }
+        if self.emptied.swap(false, Ordering::SeqCst) {
+            *closure = None;
+        }
+    }
+
+    // Drops the closure, waiting for it to return if another thread is
+    // calling it. Called from inside the closure, it is dropped once it
+    // returns instead.
+    pub(crate) fn empty(&self) {
+        let closure = match self.closure.try_lock() {
+            Ok(mut closure) => closure.take(),
+            Err(TryLockError::Poisoned(closure)) => closure.into_inner().take(),
+            Err(TryLockError::WouldBlock) => {
+                if *self.running_on.lock().unwrap_or_else(PoisonError::into_inner) == Some(thread::current().id()) {
+                    self.emptied.store(true, Ordering::SeqCst);
+                    return;
+                }
+                self.closure.lock().unwrap_or_else(PoisonError::into_inner).take()
+            },
+        };
+        drop(closure);
+    }
+}
+
+// Cookies of unregistered callbacks. A late event can still read them, so
+// they're reused by later registrations instead of being freed, until
+// nothing can call back anymore. A late event may then call the closure of
+// the registration that reused the cookie.
+pub(crate) struct CookiePool<T: ?Sized> {
+    cookies: Mutex<Vec<*mut Cookie<T>>>,
+}
+
+// The pool only hands out cookies, whose closures are behind their locks.
+unsafe impl<T: ?Sized + Send> Send for CookiePool<T> {}
+unsafe impl<T: ?Sized + Send> Sync for CookiePool<T> {}
+
+impl<T: ?Sized> CookiePool<T> {
+    pub(crate) const fn new() -> Self {
+        CookiePool { cookies: Mutex::new(Vec::new()) }
+    }
+
+    // Returns a cookie holding `closure`, reusing an emptied one that isn't
+    // being called back with.
+    pub(crate) fn cookie(&self, closure: Box<T>) -> *mut Cookie<T> {
+        let mut cookies = self.cookies.lock().unwrap_or_else(PoisonError::into_inner);
+        let reusable = cookies.iter().position(|&cookie| {
+            unsafe { &*cookie }.closure.try_lock().map(|closure| closure.is_none()).unwrap_or(false)
+        });
+        match reusable {
+            Some(index) => {
+                let cookie = cookies.swap_remove(index);
+                *unsafe { &*cookie }.closure.lock().unwrap_or_else(PoisonError::into_inner) = Some(closure);
+                cookie
+            },
+            None => Box::into_raw(Box::new(Cookie {
+                closure: Mutex::new(Some(closure)),
+                running_on: Mutex::new(None),
+                emptied: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    // Takes back the cookie of an unregistered callback, after emptying it.
+    pub(crate) fn retire(&self, cookie: *mut Cookie<T>) {
+        unsafe { &*cookie }.empty();
+        self.cookies.lock().unwrap_or_else(PoisonError::into_inner).push(cookie);
+    }
+
+    // Frees every retired cookie.
+    //
+    // Safety: nothing may call back with them anymore.
+    pub(crate) unsafe fn free(&self) {
+        let cookies = mem::take(&mut *self.cookies.lock().unwrap_or_else(PoisonError::into_inner));
+        for cookie in cookies {
+            drop(Box::from_raw(cookie));
+        }
+    }
+}
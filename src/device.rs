@@ -1,19 +1,31 @@
-use std::os::raw::{c_int, c_char, c_float, c_void};
+use std::os::raw::{c_int, c_char, c_float, c_double, c_void};
 use std::{ptr, fmt, mem, slice};
 use std::ffi::{CString, CStr};
 
 use openni2_sys::*;
 use types::{Status, SensorType, ImageRegistrationMode, VideoMode, SensorInfo};
 use stream::Stream;
+use backend::DeviceBackend;
+use playback::PlaybackControl;
+use typed_stream::{TypedStream, StreamFormat};
 
 /// An open device. The device is closed when this struct drops out of scope.
 pub struct Device {
-    pub(crate) handle: OniDeviceHandle,
+    pub(crate) handle: DeviceHandle,
+}
+
+// A device is either opened through OpenNI2 or wraps a `DeviceBackend`.
+pub(crate) enum DeviceHandle {
+    OpenNI(OniDeviceHandle),
+    Backend(Box<dyn DeviceBackend>),
 }
 
 impl fmt::Debug for Device {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Device {{ handle: OniDeviceHandle({:p}) }}", &self.handle)
+        match self.handle {
+            DeviceHandle::OpenNI(ref handle) => write!(f, "Device {{ handle: OniDeviceHandle({:p}) }}", handle),
+            DeviceHandle::Backend(_) => write!(f, "Device {{ handle: DeviceBackend }}"),
+        }
     }
 }
 
@@ -68,11 +80,32 @@ impl Device {
         };
         let status = unsafe { oniDeviceOpen(uri_ptr, &mut handle ) }.into();
         match status {
-            Status::Ok => Ok(Device { handle }),
+            Status::Ok => Ok(Device { handle: DeviceHandle::OpenNI(handle) }),
             _ => Err(status),
         }
     }
 
+    /// Wraps a `DeviceBackend`, such as a `mock::MockDevice`, in a `Device`
+    /// so that it can be used like one opened through OpenNI2. Doesn't need
+    /// `openni2::init` to be called first.
+    ///
+    /// # Example
+    /// ```
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # use openni2::mock::MockDevice;
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// let device = Device::from_backend(MockDevice::new());
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// stream.start()?;
+    /// let frame = stream.read_frame::<OniDepthPixel>()?;
+    /// assert_eq!(frame.index(), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_backend<B: DeviceBackend + 'static>(backend: B) -> Self {
+        Device { handle: DeviceHandle::Backend(Box::new(backend)) }
+    }
+
     /// Returns a `DeviceInfo` that describes the device.
     ///
     /// # Example
@@ -90,8 +123,12 @@ impl Device {
     /// # }
     /// ```
     pub fn info(&self) -> Result<DeviceInfo, Status> {
+        let handle = match self.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return backend.info(),
+        };
         let mut oni_info = mem::MaybeUninit::<OniDeviceInfo>::uninit();
-        let status: Status = unsafe { oniDeviceGetInfo(self.handle, oni_info.as_mut_ptr()) }.into();
+        let status: Status = unsafe { oniDeviceGetInfo(handle, oni_info.as_mut_ptr()) }.into();
         match status {
             Status::Ok => Ok(unsafe { oni_info.assume_init() }.into()),
             _ => Err(status),
@@ -113,8 +150,12 @@ impl Device {
     /// # }
     /// ```
     pub fn get_sensor_info(&self, sensor_type: SensorType) -> Option<SensorInfo> {
+        let handle = match self.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return backend.sensor_info(sensor_type),
+        };
         unsafe {
-            let ptr: *const OniSensorInfo = oniDeviceGetSensorInfo(self.handle, sensor_type as i32);
+            let ptr: *const OniSensorInfo = oniDeviceGetSensorInfo(handle, sensor_type as i32);
             if ptr.is_null() {
                 None
             } else {
@@ -138,10 +179,13 @@ impl Device {
     /// `OniGrayscale16Pixel`, `OniGrayscale8Pixel`, `OniRGB888Pixel`, and `OniYUV422DoublePixel`.
     ///
     /// # Example
-    /// ```no_run
+    /// ```
     /// use openni2::{Device, SensorType, OniRGB888Pixel, OniDepthPixel};
     /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
     /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
     /// let color = device.create_stream(SensorType::COLOR)?;
     /// let depth = device.create_stream(SensorType::DEPTH)?;
     /// # Ok(())
@@ -151,25 +195,54 @@ impl Device {
         Stream::create(&self, sensor_type)
     }
 
+    /// Creates a `TypedStream` for a given `SensorType`, with the pixel format
+    /// given by the type parameter. See `TypedStream::from_stream`.
+    ///
+    /// # Example
+    /// ```
+    /// use openni2::{Device, SensorType, Rgb888, DepthMm};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
+    /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let color = device.create_typed_stream::<Rgb888>(SensorType::COLOR)?;
+    /// let depth = device.create_typed_stream::<DepthMm>(SensorType::DEPTH)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_typed_stream<F: StreamFormat>(&self, sensor_type: SensorType) -> Result<TypedStream<'_, F>, Status> {
+        TypedStream::from_stream(self.create_stream(sensor_type)?)
+    }
+
     pub fn color_depth_sync(&self) -> bool {
-        unsafe {
-            oniDeviceGetDepthColorSyncEnabled(self.handle) != 0
+        match self.handle {
+            DeviceHandle::OpenNI(handle) => unsafe {
+                oniDeviceGetDepthColorSyncEnabled(handle) != 0
+            },
+            DeviceHandle::Backend(ref backend) => backend.color_depth_sync(),
         }
     }
 
     pub fn enable_color_depth_sync(&mut self, enabled: bool) -> Status {
+        let handle = match self.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return backend.enable_color_depth_sync(enabled),
+        };
         if enabled {
-            unsafe { oniDeviceEnableDepthColorSync(self.handle) }.into()
+            unsafe { oniDeviceEnableDepthColorSync(handle) }.into()
         } else {
-            unsafe { oniDeviceDisableDepthColorSync(self.handle) }
+            unsafe { oniDeviceDisableDepthColorSync(handle) }
             Status::Ok
         }
     }
 
     #[doc(hidden)]
     pub fn is_property_supported(&self, property: OniDeviceProperty) -> bool {
-        let res = unsafe { oniDeviceIsPropertySupported(self.handle, property) };
-        res == 1
+        match self.handle {
+            DeviceHandle::OpenNI(handle) => unsafe { oniDeviceIsPropertySupported(handle, property) == 1 },
+            DeviceHandle::Backend(ref backend) => backend.is_property_supported(property),
+        }
     }
 
     /// Returns the device's firmware version.
@@ -245,7 +318,10 @@ impl Device {
     /// The only modes supported are `ImageRegistrationMode::OFF` (do nothing), and
     /// `ImageRegistrationMode::DEPTH_TO_COLOR` (aligns depth and color streams).
     pub fn is_image_registration_mode_supported(&self, mode: ImageRegistrationMode) -> bool {
-        unsafe { oniDeviceIsImageRegistrationModeSupported(self.handle, mode as i32) != 0 }
+        match self.handle {
+            DeviceHandle::OpenNI(handle) => unsafe { oniDeviceIsImageRegistrationModeSupported(handle, mode as i32) != 0 },
+            DeviceHandle::Backend(ref backend) => backend.is_image_registration_mode_supported(mode),
+        }
     }
 
     /// Returns whether image registration mode is set to resize and align depth and color streams.
@@ -287,16 +363,20 @@ impl Device {
 
     /// Sets the playback repeat for recordings.
     pub fn set_playback_repeat_enabled(&self, value: bool) -> Result<(), Status> {
-        self.set_property(ONI_DEVICE_PROPERTY_PLAYBACK_REPEAT_ENABLED, &value)
+        self.set_property::<c_int>(ONI_DEVICE_PROPERTY_PLAYBACK_REPEAT_ENABLED, &(value as c_int))
     }
 
-    fn get_property<T>(&self, property: OniDeviceProperty) -> Result<T, Status> {
+    fn get_property<T: PropertyData>(&self, property: OniDeviceProperty) -> Result<T, Status> {
+        let handle = match self.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return backend_get(|data| backend.get_property(property, data)),
+        };
         let mut data = mem::MaybeUninit::<T>::uninit();
         let mut len = mem::size_of::<T>() as c_int;
 
         let status = unsafe {
             oniDeviceGetProperty(
-                self.handle,
+                handle,
                 property, data.as_mut_ptr() as *mut c_void,
                 &mut len as *mut c_int,
             )
@@ -308,11 +388,15 @@ impl Device {
         }
     }
 
-    fn set_property<T>(&self, property: OniDeviceProperty, value: &T) -> Result<(), Status> {
+    fn set_property<T: PropertyData>(&self, property: OniDeviceProperty, value: &T) -> Result<(), Status> {
+        let handle = match self.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return backend.set_property(property, as_bytes(value)),
+        };
         let len = mem::size_of::<T>() as c_int;
         let status = unsafe {
             oniDeviceSetProperty(
-                self.handle,
+                handle,
                 property,
                 value as *const T as *const _,
                 len,
@@ -324,25 +408,104 @@ impl Device {
         }
     }
 
-    // TODO: just replace with seek??
-    // pub fn is_command_supported(&self, command: Command) -> bool {
-    //     unsafe { oniDeviceIsCommandSupported(self.handle, command) != 0 }
-    // }
+    /// Returns whether the device supports a command, like `ONI_DEVICE_COMMAND_SEEK`.
+    #[doc(hidden)]
+    pub fn is_command_supported(&self, command: OniDeviceCommand) -> bool {
+        match self.handle {
+            DeviceHandle::OpenNI(handle) => unsafe { oniDeviceIsCommandSupported(handle, command) != 0 },
+            DeviceHandle::Backend(ref backend) => backend.is_command_supported(command),
+        }
+    }
+
+    pub(crate) fn invoke_command<T: PropertyData>(&self, command: OniDeviceCommand, data: &mut T) -> Result<(), Status> {
+        let handle = match self.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return backend.invoke_command(command, as_bytes_mut(data)),
+        };
+        let status = unsafe {
+            oniDeviceInvoke(
+                handle,
+                command,
+                data as *mut T as *mut c_void,
+                mem::size_of::<T>() as c_int,
+            )
+        }.into();
+        match status {
+            Status::Ok => Ok(()),
+            _ => Err(status),
+        }
+    }
 
-    // pub fn invoke_command(&mut self, command: Command) -> Status {
-    //     unsafe { oniDeviceInvoke(self.handle, command, data, dataSize) }.into()
-    // }
+    /// Returns a `PlaybackControl` for seeking and stepping through the
+    /// streams of a device that was opened with a URI pointing to a
+    /// recording file. Returns `Err(Status::NotSupported)` for live devices.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// let device = Device::open_uri("recording.oni")?;
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// let playback = device.playback()?;
+    /// playback.set_manual_stepping(true)?;
+    /// stream.start()?;
+    /// playback.seek(&stream, 100)?;
+    /// let frame = playback.step::<OniDepthPixel>(&stream)?;
+    /// assert_eq!(frame.index(), 100);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn playback(&self) -> Result<PlaybackControl<'_>, Status> {
+        PlaybackControl::new(self)
+    }
 }
 
 impl Drop for Device {
     fn drop(&mut self) {
-        unsafe { oniDeviceClose(self.handle) };
+        if let DeviceHandle::OpenNI(handle) = self.handle {
+            unsafe { oniDeviceClose(handle) };
+        }
+    }
+}
+
+/// Property values and command arguments, plain C structs and numbers,
+/// which a backend reads and writes as bytes.
+///
+/// # Safety
+///
+/// Implementors have no padding bytes, and any bytes of the right length are
+/// a valid value.
+pub(crate) unsafe trait PropertyData: Copy {}
+
+macro_rules! propertyData {
+    ($($data:ty),*) => {
+        $(unsafe impl PropertyData for $data {})*
     }
 }
 
+propertyData!(c_char, c_int, c_float, c_double, u64, OniVersion, OniVideoMode, OniCropping);
+
+unsafe impl<T: PropertyData, const N: usize> PropertyData for [T; N] {}
+
+pub(crate) fn as_bytes<T: PropertyData>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+pub(crate) fn as_bytes_mut<T: PropertyData>(value: &mut T) -> &mut [u8] {
+    unsafe { slice::from_raw_parts_mut(value as *mut T as *mut u8, mem::size_of::<T>()) }
+}
+
+// Reads a property through a backend into a zeroed value, so a backend that
+// writes too little can't leave it uninitialized.
+pub(crate) fn backend_get<T: PropertyData, F: FnOnce(&mut [u8]) -> Result<(), Status>>(get: F) -> Result<T, Status> {
+    let mut value: T = unsafe { mem::zeroed() };
+    get(as_bytes_mut(&mut value))?;
+    Ok(value)
+}
+
 /// A descriptive information struct for a `Device`. Can be obtained for a specific device by
 /// calling `Device::info(&self)`, or as a vector by calling `openni2::get_device_list()`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DeviceInfo {
     /// The identifying URI of the device. Can be passed to `Device::open_uri(&str)` to open
     /// the devcie described by this struct.
@@ -1,28 +1,71 @@
 use openni2_sys::*;
 use std::marker::PhantomData;
-use types::{VideoMode, Pixel, bytes_per_pixel};
-use std::{mem, slice};
+use types::{VideoMode, PixelFormat, Pixel, DepthMmPixel, Depth100UmPixel, ShiftPixel, Gray16Pixel, JpegByte, bytes_per_pixel, pixels_per_frame};
+use stream::Cropping;
+use backend::FrameData;
+use std::{mem, ptr, slice};
+use std::sync::Arc;
+use std::any::type_name;
 
 #[doc(hidden)]
 pub unsafe fn frame_from_pointer<P: Pixel>(frame_pointer: *mut OniFrame) -> Frame<P> {
     assert!(!frame_pointer.is_null());
-    let oni_frame: OniFrame = *frame_pointer;
+    frame_from_source(*frame_pointer, FrameSource::OpenNI(frame_pointer))
+}
+
+#[doc(hidden)]
+pub unsafe fn dyn_frame_from_pointer(frame_pointer: *mut OniFrame) -> DynFrame {
+    assert!(!frame_pointer.is_null());
+    dyn_frame_from_source(*frame_pointer, FrameSource::OpenNI(frame_pointer))
+}
+
+pub(crate) fn frame_from_data<P: Pixel>(data: FrameData) -> Frame<P> {
+    let data = Arc::new(data);
+    frame_from_source(data.oni_frame(), FrameSource::Backend(data))
+}
 
+pub(crate) fn dyn_frame_from_data(data: FrameData) -> DynFrame {
+    let data = Arc::new(data);
+    dyn_frame_from_source(data.oni_frame(), FrameSource::Backend(data))
+}
+
+fn frame_from_source<P: Pixel>(oni_frame: OniFrame, source: FrameSource) -> Frame<P> {
     // Ensure that pixel type P matches the pixel format that the
     // current video mode will return. Compile-time typing is possible
     // but is extremely impractical considering that a stream's video
     // mode can be changed.
-    let type_param_size = mem::size_of::<P>();
-    let pixel_size = bytes_per_pixel(oni_frame.videoMode.pixelFormat.into());
-    assert_eq!(type_param_size, pixel_size, "Size of callback's type parameter ({}) is different than the stream's pixel size reported by OpenNI2 ({}). Did you register the wrong callback on a stream?", type_param_size, pixel_size);
+    let pixel_format: PixelFormat = oni_frame.videoMode.pixelFormat.into();
+    assert!(P::supports_format(pixel_format), "Pixel type parameter {} can't hold pixels of the stream's pixel format {:?}. Did you register the wrong callback on a stream?", type_name::<P>(), pixel_format);
+
+    Frame::from_parts(oni_frame, source)
+}
 
-    Frame {
-        oni_frame,
-        frame_pointer,
-        _pixel_type: PhantomData,
+fn dyn_frame_from_source(oni_frame: OniFrame, source: FrameSource) -> DynFrame {
+    let pixel_format: PixelFormat = oni_frame.videoMode.pixelFormat.into();
+
+    match pixel_format {
+        PixelFormat::DEPTH_1_MM => DynFrame::DEPTH_1_MM(Frame::from_parts(oni_frame, source)),
+        PixelFormat::DEPTH_100_UM => DynFrame::DEPTH_100_UM(Frame::from_parts(oni_frame, source)),
+        PixelFormat::SHIFT_9_2 => DynFrame::SHIFT_9_2(Frame::from_parts(oni_frame, source)),
+        PixelFormat::SHIFT_9_3 => DynFrame::SHIFT_9_3(Frame::from_parts(oni_frame, source)),
+        PixelFormat::RGB888 => DynFrame::RGB888(Frame::from_parts(oni_frame, source)),
+        PixelFormat::YUV422 => DynFrame::YUV422(Frame::from_parts(oni_frame, source)),
+        PixelFormat::GRAY8 => DynFrame::GRAY8(Frame::from_parts(oni_frame, source)),
+        PixelFormat::GRAY16 => DynFrame::GRAY16(Frame::from_parts(oni_frame, source)),
+        PixelFormat::JPEG => DynFrame::JPEG(Frame::from_parts(oni_frame, source)),
+        PixelFormat::YUYV => DynFrame::YUYV(Frame::from_parts(oni_frame, source)),
     }
 }
 
+// Who owns the buffer that a `Frame`'s `OniFrame` points into.
+#[derive(Debug, Clone)]
+enum FrameSource {
+    // A reference counted frame of OpenNI2's
+    OpenNI(*mut OniFrame),
+    // A frame read from a `StreamBackend`, kept alive for its pixel data
+    Backend(#[allow(dead_code)] Arc<FrameData>),
+}
+
 /// A single frame of video data.
 ///
 /// # Example
@@ -42,11 +85,27 @@ pub unsafe fn frame_from_pointer<P: Pixel>(frame_pointer: *mut OniFrame) -> Fram
 #[derive(Debug)]
 pub struct Frame<P: Pixel> {
     oni_frame: OniFrame,
-    frame_pointer: *mut OniFrame,
+    source: FrameSource,
     _pixel_type: PhantomData<P>,
 }
 
 impl<P: Pixel> Frame<P> {
+    fn from_parts(oni_frame: OniFrame, source: FrameSource) -> Self {
+        Frame {
+            oni_frame,
+            source,
+            _pixel_type: PhantomData,
+        }
+    }
+
+    // Hands ownership of the underlying `OniFrame` to a `Frame` of a
+    // different pixel type without touching its reference count.
+    fn cast<Q: Pixel>(self) -> Frame<Q> {
+        let frame = Frame::from_parts(self.oni_frame, unsafe { ptr::read(&self.source) });
+        mem::forget(self);
+        frame
+    }
+
     /// The timestamp of the frame.
     pub fn timestamp(&self) -> u64 {
         self.oni_frame.timestamp
@@ -95,31 +154,86 @@ impl<P: Pixel> Frame<P> {
     }
 
     /// Returns the actual pixel data of the frame as an array of pixels of type `P`.
+    /// The pixels of a `PixelFormat::JPEG` frame are the `JpegByte`s of the
+    /// compressed image, and each `OniYUV422DoublePixel` of a `YUV422` or
+    /// `YUYV` frame holds two neighbouring pixels, so those frames have half
+    /// as many pixels as `width * height`.
     ///
     /// # Panics
-    /// `Frame::pixels` will panic if the byte size of the pixel format, as described in the
-    /// frame's `VideoMode`, doesn't match `mem::size_of::<P>()`. This could happen if you
+    /// `Frame::pixels` will panic if `P` doesn't support the pixel format described in the
+    /// frame's `VideoMode` (see `Pixel::supports_format`). This could happen if you
     /// created a stream with a `Pixel` type parameter that doesn't match what the stream
     /// is actually going to return.
     ///
     /// In other word's it's the programmer's responsibility to type the `Stream` correctly.
     pub fn pixels(&self) -> &[P] {
-        let pixel_size = bytes_per_pixel(self.oni_frame.videoMode.pixelFormat.into());
-        let type_param_size = mem::size_of::<P>();
-        assert_eq!(type_param_size, pixel_size, "Size of Frame::pixels() type parameter ({}) is different than pixel size reported by OpenNI2 ({}). If this method worked before, you may have changed the video mode of a stream without unregistering an existing callback.", type_param_size, pixel_size);
+        let pixel_format: PixelFormat = self.oni_frame.videoMode.pixelFormat.into();
+        assert!(P::supports_format(pixel_format), "Frame::pixels() type parameter {} can't hold pixels of format {:?}. If this method worked before, you may have changed the video mode of a stream without unregistering an existing callback.", type_name::<P>(), pixel_format);
+        if pixel_format == PixelFormat::JPEG {
+            // Compressed frames hold however many bytes the encoder produced
+            return unsafe {
+                slice::from_raw_parts(self.oni_frame.data as *const P, self.oni_frame.dataSize as usize / mem::size_of::<P>())
+            };
+        }
+        let pixel_size = bytes_per_pixel(pixel_format);
 
-        let num_pixels = self.oni_frame.width as usize * self.oni_frame.height as usize;
+        let num_pixels = pixels_per_frame(pixel_format, self.oni_frame.width as usize, self.oni_frame.height as usize);
         assert_eq!(self.oni_frame.dataSize as usize, num_pixels * pixel_size);
         unsafe {
             slice::from_raw_parts(self.oni_frame.data as *const P, num_pixels)
         }
     }
 
+    /// Returns the raw bytes of the frame, exactly as OpenNI2 delivered them.
+    ///
+    /// Unlike `Frame::pixels`, this doesn't assume that the frame holds one
+    /// fixed-size pixel per coordinate, so it is the way to get at
+    /// compressed data such as `PixelFormat::JPEG`.
+    pub fn data(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.oni_frame.data as *const u8, self.oni_frame.dataSize as usize)
+        }
+    }
+
     /// A shorthand method for frame width and height.
     pub fn dimensions(&self) -> (u16, u16) {
         (self.oni_frame.width as u16, self.oni_frame.height as u16)
     }
 
+    /// Returns the frame's `Cropping`, or `None` if the frame isn't cropped.
+    pub fn cropping(&self) -> Option<Cropping> {
+        if self.cropped() {
+            Some(Cropping {
+                width: self.width(),
+                height: self.height(),
+                origin_x: self.origin_x(),
+                origin_y: self.origin_y(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Copies the frame's pixels and metadata into an `OwnedFrame`.
+    ///
+    /// A `Frame` borrows one of OpenNI2's internal frame buffers until it is
+    /// dropped, and holding on to too many will starve the stream. An
+    /// `OwnedFrame` can be kept around indefinitely and sent to other threads.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `Frame::pixels`.
+    pub fn to_owned(&self) -> OwnedFrame<P> {
+        OwnedFrame {
+            pixels: self.pixels().to_vec(),
+            width: self.width(),
+            height: self.height(),
+            timestamp: self.timestamp(),
+            index: self.index(),
+            video_mode: self.video_mode(),
+            cropping: self.cropping(),
+        }
+    }
+
     #[doc(hidden)]
     pub fn inspect(&self) {
         println!("{:?}", self.oni_frame);
@@ -128,17 +242,254 @@ impl<P: Pixel> Frame<P> {
 
 impl<P: Pixel> Drop for Frame<P> {
     fn drop(&mut self) {
-        unsafe { oniFrameRelease(self.frame_pointer); }
+        if let FrameSource::OpenNI(frame_pointer) = self.source {
+            unsafe { oniFrameRelease(frame_pointer); }
+        }
     }
 }
 
 impl<P: Pixel> Clone for Frame<P> {
     fn clone(&self) -> Frame<P> {
-        unsafe { oniFrameAddRef(self.frame_pointer); }
+        if let FrameSource::OpenNI(frame_pointer) = self.source {
+            unsafe { oniFrameAddRef(frame_pointer); }
+        }
         Frame {
             oni_frame: self.oni_frame,
-            frame_pointer: self.frame_pointer,
+            source: self.source.clone(),
             _pixel_type: PhantomData,
         }
     }
 }
+
+/// A `Frame` whose pixel type is decided at runtime by the `PixelFormat`
+/// reported in its `VideoMode`. Returned by `Stream::read_frame_any`.
+///
+/// Because a stream's video mode can be changed at any time, matching on a
+/// `DynFrame` (or calling `DynFrame::downcast`) is the panic-free way to read
+/// frames from a stream whose pixel format isn't known ahead of time.
+///
+/// # Example
+/// ```
+/// # use openni2::{Device, SensorType, DynFrame};
+/// # fn main() -> Result<(), openni2::Status> {
+/// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+/// let stream = device.create_stream(SensorType::COLOR)?;
+/// stream.start()?;
+/// match stream.read_frame_any()? {
+///     DynFrame::RGB888(frame) => println!("{:?}", frame.pixels()[0]),
+///     DynFrame::JPEG(frame) => println!("{} compressed bytes", frame.data().len()),
+///     other => println!("Unexpected pixel format {:?}", other.pixel_format()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone)]
+pub enum DynFrame {
+    DEPTH_1_MM(Frame<DepthMmPixel>),
+    DEPTH_100_UM(Frame<Depth100UmPixel>),
+    SHIFT_9_2(Frame<ShiftPixel>),
+    SHIFT_9_3(Frame<ShiftPixel>),
+    RGB888(Frame<OniRGB888Pixel>),
+    YUV422(Frame<OniYUV422DoublePixel>),
+    GRAY8(Frame<OniGrayscale8Pixel>),
+    GRAY16(Frame<Gray16Pixel>),
+    /// JPEG frames are compressed, so their pixels are the bytes of the
+    /// compressed image.
+    JPEG(Frame<JpegByte>),
+    YUYV(Frame<OniYUV422DoublePixel>),
+}
+
+macro_rules! with_frame {
+    ($dyn_frame:expr, $frame:ident => $body:expr) => (
+        match $dyn_frame {
+            DynFrame::DEPTH_1_MM($frame) => $body,
+            DynFrame::DEPTH_100_UM($frame) => $body,
+            DynFrame::SHIFT_9_2($frame) => $body,
+            DynFrame::SHIFT_9_3($frame) => $body,
+            DynFrame::RGB888($frame) => $body,
+            DynFrame::YUV422($frame) => $body,
+            DynFrame::GRAY8($frame) => $body,
+            DynFrame::GRAY16($frame) => $body,
+            DynFrame::JPEG($frame) => $body,
+            DynFrame::YUYV($frame) => $body,
+        }
+    )
+}
+
+impl DynFrame {
+    /// The `PixelFormat` of the frame, which decides its variant.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.video_mode().pixel_format
+    }
+
+    /// The timestamp of the frame.
+    pub fn timestamp(&self) -> u64 {
+        with_frame!(self, frame => frame.timestamp())
+    }
+
+    /// The sequential index of the frame.
+    pub fn index(&self) -> usize {
+        with_frame!(self, frame => frame.index())
+    }
+
+    /// The width of the frame.
+    pub fn width(&self) -> u16 {
+        with_frame!(self, frame => frame.width())
+    }
+
+    /// The height of the frame.
+    pub fn height(&self) -> u16 {
+        with_frame!(self, frame => frame.height())
+    }
+
+    /// Returns the `VideoMode` of the frame.
+    pub fn video_mode(&self) -> VideoMode {
+        with_frame!(self, frame => frame.video_mode())
+    }
+
+    /// Returns the raw bytes of the frame. See `Frame::data`.
+    pub fn data(&self) -> &[u8] {
+        with_frame!(self, frame => frame.data())
+    }
+
+    /// Converts into a `Frame<P>`. If `P` doesn't support the frame's
+    /// `PixelFormat`, the `DynFrame` is handed back in `Err` instead of
+    /// panicking.
+    ///
+    /// # Example
+    /// ```
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// stream.start()?;
+    /// match stream.read_frame_any()?.downcast::<OniDepthPixel>() {
+    ///     Ok(frame) => println!("{:?}", frame.pixels()[0]),
+    ///     Err(other) => println!("Not a depth frame: {:?}", other.pixel_format()),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn downcast<P: Pixel>(self) -> Result<Frame<P>, DynFrame> {
+        if P::supports_format(self.pixel_format()) {
+            Ok(with_frame!(self, frame => frame.cast()))
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// A frame whose pixels have been copied out of OpenNI2's frame buffer into a
+/// `Vec`, along with the metadata of the `Frame` it came from. Created with
+/// `Frame::to_owned`, or built directly to make synthetic frames.
+///
+/// Unlike `Frame`, an `OwnedFrame` is `Send`, and can be serialized when the
+/// `serde` feature is enabled.
+///
+/// # Example
+/// ```
+/// # use std::thread;
+/// # use openni2::{Device, SensorType, OniDepthPixel};
+/// # fn main() -> Result<(), openni2::Status> {
+/// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+/// let stream = device.create_stream(SensorType::DEPTH)?;
+/// stream.start()?;
+/// let frame = stream.read_frame::<OniDepthPixel>()?.to_owned();
+/// thread::spawn(move || {
+///     println!("{}: {:?}", frame.timestamp, frame.pixels[0]);
+/// });
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OwnedFrame<P: Pixel> {
+    /// Pixel data, in rows of `width` pixels, or `width / 2` pixels for
+    /// `YUV422` and `YUYV`, whose pixels hold two neighbouring pixels each.
+    pub pixels: Vec<P>,
+    pub width: u16,
+    pub height: u16,
+    pub timestamp: u64,
+    pub index: usize,
+    pub video_mode: VideoMode,
+    /// The crop of the original frame, if it was cropped.
+    pub cropping: Option<Cropping>,
+}
+
+impl<P: Pixel> OwnedFrame<P> {
+    /// A shorthand method for frame width and height.
+    pub fn dimensions(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Returns the pixel data reinterpreted as bytes.
+    pub fn data(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.pixels.as_ptr() as *const u8, self.pixels.len() * mem::size_of::<P>())
+        }
+    }
+}
+
+pub(crate) fn pixels_from_bytes<P: Pixel>(bytes: &[u8]) -> Option<Vec<P>> {
+    let pixel_size = mem::size_of::<P>();
+    let len = bytes.len() / pixel_size;
+    if len * pixel_size != bytes.len() {
+        return None;
+    }
+    let mut pixels: Vec<P> = Vec::with_capacity(len);
+    unsafe {
+        ::std::ptr::copy_nonoverlapping(bytes.as_ptr(), pixels.as_mut_ptr() as *mut u8, bytes.len());
+        pixels.set_len(len);
+    }
+    Some(pixels)
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct OwnedFrameRepr {
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    index: usize,
+    video_mode: VideoMode,
+    cropping: Option<Cropping>,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl<P: Pixel> ::serde::Serialize for OwnedFrame<P> {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        OwnedFrameRepr {
+            width: self.width,
+            height: self.height,
+            timestamp: self.timestamp,
+            index: self.index,
+            video_mode: self.video_mode,
+            cropping: self.cropping,
+            data: self.data().to_vec(),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, P: Pixel> ::serde::Deserialize<'de> for OwnedFrame<P> {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let repr = OwnedFrameRepr::deserialize(deserializer)?;
+        let pixels = pixels_from_bytes::<P>(&repr.data)
+            .filter(|pixels| pixels.len() == pixels_per_frame(repr.video_mode.pixel_format, repr.width as usize, repr.height as usize))
+            .ok_or_else(|| D::Error::custom(format!(
+                "{} bytes of pixel data don't fit a {}x{} frame of {}-byte pixels",
+                repr.data.len(), repr.width, repr.height, mem::size_of::<P>(),
+            )))?;
+        Ok(OwnedFrame {
+            pixels,
+            width: repr.width,
+            height: repr.height,
+            timestamp: repr.timestamp,
+            index: repr.index,
+            video_mode: repr.video_mode,
+            cropping: repr.cropping,
+        })
+    }
+}
@@ -0,0 +1,109 @@
+//! Zero-copy `ndarray` views over frame pixel data, enabled by the `ndarray`
+//! cargo feature.
+
+use ndarray::{ArrayView, Dimension, Ix2, Ix3, ShapeBuilder, StrideShape};
+use std::mem;
+
+use openni2_sys::*;
+use frame::Frame;
+use types::{Pixel, PixelFormat, DepthMmPixel, Depth100UmPixel, Gray16Pixel, ShiftPixel};
+
+/// A `Pixel` type whose frames can be viewed as an `ndarray` array of its
+/// channels: 2-dimensional (rows × columns) for single-channel pixels, and
+/// 3-dimensional (rows × columns × channels) for `OniRGB888Pixel`.
+pub trait ArrayPixel: Pixel {
+    /// The type of a single channel, e.g. `u16` for depth
+    type Element;
+    /// `Ix2` or `Ix3`
+    type Dim: Dimension;
+
+    #[doc(hidden)]
+    fn shape(width: usize, height: usize, row_stride: usize) -> StrideShape<Self::Dim>;
+}
+
+macro_rules! arrayPixel2 {
+    ($($pixel:ty => $element:ty),+) => (
+        $(
+            impl ArrayPixel for $pixel {
+                type Element = $element;
+                type Dim = Ix2;
+
+                fn shape(width: usize, height: usize, row_stride: usize) -> StrideShape<Ix2> {
+                    (height, width).strides((row_stride, 1))
+                }
+            }
+        )+
+    )
+}
+
+arrayPixel2!(
+    OniDepthPixel => u16,
+    DepthMmPixel => u16,
+    Depth100UmPixel => u16,
+    Gray16Pixel => u16,
+    ShiftPixel => u16,
+    OniGrayscale8Pixel => u8
+);
+
+impl ArrayPixel for OniRGB888Pixel {
+    type Element = u8;
+    type Dim = Ix3;
+
+    fn shape(width: usize, height: usize, row_stride: usize) -> StrideShape<Ix3> {
+        (height, width, 3).strides((row_stride, 3, 1))
+    }
+}
+
+impl<P: ArrayPixel> Frame<P> {
+    /// Returns a view of the frame's pixels indexed by `[y, x]`, or by
+    /// `[y, x, channel]` for RGB frames, without copying them. Rows are
+    /// `Frame::stride` bytes apart, so padded rows are skipped over.
+    ///
+    /// A cropped frame only holds the pixels inside the crop, so index
+    /// `[0, 0]` is the pixel at (`Frame::origin_x`, `Frame::origin_y`) of
+    /// the full image.
+    ///
+    /// # Panics
+    /// Panics if `P` doesn't support the frame's pixel format, or if the
+    /// frame's data is too small for its dimensions and stride.
+    ///
+    /// # Example
+    /// ```
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
+    /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// stream.start()?;
+    /// let frame = stream.read_frame::<OniDepthPixel>()?;
+    /// let depth = frame.as_array();
+    /// println!("center: {}mm", depth[[120, 160]]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn as_array(&self) -> ArrayView<'_, P::Element, P::Dim> {
+        let pixel_format: PixelFormat = self.video_mode().pixel_format;
+        assert!(P::supports_format(pixel_format), "Frame::as_array() pixel type can't hold pixels of format {:?}", pixel_format);
+
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let element_size = mem::size_of::<P::Element>();
+        let row_bytes = width * mem::size_of::<P>();
+        let stride = match self.stride() as usize {
+            0 => row_bytes,
+            stride => stride,
+        };
+        assert!(stride >= row_bytes && stride % element_size == 0, "Frame stride of {} bytes doesn't fit rows of {} bytes", stride, row_bytes);
+
+        let data = self.data();
+        let needed = if height == 0 { 0 } else { (height - 1) * stride + row_bytes };
+        assert!(data.len() >= needed, "Frame has {} bytes of data, but its dimensions and stride need {}", data.len(), needed);
+
+        // The pixel types are plain old data made of `P::Element`s, and the
+        // assertions above keep every strided index inside `data`.
+        unsafe {
+            ArrayView::from_shape_ptr(P::shape(width, height, stride / element_size), data.as_ptr() as *const P::Element)
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Conversions between frames and `image` crate buffers, enabled by the
+//! `image` cargo feature.
+
+use image::{ImageBuffer, Rgb, Luma};
+
+use openni2_sys::*;
+use frame::{Frame, OwnedFrame};
+use types::{Status, Pixel, PixelFormat, VideoMode, DepthMmPixel, Depth100UmPixel, Gray16Pixel, ShiftPixel};
+
+/// A `Pixel` type that has an equivalent `image::Pixel` type, so that frames
+/// of it can be converted to and from an `image::ImageBuffer`.
+pub trait ImagePixel: Pixel {
+    /// The `image` crate pixel type, e.g. `image::Rgb<u8>`
+    type Image: ::image::Pixel;
+    /// The `PixelFormat` given to frames built with `OwnedFrame::from_image`
+    const PIXEL_FORMAT: PixelFormat;
+
+    #[doc(hidden)]
+    fn to_subpixels(pixels: &[Self]) -> Vec<Subpixel<Self>>;
+    #[doc(hidden)]
+    fn from_subpixels(subpixels: &[Subpixel<Self>]) -> Vec<Self>;
+}
+
+type Subpixel<P> = <<P as ImagePixel>::Image as ::image::Pixel>::Subpixel;
+
+/// The `image::ImageBuffer` that a `Frame<P>` converts into.
+pub type FrameImage<P> = ImageBuffer<<P as ImagePixel>::Image, Vec<Subpixel<P>>>;
+
+impl ImagePixel for OniRGB888Pixel {
+    type Image = Rgb<u8>;
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::RGB888;
+
+    fn to_subpixels(pixels: &[Self]) -> Vec<u8> {
+        pixels.iter().flat_map(|px| [px.r, px.g, px.b]).collect()
+    }
+
+    fn from_subpixels(subpixels: &[u8]) -> Vec<Self> {
+        subpixels.chunks(3).map(|rgb| OniRGB888Pixel { r: rgb[0], g: rgb[1], b: rgb[2] }).collect()
+    }
+}
+
+impl ImagePixel for OniGrayscale8Pixel {
+    type Image = Luma<u8>;
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::GRAY8;
+
+    fn to_subpixels(pixels: &[Self]) -> Vec<u8> {
+        pixels.to_vec()
+    }
+
+    fn from_subpixels(subpixels: &[u8]) -> Vec<Self> {
+        subpixels.to_vec()
+    }
+}
+
+macro_rules! imagePixel16 {
+    ($($pixel:ty => $format:ident),+) => (
+        $(
+            impl ImagePixel for $pixel {
+                type Image = Luma<u16>;
+                const PIXEL_FORMAT: PixelFormat = PixelFormat::$format;
+
+                fn to_subpixels(pixels: &[Self]) -> Vec<u16> {
+                    pixels.iter().map(|&px| u16::from(px)).collect()
+                }
+
+                fn from_subpixels(subpixels: &[u16]) -> Vec<Self> {
+                    subpixels.iter().map(|&value| <$pixel>::from(value)).collect()
+                }
+            }
+        )+
+    )
+}
+
+imagePixel16!(
+    OniDepthPixel => DEPTH_1_MM,
+    DepthMmPixel => DEPTH_1_MM,
+    Depth100UmPixel => DEPTH_100_UM,
+    Gray16Pixel => GRAY16,
+    ShiftPixel => SHIFT_9_2
+);
+
+impl<P: ImagePixel> Frame<P> {
+    /// Copies the frame into an `image::ImageBuffer`: an `RgbImage` for
+    /// `OniRGB888Pixel`, a `GrayImage` for `OniGrayscale8Pixel`, and an
+    /// `ImageBuffer<Luma<u16>, Vec<u16>>` for depth, IR and shift pixels.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `Frame::pixels`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use openni2::{Device, SensorType, OniRGB888Pixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// let device = Device::open_default()?;
+    /// let stream = device.create_stream(SensorType::COLOR)?;
+    /// stream.start()?;
+    /// let image = stream.read_frame::<OniRGB888Pixel>()?.to_image();
+    /// assert_eq!(image.dimensions(), (320, 240));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_image(&self) -> FrameImage<P> {
+        ImageBuffer::from_raw(u32::from(self.width()), u32::from(self.height()), P::to_subpixels(self.pixels()))
+            .expect("Frame has the wrong number of pixels for its dimensions")
+    }
+}
+
+impl<P: ImagePixel> OwnedFrame<P> {
+    /// Copies the frame into an `image::ImageBuffer`. See `Frame::to_image`.
+    ///
+    /// # Panics
+    /// Panics if the frame has fewer than `width * height` pixels.
+    pub fn to_image(&self) -> FrameImage<P> {
+        ImageBuffer::from_raw(u32::from(self.width), u32::from(self.height), P::to_subpixels(&self.pixels))
+            .expect("OwnedFrame has the wrong number of pixels for its dimensions")
+    }
+
+    /// Builds a synthetic frame from an `image::ImageBuffer`, with a
+    /// timestamp and index of 0 and a video mode of `P::PIXEL_FORMAT` at
+    /// 30 FPS.
+    ///
+    /// Returns `Err(Status::BadParameter)` if the image is wider or taller
+    /// than `u16::MAX`.
+    ///
+    /// # Example
+    /// ```
+    /// # extern crate image;
+    /// # extern crate openni2;
+    /// # use openni2::{OwnedFrame, OniDepthPixel, PixelFormat};
+    /// # fn main() {
+    /// let image = image::ImageBuffer::from_fn(64, 48, |x, _| image::Luma([x as u16 * 10]));
+    /// let frame = OwnedFrame::<OniDepthPixel>::from_image(&image).unwrap();
+    /// assert_eq!(frame.pixels[5], 50);
+    /// assert_eq!(frame.video_mode.pixel_format, PixelFormat::DEPTH_1_MM);
+    /// # }
+    /// ```
+    pub fn from_image(image: &FrameImage<P>) -> Result<Self, Status> {
+        let (width, height) = image.dimensions();
+        if width > u32::from(u16::MAX) || height > u32::from(u16::MAX) {
+            return Err(Status::BadParameter);
+        }
+        Ok(OwnedFrame {
+            pixels: P::from_subpixels(image.as_raw()),
+            width: width as u16,
+            height: height as u16,
+            timestamp: 0,
+            index: 0,
+            video_mode: VideoMode {
+                pixel_format: P::PIXEL_FORMAT,
+                resolution_x: width as i32,
+                resolution_y: height as i32,
+                fps: 30,
+            },
+            cropping: None,
+        })
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use frame::DynFrame;
+use stream::Stream;
+use types::{Status, SensorType, Timeout};
+use wait_for_any_stream;
+
+/// Frames from different sensors of a device whose timestamps lie within
+/// the tolerance of a `FrameSetReader`. A field is `None` only if the
+/// reader wasn't given a stream for that sensor.
+#[derive(Debug, Clone)]
+pub struct FrameSet {
+    pub depth: Option<DynFrame>,
+    pub color: Option<DynFrame>,
+    pub ir: Option<DynFrame>,
+}
+
+impl FrameSet {
+    /// The timestamp of the earliest frame in the set.
+    pub fn timestamp(&self) -> u64 {
+        [&self.depth, &self.color, &self.ir].iter()
+            .filter_map(|frame| frame.as_ref().map(DynFrame::timestamp))
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// Counts of the frames a `FrameSetReader` has handled.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FrameSetStats {
+    /// Number of `FrameSet`s returned
+    pub matched: u64,
+    /// Frames discarded because no frame of another stream was close enough
+    /// in time to pair with them
+    pub unmatched: u64,
+    /// Frames discarded because too many were waiting for a match, which
+    /// happens when one stream stops delivering frames
+    pub dropped: u64,
+}
+
+/// Reads frames from several streams of a device and groups them into
+/// `FrameSet`s by timestamp.
+///
+/// Each call to `FrameSetReader::read` waits for frames from all of the
+/// streams, discarding frames that are too old to be matched, until it has
+/// one frame per stream whose timestamps all lie within the tolerance
+/// (16ms by default). Turning on `Device::enable_color_depth_sync` makes
+/// depth and color timestamps line up much more closely.
+///
+/// # Example
+/// ```no_run
+/// # use std::time::Duration;
+/// # use openni2::{Device, SensorType, FrameSetReader};
+/// # fn main() -> Result<(), openni2::Status> {
+/// let mut device = Device::open_default()?;
+/// device.enable_color_depth_sync(true);
+/// let depth = device.create_stream(SensorType::DEPTH)?;
+/// let color = device.create_stream(SensorType::COLOR)?;
+/// depth.start()?;
+/// color.start()?;
+///
+/// let mut reader = FrameSetReader::new(&[&depth, &color])?
+///     .tolerance(Duration::from_millis(5));
+/// loop {
+///     let set = reader.read(Duration::from_millis(2000))?;
+///     let (depth, color) = (set.depth.unwrap(), set.color.unwrap());
+///     println!("{} {}", depth.timestamp(), color.timestamp());
+///     println!("{:?}", reader.stats());
+/// }
+/// # }
+/// ```
+pub struct FrameSetReader<'a, 'device: 'a> {
+    streams: Vec<&'a Stream<'device>>,
+    queues: Vec<VecDeque<DynFrame>>,
+    tolerance: u64,
+    max_queued: usize,
+    stats: FrameSetStats,
+}
+
+impl<'a, 'device: 'a> FrameSetReader<'a, 'device> {
+    /// Creates a reader for streams of different sensors.
+    ///
+    /// Returns `Err(Status::BadParameter)` if `streams` is empty or has two
+    /// streams of the same sensor type.
+    pub fn new(streams: &[&'a Stream<'device>]) -> Result<Self, Status> {
+        if streams.is_empty() {
+            return Err(Status::BadParameter);
+        }
+        for (i, stream) in streams.iter().enumerate() {
+            if streams[..i].iter().any(|other| other.sensor_type() == stream.sensor_type()) {
+                return Err(Status::BadParameter);
+            }
+        }
+        Ok(FrameSetReader {
+            streams: streams.to_vec(),
+            queues: streams.iter().map(|_| VecDeque::new()).collect(),
+            tolerance: 16_000,
+            max_queued: 4,
+            stats: FrameSetStats::default(),
+        })
+    }
+
+    /// Sets the largest difference between the timestamps of frames in a set.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance.as_secs() * 1_000_000 + u64::from(tolerance.subsec_micros());
+        self
+    }
+
+    /// Sets how many frames of a stream can wait for a match before the
+    /// oldest is dropped (4 by default). Waiting frames hold on to OpenNI2's
+    /// frame buffers, so this should be kept small.
+    pub fn max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued.max(1);
+        self
+    }
+
+    /// Counts of the frames handled so far.
+    pub fn stats(&self) -> FrameSetStats {
+        self.stats
+    }
+
+    /// Blocks until a matched `FrameSet` is available. `timeout` applies to
+    /// each wait for a new frame, so `Err(Status::TimeOut)` means that none
+    /// of the streams delivered a frame in that time.
+    pub fn read<T: Into<Timeout>>(&mut self, timeout: T) -> Result<FrameSet, Status> {
+        let timeout = timeout.into();
+        loop {
+            if let Some(set) = self.try_match() {
+                return Ok(set);
+            }
+            let index = wait_for_any_stream(&self.streams, timeout)?;
+            let frame = self.streams[index].read_frame_any()?;
+            let queue = &mut self.queues[index];
+            queue.push_back(frame);
+            if queue.len() > self.max_queued {
+                queue.pop_front();
+                self.stats.dropped += 1;
+            }
+        }
+    }
+
+    fn try_match(&mut self) -> Option<FrameSet> {
+        loop {
+            if self.queues.iter().any(VecDeque::is_empty) {
+                return None;
+            }
+            let newest = self.queues.iter().map(|queue| queue[0].timestamp()).max()?;
+
+            // A frame that is older than the newest head by more than the
+            // tolerance can never be matched, since every frame still to
+            // come from the newest head's stream is newer still.
+            let mut discarded = false;
+            for queue in &mut self.queues {
+                if queue[0].timestamp() + self.tolerance < newest {
+                    queue.pop_front();
+                    self.stats.unmatched += 1;
+                    discarded = true;
+                }
+            }
+            if discarded {
+                continue;
+            }
+
+            let mut set = FrameSet { depth: None, color: None, ir: None };
+            for (stream, queue) in self.streams.iter().zip(&mut self.queues) {
+                let frame = queue.pop_front();
+                match stream.sensor_type() {
+                    SensorType::DEPTH => set.depth = frame,
+                    SensorType::COLOR => set.color = frame,
+                    SensorType::IR => set.ir = frame,
+                }
+            }
+            self.stats.matched += 1;
+            return Some(set);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Folders of depth and color images played back as a device, enabled by the
+//! `image-sequence` cargo feature.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{self, DynamicImage};
+
+use device::{Device, DeviceInfo};
+use mock::{FrameGenerator, FrameRequest, MockDevice, MockSensor};
+use types::{Status, SensorType, PixelFormat, VideoMode};
+
+// Frame rate of image sequences that don't have timestamps.
+const DEFAULT_FPS: i32 = 30;
+
+// Images paired with when they were taken, in seconds.
+type TimedImages = Vec<(f64, PathBuf)>;
+
+// The images of one stream and when they were taken, in seconds.
+#[derive(Debug)]
+struct Track {
+    paths: Vec<PathBuf>,
+    times: Vec<f64>,
+    width: u32,
+    height: u32,
+}
+
+impl Track {
+    fn new(mut frames: TimedImages) -> Result<Option<Self>, Status> {
+        if frames.is_empty() {
+            return Ok(None);
+        }
+        frames.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        let (width, height) = image::image_dimensions(&frames[0].1).map_err(|err| image_error(&frames[0].1, err))?;
+        let (times, paths) = frames.into_iter().unzip();
+        Ok(Some(Track { paths, times, width, height }))
+    }
+
+    // The frame rate, rounded, that the timestamps average out to.
+    fn fps(&self) -> i32 {
+        let duration = self.times[self.times.len() - 1] - self.times[0];
+        if self.times.len() < 2 || duration <= 0.0 {
+            return DEFAULT_FPS;
+        }
+        (((self.times.len() - 1) as f64 / duration).round() as i32).max(1)
+    }
+
+    fn sensor(&self, sensor_type: SensorType, formats: &[PixelFormat], start: f64, generator: Images) -> MockSensor {
+        let fps = self.fps();
+        let modes = formats.iter().map(|&pixel_format| VideoMode {
+            pixel_format,
+            resolution_x: self.width as i32,
+            resolution_y: self.height as i32,
+            fps,
+        }).collect();
+        let timestamps = self.times.iter().map(|&time| ((time - start) * 1e6).round().max(0.0) as u64).collect();
+        MockSensor::new(sensor_type, modes)
+            .timestamps(timestamps)
+            .generator(generator)
+    }
+}
+
+fn image_error(path: &Path, err: image::ImageError) -> Status {
+    Status::Error(format!("Couldn't read image {}: {}", path.display(), err))
+}
+
+fn io_error(path: &Path, err: ::std::io::Error) -> Status {
+    Status::Error(format!("Couldn't read {}: {}", path.display(), err))
+}
+
+fn is_image(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => ["png", "jpg", "jpeg", "pgm", "ppm"].contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+// Reads a TUM RGB-D file list: lines of a timestamp in seconds and a file
+// name relative to the dataset folder, and comments starting with `#`.
+fn read_list(root: &Path, name: &str) -> Result<TimedImages, Status> {
+    let path = root.join(name);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).map_err(|err| io_error(&path, err))?;
+    let mut frames = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        match (fields.next().and_then(|time| time.parse().ok()), fields.next()) {
+            (Some(time), Some(file)) => frames.push((time, root.join(file))),
+            _ => return Err(Status::Error(format!(
+                "{}:{}: expected a timestamp and a file name", path.display(), number + 1,
+            ))),
+        }
+    }
+    Ok(frames)
+}
+
+// Lists the images in a folder, by name.
+fn read_folder(folder: &Path) -> Result<Vec<PathBuf>, Status> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(folder).map_err(|err| io_error(folder, err))? {
+        let path = entry.map_err(|err| io_error(folder, err))?.path();
+        if path.is_file() && is_image(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Timestamps images by the numbers in their names, which TUM RGB-D's are, or
+// by `times`, or else at `DEFAULT_FPS`.
+fn timestamp_files(paths: Vec<PathBuf>, times: Option<&[f64]>) -> Result<TimedImages, Status> {
+    if let Some(times) = times {
+        if times.len() < paths.len() {
+            return Err(Status::Error(format!("timestamps.txt has {} timestamps for {} images", times.len(), paths.len())));
+        }
+        return Ok(times.iter().cloned().zip(paths).collect());
+    }
+    let named: Option<Vec<f64>> = paths.iter()
+        .map(|path| path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()))
+        .collect();
+    let times = named.unwrap_or_else(|| (0..paths.len()).map(|index| index as f64 / f64::from(DEFAULT_FPS)).collect());
+    Ok(times.into_iter().zip(paths).collect())
+}
+
+// Reads the color images of NYU Depth v2 raw dumps, named
+// `r-<timestamp>-<frame>.ppm`. Their `d-<timestamp>-<frame>.pgm` depth
+// images hold raw disparity, which would play back as wrong depths, so they
+// are left out.
+fn read_nyu(root: &Path) -> Result<TimedImages, Status> {
+    let mut color = Vec::new();
+    for path in read_folder(root)? {
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let mut fields = name.splitn(3, '-');
+        if let (Some("r"), Some(time)) = (fields.next(), fields.next().and_then(|time| time.parse::<f64>().ok())) {
+            color.push((time, path));
+        }
+    }
+    Ok(color)
+}
+
+// Renders the frames of a stream by reading its images.
+struct Images {
+    paths: Vec<PathBuf>,
+    // Depth image values per millimeter, or `None` for color images
+    depth_scale: Option<f32>,
+}
+
+impl Images {
+    fn open(&self, request: &FrameRequest) -> Result<DynamicImage, Status> {
+        let path = self.paths.get(request.index).ok_or(Status::TimeOut)?;
+        let image = image::open(path).map_err(|err| image_error(path, err))?;
+        let mode = request.video_mode;
+        if image.width() as i32 != mode.resolution_x || image.height() as i32 != mode.resolution_y {
+            return Err(Status::Error(format!(
+                "Image {} is {}x{}, not {}x{} like the first of its stream",
+                path.display(), image.width(), image.height(), mode.resolution_x, mode.resolution_y,
+            )));
+        }
+        Ok(image)
+    }
+}
+
+impl FrameGenerator for Images {
+    fn generate(&self, request: &FrameRequest) -> Result<Vec<u8>, Status> {
+        match (self.depth_scale, request.video_mode.pixel_format) {
+            (Some(scale), format @ PixelFormat::DEPTH_1_MM) | (Some(scale), format @ PixelFormat::DEPTH_100_UM) => {
+                let millimeters = if format == PixelFormat::DEPTH_1_MM { 1.0 } else { 0.1 };
+                let image = self.open(request)?.into_luma16();
+                Ok(image.pixels().flat_map(|pixel| {
+                    let depth = (f32::from(pixel[0]) / scale / millimeters).round().min(f32::from(u16::MAX)) as u16;
+                    depth.to_le_bytes()
+                }).collect())
+            },
+            (None, PixelFormat::RGB888) => Ok(self.open(request)?.into_rgb8().into_raw()),
+            _ => Err(Status::NotSupported),
+        }
+    }
+}
+
+/// A folder of depth and color images, such as a TUM RGB-D or NYU Depth v2
+/// dataset, that plays back like a recording. See
+/// `Device::open_image_sequence`.
+///
+/// Three layouts are recognized:
+///
+/// * TUM RGB-D: `depth.txt` and `rgb.txt` list the depth and color images,
+///   one per line after a timestamp in seconds. Depth images hold 5 units
+///   per millimeter.
+/// * A folder named `depth`, one named `rgb` or `color`, or both, holding
+///   images sorted by name. A `timestamps.txt` with one timestamp in seconds
+///   per line gives the times of each stream's images in order; without one,
+///   image names that are numbers are read as timestamps in seconds, and
+///   otherwise the images are spaced at 30 frames per second. Depth images
+///   hold millimeters.
+/// * NYU Depth v2 raw dumps: `r-<timestamp>-<frame>.ppm` color images in one
+///   folder. Only the color images play back: the dumps' `d-*.pgm` depth
+///   images hold the Kinect's raw disparity, not depth, so they need
+///   converting with the dataset's toolbox, and moving into the folder
+///   layout above, first.
+///
+/// Depth images are 16-bit grayscale PNG or PGM files, and play back as
+/// `DEPTH_1_MM` or `DEPTH_100_UM` streams. Color images are PNG, JPEG or PPM
+/// files, and play back as `RGB888` streams. Each stream's resolution is
+/// that of its first image, and its frame rate the average of its
+/// timestamps. Timestamps start at 0 for the first image of either stream.
+#[derive(Debug)]
+pub struct ImageSequence {
+    path: PathBuf,
+    depth: Option<Track>,
+    color: Option<Track>,
+    depth_scale: f32,
+    field_of_view: Option<(f32, f32)>,
+}
+
+impl ImageSequence {
+    /// Finds the images and timestamps of the dataset in folder `path`.
+    /// Returns `Err` if it has no depth or color images in a layout listed
+    /// above, or its files can't be read.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Status> {
+        let root = path.as_ref();
+        let folder = |names: &[&str]| names.iter().map(|name| root.join(name)).find(|folder| folder.is_dir());
+        let (depth, color, depth_scale) = if root.join("depth.txt").is_file() || root.join("rgb.txt").is_file() {
+            (read_list(root, "depth.txt")?, read_list(root, "rgb.txt")?, 5.0)
+        } else if let (depth @ Some(_), color) | (depth, color @ Some(_)) = (folder(&["depth"]), folder(&["rgb", "color"])) {
+            let times_path = root.join("timestamps.txt");
+            let times = if times_path.is_file() {
+                let contents = fs::read_to_string(&times_path).map_err(|err| io_error(&times_path, err))?;
+                let times: Result<Vec<f64>, _> = contents.split_whitespace().map(|time| time.parse()).collect();
+                Some(times.map_err(|err| Status::Error(format!("Bad timestamp in {}: {}", times_path.display(), err)))?)
+            } else {
+                None
+            };
+            let times = times.as_ref().map(|times| &times[..]);
+            let images = |folder: Option<PathBuf>| match folder {
+                Some(folder) => timestamp_files(read_folder(&folder)?, times),
+                None => Ok(Vec::new()),
+            };
+            (images(depth)?, images(color)?, 1.0)
+        } else {
+            (Vec::new(), read_nyu(root)?, 1.0)
+        };
+
+        let (depth, color) = (Track::new(depth)?, Track::new(color)?);
+        if depth.is_none() && color.is_none() {
+            return Err(Status::Error(format!("No depth or color images found in {}", root.display())));
+        }
+        Ok(ImageSequence {
+            path: root.to_owned(),
+            depth,
+            color,
+            depth_scale,
+            field_of_view: None,
+        })
+    }
+
+    /// Sets how many units of a depth image's values make a millimeter,
+    /// e.g. 5 for TUM RGB-D's depth images, which is the default for them.
+    pub fn depth_scale(mut self, units_per_mm: f32) -> Self {
+        self.depth_scale = units_per_mm;
+        self
+    }
+
+    /// Sets the fields of view of both streams, in radians. By default
+    /// they are a PS1080's, which most datasets were recorded with.
+    pub fn field_of_view(mut self, horizontal_fov: f32, vertical_fov: f32) -> Self {
+        self.field_of_view = Some((horizontal_fov, vertical_fov));
+        self
+    }
+
+    /// Returns the number of depth or color images.
+    pub fn number_of_frames(&self, sensor_type: SensorType) -> usize {
+        match sensor_type {
+            SensorType::DEPTH => self.depth.as_ref().map_or(0, |track| track.paths.len()),
+            SensorType::COLOR => self.color.as_ref().map_or(0, |track| track.paths.len()),
+            SensorType::IR => 0,
+        }
+    }
+
+    /// Makes a `MockDevice` that plays back the images, with
+    /// `MockDevice::playback` turned on and in real time.
+    pub fn device(self) -> MockDevice {
+        let start = self.depth.iter().chain(self.color.iter())
+            .map(|track| track.times[0])
+            .fold(f64::INFINITY, f64::min);
+        let mut device = MockDevice::new()
+            .info(DeviceInfo {
+                uri: self.path.display().to_string(),
+                vendor: "openni2-rs".to_owned(),
+                name: "ImageSequence".to_owned(),
+                usb_vendor_id: 0,
+                usb_product_id: 0,
+            })
+            .without_sensor(SensorType::DEPTH)
+            .without_sensor(SensorType::COLOR)
+            .without_sensor(SensorType::IR)
+            .playback(true)
+            .realtime(true);
+        let field_of_view = self.field_of_view;
+        let with_fov = |sensor: MockSensor| match field_of_view {
+            Some((horizontal_fov, vertical_fov)) => sensor.field_of_view(horizontal_fov, vertical_fov),
+            None => sensor,
+        };
+        if let Some(track) = self.depth {
+            let images = Images { paths: track.paths.clone(), depth_scale: Some(self.depth_scale) };
+            let formats = [PixelFormat::DEPTH_1_MM, PixelFormat::DEPTH_100_UM];
+            device = device.sensor(with_fov(track.sensor(SensorType::DEPTH, &formats, start, images)));
+        }
+        if let Some(track) = self.color {
+            let images = Images { paths: track.paths.clone(), depth_scale: None };
+            device = device.sensor(with_fov(track.sensor(SensorType::COLOR, &[PixelFormat::RGB888], start, images)));
+        }
+        device
+    }
+}
+
+impl Device {
+    /// Opens a folder of depth and color images, such as a TUM RGB-D or NYU
+    /// Depth v2 dataset, as a device whose streams play them back like a
+    /// recording: frames carry the images' timestamps, `get_number_of_frames`
+    /// counts them, and `Device::playback` seeks and steps through them.
+    /// See `ImageSequence` for the layouts it reads. Needs the
+    /// `image-sequence` cargo feature.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// let device = Device::open_image_sequence("rgbd_dataset_freiburg1_xyz")?;
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// println!("{} frames", stream.get_number_of_frames()?);
+    /// let playback = device.playback()?;
+    /// playback.set_manual_stepping(true)?;
+    /// stream.start()?;
+    /// playback.seek(&stream, 100)?;
+    /// let frame = playback.step::<OniDepthPixel>(&stream)?;
+    /// assert_eq!(frame.index(), 100);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_image_sequence<P: AsRef<Path>>(path: P) -> Result<Self, Status> {
+        Ok(Device::from_backend(ImageSequence::open(path)?.device()))
+    }
+}
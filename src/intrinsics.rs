@@ -0,0 +1,116 @@
+use frame::Frame;
+use stream::{Stream, Cropping};
+use types::{Status, Pixel};
+
+/// A pinhole camera model of a stream: focal lengths and principal point in
+/// pixels, for an image of `width` by `height` pixels.
+///
+/// OpenNI2 doesn't report these directly; they are derived from a stream's
+/// fields of view and resolution the same way OpenNI2's own coordinate
+/// converter does, so `Intrinsics::unproject` agrees with
+/// `Stream::depth_to_world` and `Intrinsics::project` agrees with
+/// `Stream::world_to_depth`.
+///
+/// # Example
+/// ```
+/// # use openni2::{Cropping, Device, Intrinsics, OniDepthPixel, PixelFormat, SensorType, VideoMode};
+/// # use openni2::mock::{MockDevice, MockSensor};
+/// # fn main() -> Result<(), openni2::Status> {
+/// # /*
+/// let device = Device::open_default()?;
+/// # */
+/// # let mode = VideoMode { pixel_format: PixelFormat::DEPTH_1_MM, resolution_x: 640, resolution_y: 480, fps: 30 };
+/// # let sensor = MockSensor::new(SensorType::DEPTH, vec![mode]).field_of_view(1.0, 0.75);
+/// # let device = Device::from_backend(MockDevice::new().sensor(sensor));
+/// let stream = device.create_stream(SensorType::DEPTH)?;
+/// let intrinsics = Intrinsics::from_stream(&stream)?;
+///
+/// // For a 640x480 stream with fields of view of 1.0 and 0.75 radians,
+/// // OpenNI2 converts pixel (100, 50) at 1000mm to
+/// // x = (100 / 640 - 0.5) * 1000 * 2 * tan(1.0 / 2) and
+/// // y = (0.5 - 50 / 480) * 1000 * 2 * tan(0.75 / 2)
+/// let expected = [-375.583, 311.621, 1000.0];
+/// let close = |point: [f32; 3]| point.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 0.01);
+/// assert!(close(intrinsics.unproject(100.0, 50.0, 1000.0)));
+///
+/// // The same pixel, in a frame cropped from (64, 32)
+/// stream.set_cropping(Some(Cropping { width: 320, height: 240, origin_x: 64, origin_y: 32 }))?;
+/// stream.start()?;
+/// let frame = stream.read_frame::<OniDepthPixel>()?;
+/// assert!(close(intrinsics.for_frame(&frame).unproject(36.0, 18.0, 1000.0)));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Intrinsics {
+    /// Horizontal focal length, in pixels
+    pub fx: f32,
+    /// Vertical focal length, in pixels
+    pub fy: f32,
+    /// Horizontal principal point, in pixels
+    pub cx: f32,
+    /// Vertical principal point, in pixels
+    pub cy: f32,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Intrinsics {
+    /// Derives intrinsics from fields of view, in radians, and a resolution.
+    pub fn from_fov(horizontal_fov: f32, vertical_fov: f32, width: u16, height: u16) -> Self {
+        Intrinsics {
+            fx: f32::from(width) / (2.0 * (horizontal_fov / 2.0).tan()),
+            fy: f32::from(height) / (2.0 * (vertical_fov / 2.0).tan()),
+            cx: f32::from(width) / 2.0,
+            cy: f32::from(height) / 2.0,
+            width,
+            height,
+        }
+    }
+
+    /// Derives the intrinsics of a stream from its fields of view and the
+    /// resolution of its current `VideoMode`. These describe the uncropped
+    /// image; see `Intrinsics::for_frame` for cropped frames.
+    pub fn from_stream(stream: &Stream) -> Result<Self, Status> {
+        let video_mode = stream.get_video_mode()?;
+        Ok(Self::from_fov(
+            stream.get_horizontal_fov()?,
+            stream.get_vertical_fov()?,
+            video_mode.resolution_x as u16,
+            video_mode.resolution_y as u16,
+        ))
+    }
+
+    /// Returns the intrinsics of a crop of the image, where pixel `(0, 0)` is
+    /// the crop's origin.
+    pub fn cropped(&self, cropping: Cropping) -> Self {
+        Intrinsics {
+            cx: self.cx - f32::from(cropping.origin_x),
+            cy: self.cy - f32::from(cropping.origin_y),
+            width: cropping.width,
+            height: cropping.height,
+            ..*self
+        }
+    }
+
+    /// Returns the intrinsics that apply to the pixels of `frame`, which
+    /// differ from the stream's if the frame is cropped.
+    pub fn for_frame<P: Pixel>(&self, frame: &Frame<P>) -> Self {
+        match frame.cropping() {
+            Some(cropping) => self.cropped(cropping),
+            None => *self,
+        }
+    }
+
+    /// Converts a pixel coordinate and depth into a point in world space, in
+    /// the same units as `z`. World y increases upward, unlike pixel y.
+    pub fn unproject(&self, x: f32, y: f32, z: f32) -> [f32; 3] {
+        [(x - self.cx) * z / self.fx, (self.cy - y) * z / self.fy, z]
+    }
+
+    /// Converts a point in world space into a pixel coordinate and depth.
+    pub fn project(&self, point: [f32; 3]) -> (f32, f32, f32) {
+        let [x, y, z] = point;
+        (self.cx + x * self.fx / z, self.cy - y * self.fy / z, z)
+    }
+}
@@ -64,17 +64,52 @@
 //! ```
 
 extern crate openni2_sys;
+extern crate crossbeam_channel;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "jpeg")]
+extern crate jpeg_decoder;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[cfg(feature = "async")]
+extern crate futures_core;
 use std::os::raw::{c_int, c_void, c_char};
 use std::ffi::{CString, CStr};
-use std::{mem, ptr, slice};
-use std::marker::PhantomData;
+use std::{ptr, slice};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use openni2_sys::*;
+use cookie::{Cookie, CookiePool};
 
 mod device;
+mod backend;
+mod cookie;
 mod stream;
+mod typed_stream;
 mod frame;
+mod frame_set;
 mod recorder;
+mod playback;
+mod point_cloud;
+mod intrinsics;
+mod shift;
+#[cfg(feature = "image")]
+mod frame_image;
+#[cfg(feature = "ndarray")]
+mod frame_array;
+#[cfg(feature = "async")]
+mod async_stream;
+#[cfg(feature = "image-sequence")]
+mod image_sequence;
 mod types;
+pub mod oni;
+pub mod convert;
+pub mod visualize;
+pub mod mock;
+pub mod scene;
 
 pub use types::{
     Status,
@@ -85,12 +120,44 @@ pub use types::{
     Timeout,
     VideoMode,
     Pixel,
+    DepthMmPixel,
+    Depth100UmPixel,
+    Gray16Pixel,
+    ShiftPixel,
+    JpegByte,
     LogLevel,
 };
 pub use device::{Device, DeviceInfo};
-pub use stream::{Stream, StreamListener, Cropping};
-pub use frame::{Frame, frame_from_pointer};
+pub use backend::{DeviceBackend, StreamBackend, FrameData};
+pub use stream::{Stream, StreamListener, Frames, Subscription, Cropping};
+pub use typed_stream::{
+    TypedStream,
+    StreamFormat,
+    DepthMm,
+    Depth100Um,
+    Rgb888,
+    Yuv422,
+    Yuyv,
+    Gray8,
+    Gray16,
+    Shift92,
+    Shift93,
+};
+pub use frame::{Frame, DynFrame, OwnedFrame, frame_from_pointer, dyn_frame_from_pointer};
+pub use frame_set::{FrameSet, FrameSetReader, FrameSetStats};
 pub use recorder::Recorder;
+pub use playback::PlaybackControl;
+pub use point_cloud::{PointCloud, PointCloudBuilder};
+pub use intrinsics::Intrinsics;
+pub use shift::{ShiftCalibration, ShiftToDepth};
+#[cfg(feature = "image")]
+pub use frame_image::{ImagePixel, FrameImage};
+#[cfg(feature = "ndarray")]
+pub use frame_array::ArrayPixel;
+#[cfg(feature = "async")]
+pub use async_stream::{FrameStream, DeviceEvent, DeviceEvents, device_events};
+#[cfg(feature = "image-sequence")]
+pub use image_sequence::ImageSequence;
 pub use openni2_sys::{
     OniDepthPixel,
     OniGrayscale16Pixel,
@@ -118,6 +185,9 @@ pub fn init_version(major: c_int, minor: c_int) -> Result<(), Status> {
 /// Shutdown the OpenNI2 library
 pub fn shutdown() {
     unsafe { oniShutdown() };
+    // OpenNI2 raises no more device events, so unregistered callbacks' cookies
+    // can't be read anymore
+    unsafe { DEVICE_CALLBACK_COOKIES.free() };
 }
 
 /// Returns the version of the OpenNI2 library
@@ -167,6 +237,117 @@ pub fn get_device_list() -> Vec<DeviceInfo> {
     mapped
 }
 
+/// Blocks until one of `streams` has a frame ready to read, then returns the
+/// index of that stream within the slice. Returns `Err(Status::TimeOut)` if
+/// no stream became ready before `timeout` elapsed.
+///
+/// This lets a single thread service several streams, as an alternative to
+/// registering a `Stream::listener` on each of them.
+///
+/// # Example
+/// ```no_run
+/// # use std::time::Duration;
+/// # use openni2::{Device, SensorType, OniDepthPixel, OniRGB888Pixel};
+/// # fn main() -> Result<(), openni2::Status> {
+/// let device = Device::open_default()?;
+/// let depth = device.create_stream(SensorType::DEPTH)?;
+/// let color = device.create_stream(SensorType::COLOR)?;
+/// depth.start()?;
+/// color.start()?;
+/// loop {
+///     match openni2::wait_for_any_stream(&[&depth, &color], Duration::from_millis(2000))? {
+///         0 => println!("depth: {}", depth.read_frame::<OniDepthPixel>()?.timestamp()),
+///         _ => println!("color: {}", color.read_frame::<OniRGB888Pixel>()?.timestamp()),
+///     }
+/// }
+/// # }
+/// ```
+pub fn wait_for_any_stream<T: Into<Timeout>>(streams: &[&Stream], timeout: T) -> Result<usize, Status> {
+    let timeout = timeout.into();
+    if streams.iter().any(|stream| stream.backend().is_some()) {
+        return wait_for_backend_streams(streams, timeout);
+    }
+    let mut handles: Vec<OniStreamHandle> = streams.iter().map(|stream| stream.handle()).collect();
+    let mut ready_index: c_int = -1;
+    let status = unsafe {
+        oniWaitForAnyStream(
+            handles.as_mut_ptr(),
+            handles.len() as c_int,
+            &mut ready_index,
+            timeout.as_int(),
+        )
+    }.into();
+    match status {
+        Status::Ok => Ok(ready_index as usize),
+        _ => Err(status),
+    }
+}
+
+// OpenNI2 can't wait on the streams of a `DeviceBackend`, so every stream
+// gets a listener that wakes this thread when it has a new frame, and the
+// streams are checked for a ready frame each time. Of several ready backend
+// streams, the one with the earliest frame goes first, so that streams which
+// never block don't starve the others.
+fn wait_for_backend_streams(streams: &[&Stream], timeout: Timeout) -> Result<usize, Status> {
+    let deadline = match timeout {
+        Timeout::NONE => Some(Instant::now()),
+        Timeout::FOREVER => None,
+        Timeout::MILLISECONDS(ms) => Some(Instant::now() + Duration::from_millis(u64::from(ms))),
+    };
+    let signal = Arc::new((Mutex::new(false), Condvar::new()));
+    let _listeners = streams.iter()
+        .map(|stream| {
+            let signal = signal.clone();
+            stream.listener(move |_| {
+                let (ref woken, ref condvar) = *signal;
+                *woken.lock().expect("Stream wait signal poisoned") = true;
+                condvar.notify_all();
+            })
+        })
+        .collect::<Result<Vec<_>, Status>>()?;
+    let (ref woken, ref condvar) = *signal;
+    loop {
+        // Frames that arrive from here on wake the wait below, even if they
+        // arrive before it starts.
+        *woken.lock().expect("Stream wait signal poisoned") = false;
+        let mut earliest: Option<(usize, u64)> = None;
+        for (index, stream) in streams.iter().enumerate() {
+            match stream.backend() {
+                Some(backend) => {
+                    if let Some(timestamp) = backend.ready_frame_timestamp() {
+                        if earliest.is_none_or(|(_, earliest)| timestamp < earliest) {
+                            earliest = Some((index, timestamp));
+                        }
+                    }
+                },
+                None => {
+                    let mut handle = stream.handle();
+                    let mut ready_index: c_int = -1;
+                    if unsafe { oniWaitForAnyStream(&mut handle, 1, &mut ready_index, ONI_TIMEOUT_NONE) } == ONI_STATUS_OK {
+                        return Ok(index);
+                    }
+                },
+            }
+        }
+        if let Some((index, _)) = earliest {
+            return Ok(index);
+        }
+        let mut guard = woken.lock().expect("Stream wait signal poisoned");
+        while !*guard {
+            guard = match deadline {
+                None => condvar.wait(guard).expect("Stream wait signal poisoned"),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(Status::TimeOut);
+                    }
+                    condvar.wait_timeout(guard, deadline - now).expect("Stream wait signal poisoned").0
+                },
+            };
+        }
+    }
+}
+
 /// Register callbacks to execute whenever a device is connected, disconnected,
 /// or changes state. The `DeviceInfo` that is passed in as the first argument
 /// to the callbacks contains a `uri` field that can be used to open that
@@ -192,53 +373,47 @@ pub fn get_device_list() -> Vec<DeviceInfo> {
 /// register_device_callbacks(connect, disconnect, state_change);
 /// ```
 ///
-pub fn register_device_callbacks<'a, F1, F2, F3>(on_device_connected: F1, on_device_disconnected: F2, on_device_state_changed: F3) -> Result<DeviceCallbackHandle<'a>, Status>
-    where F1: 'a + FnMut(DeviceInfo), F2: 'a + FnMut(DeviceInfo), F3: 'a + FnMut(DeviceInfo, DeviceState) {
-    unsafe extern "C" fn on_device_connected_wrapper<F1, F2, F3>(info: *const OniDeviceInfo, cookie: *mut c_void) where F1: FnMut(DeviceInfo), F2: FnMut(DeviceInfo), F3: FnMut(DeviceInfo, DeviceState) {
-        let mut closures: Box<ClosureStruct<F1, F2, F3>> = Box::from_raw(cookie as *mut ClosureStruct<F1, F2, F3>);
+pub fn register_device_callbacks<F1, F2, F3>(on_device_connected: F1, on_device_disconnected: F2, on_device_state_changed: F3) -> Result<DeviceCallbackHandle, Status>
+    where F1: FnMut(DeviceInfo) + Send + 'static, F2: FnMut(DeviceInfo) + Send + 'static, F3: FnMut(DeviceInfo, DeviceState) + Send + 'static {
+    unsafe extern "C" fn on_device_connected_wrapper(info: *const OniDeviceInfo, cookie: *mut c_void) {
         let device_info = (*info).into();
-        (closures.on_device_connected)(device_info);
-        mem::forget(closures);
+        (*(cookie as *const Cookie<DeviceClosures>)).call(|closures| (closures.on_device_connected)(device_info));
     }
 
-    unsafe extern "C" fn on_device_disconnected_wrapper<F1, F2, F3>(info: *const OniDeviceInfo, cookie: *mut c_void) where F1: FnMut(DeviceInfo), F2: FnMut(DeviceInfo), F3: FnMut(DeviceInfo, DeviceState) {
-        let mut closures: Box<ClosureStruct<F1, F2, F3>> = Box::from_raw(cookie as *mut ClosureStruct<F1, F2, F3>);
+    unsafe extern "C" fn on_device_disconnected_wrapper(info: *const OniDeviceInfo, cookie: *mut c_void) {
         let device_info = (*info).into();
-        (closures.on_device_disconnected)(device_info);
-        mem::forget(closures);
+        (*(cookie as *const Cookie<DeviceClosures>)).call(|closures| (closures.on_device_disconnected)(device_info));
     }
 
-    unsafe extern "C" fn on_device_state_changed_wrapper<F1, F2, F3>(device_info: *const OniDeviceInfo, device_state: OniDeviceState, cookie: *mut c_void) where F1: FnMut(DeviceInfo), F2: FnMut(DeviceInfo), F3: FnMut(DeviceInfo, DeviceState) {
-        let mut closures: Box<ClosureStruct<F1, F2, F3>> = Box::from_raw(cookie as *mut ClosureStruct<F1, F2, F3>);
+    unsafe extern "C" fn on_device_state_changed_wrapper(device_info: *const OniDeviceInfo, device_state: OniDeviceState, cookie: *mut c_void) {
         let device_info = (*device_info).into();
         let device_state = device_state.into();
-        (closures.on_device_state_changed)(device_info, device_state);
-        mem::forget(closures);
+        (*(cookie as *const Cookie<DeviceClosures>)).call(|closures| (closures.on_device_state_changed)(device_info, device_state));
     }
 
-    let closures = Box::new(ClosureStruct {
-        on_device_connected,
-        on_device_disconnected,
-        on_device_state_changed,
-    });
+    let cookie = DEVICE_CALLBACK_COOKIES.cookie(Box::new(DeviceClosures {
+        on_device_connected: Box::new(on_device_connected),
+        on_device_disconnected: Box::new(on_device_disconnected),
+        on_device_state_changed: Box::new(on_device_state_changed),
+    }));
 
     let mut callbacks = OniDeviceCallbacks {
-        deviceConnected: Some(on_device_connected_wrapper::<F1, F2, F3>),
-        deviceDisconnected: Some(on_device_disconnected_wrapper::<F1, F2, F3>),
-        deviceStateChanged: Some(on_device_state_changed_wrapper::<F1, F2, F3>),
+        deviceConnected: Some(on_device_connected_wrapper),
+        deviceDisconnected: Some(on_device_disconnected_wrapper),
+        deviceStateChanged: Some(on_device_state_changed_wrapper),
     };
 
     let mut callbacks_handle: OniCallbackHandle = ptr::null_mut();
     let status = unsafe {
-        oniRegisterDeviceCallbacks(&mut callbacks, Box::into_raw(closures) as *mut _, &mut callbacks_handle)
+        oniRegisterDeviceCallbacks(&mut callbacks, cookie as *mut c_void, &mut callbacks_handle)
     }.into();
 
     match status {
-        Status::Ok => Ok(DeviceCallbackHandle {
-            callbacks_handle,
-            _closures_lifetime: PhantomData,
-        }),
-        _ => Err(status),
+        Status::Ok => Ok(DeviceCallbackHandle { callbacks_handle, cookie }),
+        _ => {
+            DEVICE_CALLBACK_COOKIES.retire(cookie);
+            Err(status)
+        },
     }
 }
 
@@ -305,26 +480,30 @@ pub fn set_log_level(severity: LogLevel) -> Status {
 }
 
 /// When this falls out of scope, callbacks registered with `register_device_callbacks`
-/// are unregistered.
+/// are unregistered and freed, once they have stopped running. Dropped from
+/// inside one of its callbacks, it frees them once the callback returns.
 #[derive(Debug)]
-pub struct DeviceCallbackHandle<'a>{
+pub struct DeviceCallbackHandle {
     callbacks_handle: OniCallbackHandle,
-    _closures_lifetime: PhantomData<&'a ()>,
+    cookie: *mut Cookie<DeviceClosures>,
 }
 
-impl<'a> DeviceCallbackHandle<'a> {
+impl DeviceCallbackHandle {
     pub fn unregister(self) {} // POOF! Bye bye
 }
 
-impl<'a> Drop for DeviceCallbackHandle<'a> {
+impl Drop for DeviceCallbackHandle {
     fn drop(&mut self) {
-        unsafe { oniUnregisterDeviceCallbacks(self.callbacks_handle) }
+        unsafe { oniUnregisterDeviceCallbacks(self.callbacks_handle) };
+        DEVICE_CALLBACK_COOKIES.retire(self.cookie);
     }
 }
 
-struct ClosureStruct<F1, F2, F3>
-    where F1: FnMut(DeviceInfo), F2: FnMut(DeviceInfo), F3: FnMut(DeviceInfo, DeviceState) {
-    on_device_connected: F1,
-    on_device_disconnected: F2,
-    on_device_state_changed: F3,
+struct DeviceClosures {
+    on_device_connected: Box<dyn FnMut(DeviceInfo) + Send>,
+    on_device_disconnected: Box<dyn FnMut(DeviceInfo) + Send>,
+    on_device_state_changed: Box<dyn FnMut(DeviceInfo, DeviceState) + Send>,
 }
+
+// Cookies of unregistered device callbacks, freed by `shutdown`.
+static DEVICE_CALLBACK_COOKIES: CookiePool<DeviceClosures> = CookiePool::new();
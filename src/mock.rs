@@ -0,0 +1,1093 @@
+//! A device that generates frames in software, for testing code that uses
+//! devices and streams without any hardware or OpenNI2 drivers.
+//!
+//! A `MockDevice` has depth, color and IR sensors by default, with video
+//! modes and fields of view like those of a PS1080. Wrapped in a `Device`
+//! with `Device::from_backend`, its streams support the same properties,
+//! listeners and coordinate conversions as real ones, and their frames are
+//! deterministic: frame `n` of a stream is always the same, and is stamped
+//! `n / fps` seconds after the first.
+//!
+//! What a sensor renders is decided by its `FrameGenerator`, `TestPattern`
+//! unless another is given, and `MockSensor::fail_frame` makes reading a
+//! given frame fail, to exercise error handling.
+//!
+//! # Example
+//! ```
+//! # use openni2::{Device, SensorType, Status, OniDepthPixel, VideoMode, PixelFormat};
+//! # use openni2::mock::{MockDevice, MockSensor};
+//! # fn main() -> Result<(), Status> {
+//! let depth = MockSensor::new(SensorType::DEPTH, vec![VideoMode {
+//!     pixel_format: PixelFormat::DEPTH_1_MM,
+//!     resolution_x: 320,
+//!     resolution_y: 240,
+//!     fps: 30,
+//! }]).fail_frame(2, Status::TimeOut);
+//! let device = Device::from_backend(MockDevice::new().sensor(depth));
+//! let stream = device.create_stream(SensorType::DEPTH)?;
+//! stream.start()?;
+//!
+//! let frames: Vec<_> = stream.frames::<OniDepthPixel>().take(4).collect();
+//! assert_eq!(frames[1].as_ref().unwrap().timestamp(), 33_333);
+//! assert!(frames[2].is_err());
+//! assert_eq!(frames[3].as_ref().unwrap().index(), 3);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::os::raw::{c_char, c_float, c_int};
+use std::sync::{Arc, Weak, Mutex, MutexGuard, Condvar};
+use std::time::{Duration, Instant};
+use std::{fmt, mem, ptr, thread};
+use std::thread::ThreadId;
+
+use openni2_sys::*;
+use backend::{DeviceBackend, StreamBackend, FrameData};
+use device::{DeviceInfo, PropertyData, as_bytes_mut};
+use intrinsics::Intrinsics;
+use playback::PlaybackControl;
+use stream::Cropping;
+use types::{Status, SensorType, PixelFormat, ImageRegistrationMode, VideoMode, SensorInfo, bytes_per_pixel};
+
+/// What a `FrameGenerator` is asked to render.
+#[derive(Debug, Copy, Clone)]
+pub struct FrameRequest {
+    pub sensor_type: SensorType,
+    pub video_mode: VideoMode,
+    /// Horizontal field of view, in radians
+    pub horizontal_fov: f32,
+    /// Vertical field of view, in radians
+    pub vertical_fov: f32,
+    pub index: usize,
+    /// Timestamp in microseconds
+    pub timestamp: u64,
+}
+
+/// Renders the frames of a `MockSensor`.
+///
+/// A generator returns the pixel data of a whole, uncropped and unmirrored
+/// frame, in the byte layout of the requested video mode's pixel format:
+/// `resolution_x * resolution_y` pixels, where a pair of `YUV422` or `YUYV`
+/// pixels takes 4 bytes. The stream crops and mirrors it as configured.
+///
+/// Closures taking a `&FrameRequest` are generators too.
+pub trait FrameGenerator: Send + Sync {
+    fn generate(&self, request: &FrameRequest) -> Result<Vec<u8>, Status>;
+}
+
+impl<F> FrameGenerator for F where F: Fn(&FrameRequest) -> Result<Vec<u8>, Status> + Send + Sync {
+    fn generate(&self, request: &FrameRequest) -> Result<Vec<u8>, Status> {
+        self(request)
+    }
+}
+
+/// The default `FrameGenerator`, which draws simple patterns that are easy
+/// to check in tests. For the pixel at `(x, y)` of frame `n`:
+///
+/// * `DEPTH_1_MM`: `500 + 10 * ((x + y + n) % 400)` millimeters, except
+///   for the first column, which has no depth reading (0). `DEPTH_100_UM`
+///   is the same depth in units of 100 micrometers, and `SHIFT_9_2` and
+///   `SHIFT_9_3` are `300 + (x + y + n) % 700`.
+/// * `GRAY16`: `((x ^ y) + n) % 1024`, and `GRAY8`: `((x ^ y) + n) % 256`.
+/// * `RGB888`: red `x % 256`, green `y % 256`, blue `n % 256`.
+/// * `YUV422` and `YUYV`: luma `(x + n) % 256` and neutral chroma.
+///
+/// `JPEG` frames can't be generated and return `Err(Status::NotSupported)`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TestPattern;
+
+impl FrameGenerator for TestPattern {
+    fn generate(&self, request: &FrameRequest) -> Result<Vec<u8>, Status> {
+        let mode = request.video_mode;
+        let (width, height) = (mode.resolution_x.max(0) as usize, mode.resolution_y.max(0) as usize);
+        let n = request.index;
+        let mut data = Vec::with_capacity(width * height * pixel_size(mode.pixel_format));
+        let depth = |x: usize, y: usize| if x == 0 { 0 } else { 500 + 10 * ((x + y + n) % 400) as u16 };
+
+        for y in 0..height {
+            for x in 0..width {
+                match mode.pixel_format {
+                    PixelFormat::DEPTH_1_MM => data.extend_from_slice(&depth(x, y).to_le_bytes()),
+                    PixelFormat::DEPTH_100_UM => data.extend_from_slice(&(depth(x, y) * 10).to_le_bytes()),
+                    PixelFormat::SHIFT_9_2 | PixelFormat::SHIFT_9_3 => {
+                        data.extend_from_slice(&(300 + ((x + y + n) % 700) as u16).to_le_bytes())
+                    },
+                    PixelFormat::GRAY16 => data.extend_from_slice(&((((x ^ y) + n) % 1024) as u16).to_le_bytes()),
+                    PixelFormat::GRAY8 => data.push(((x ^ y) + n) as u8),
+                    PixelFormat::RGB888 => data.extend_from_slice(&[x as u8, y as u8, n as u8]),
+                    PixelFormat::YUV422 if x % 2 == 0 => data.extend_from_slice(&[128, (x + n) as u8, 128, (x + 1 + n) as u8]),
+                    PixelFormat::YUYV if x % 2 == 0 => data.extend_from_slice(&[(x + n) as u8, 128, (x + 1 + n) as u8, 128]),
+                    PixelFormat::YUV422 | PixelFormat::YUYV => {},
+                    PixelFormat::JPEG => return Err(Status::NotSupported),
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+// Bytes per pixel of frame data, where 4:2:2 formats pack two pixels into
+// four bytes.
+fn pixel_size(format: PixelFormat) -> usize {
+    match format {
+        PixelFormat::YUV422 | PixelFormat::YUYV => 2,
+        format => bytes_per_pixel(format),
+    }
+}
+
+// Fields of view of a PS1080's sensors, in radians.
+pub(crate) const DEPTH_FOV: (f32, f32) = (1.014_468_6, 0.789_809);
+pub(crate) const COLOR_FOV: (f32, f32) = (1.022_6, 0.796_615_66);
+
+/// One sensor of a `MockDevice`: its video modes, field of view, and how
+/// its frames are generated.
+#[derive(Clone)]
+pub struct MockSensor {
+    sensor_type: SensorType,
+    video_modes: Vec<VideoMode>,
+    horizontal_fov: f32,
+    vertical_fov: f32,
+    generator: Arc<dyn FrameGenerator>,
+    timestamp_offset: u64,
+    timestamps: Option<Arc<Vec<u64>>>,
+    frame_limit: Option<usize>,
+    failures: HashMap<usize, Status>,
+}
+
+impl MockSensor {
+    /// Creates a sensor that supports `video_modes`, the first of which is
+    /// the default, with a PS1080's field of view and a `TestPattern`.
+    ///
+    /// # Panics
+    /// Panics if `video_modes` is empty.
+    pub fn new(sensor_type: SensorType, video_modes: Vec<VideoMode>) -> Self {
+        assert!(!video_modes.is_empty(), "A mock sensor needs at least one video mode");
+        let (horizontal_fov, vertical_fov) = match sensor_type {
+            SensorType::COLOR => COLOR_FOV,
+            SensorType::DEPTH | SensorType::IR => DEPTH_FOV,
+        };
+        MockSensor {
+            sensor_type,
+            video_modes,
+            horizontal_fov,
+            vertical_fov,
+            generator: Arc::new(TestPattern),
+            timestamp_offset: 0,
+            timestamps: None,
+            frame_limit: None,
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Sets the fields of view, in radians.
+    pub fn field_of_view(mut self, horizontal_fov: f32, vertical_fov: f32) -> Self {
+        self.horizontal_fov = horizontal_fov;
+        self.vertical_fov = vertical_fov;
+        self
+    }
+
+    /// Renders frames with `generator` instead of a `TestPattern`.
+    pub fn generator<G: FrameGenerator + 'static>(mut self, generator: G) -> Self {
+        self.generator = Arc::new(generator);
+        self
+    }
+
+    /// Adds `offset` microseconds to every timestamp, e.g. to test matching
+    /// frames of sensors that aren't in sync.
+    pub fn timestamp_offset(mut self, offset: u64) -> Self {
+        self.timestamp_offset = offset;
+        self
+    }
+
+    /// Stamps frame `n` with `timestamps[n]` microseconds, instead of `n /
+    /// fps` seconds, and ends the stream after the last of them. Frames are
+    /// paced by their timestamps when played back in real time.
+    pub fn timestamps(mut self, timestamps: Vec<u64>) -> Self {
+        self.frame_limit = Some(timestamps.len());
+        self.timestamps = Some(Arc::new(timestamps));
+        self
+    }
+
+    /// Ends the stream after `frames` frames. Reading past the end returns
+    /// `Err(Status::TimeOut)`, and the stream's `get_number_of_frames`
+    /// returns the limit, like a recording's.
+    pub fn frame_limit(mut self, frames: usize) -> Self {
+        self.frame_limit = Some(frames);
+        self
+    }
+
+    /// Makes reading the frame with index `index` return `Err(status)`. The
+    /// frame is lost, and the next read returns frame `index + 1`.
+    pub fn fail_frame(mut self, index: usize, status: Status) -> Self {
+        self.failures.insert(index, status);
+        self
+    }
+
+    fn timestamp(&self, index: usize, fps: c_int) -> u64 {
+        let period = 1_000_000 / fps.max(1) as u64;
+        let timestamp = match self.timestamps {
+            Some(ref timestamps) if index < timestamps.len() => timestamps[index],
+            // Past the end, frames keep coming at the frame rate
+            Some(ref timestamps) if !timestamps.is_empty() => {
+                timestamps[timestamps.len() - 1] + (index - timestamps.len() + 1) as u64 * period
+            },
+            _ => index as u64 * 1_000_000 / fps.max(1) as u64,
+        };
+        self.timestamp_offset + timestamp
+    }
+
+    // Returns the index of the first frame stamped at or after `timestamp`.
+    fn index_at(&self, timestamp: u64, fps: c_int) -> usize {
+        let mut index = match self.timestamps {
+            Some(ref timestamps) => timestamps.partition_point(|&other| self.timestamp_offset + other < timestamp),
+            None => (timestamp.saturating_sub(self.timestamp_offset) * fps.max(1) as u64 / 1_000_000) as usize,
+        };
+        while self.timestamp(index, fps) < timestamp {
+            index += 1;
+        }
+        self.frame_limit.map_or(index, |limit| index.min(limit))
+    }
+
+    // When frame `index` is due, counting from the frame the stream started
+    // or last seeked at, when played at `speed` times real time.
+    fn due_time(&self, state: &StreamState, index: usize, speed: f32) -> Instant {
+        let fps = state.video_mode.fps;
+        let elapsed = self.timestamp(index, fps).saturating_sub(self.timestamp(state.first_index, fps));
+        state.started_at + Duration::from_micros((elapsed as f64 / f64::from(speed)) as u64)
+    }
+}
+
+impl fmt::Debug for MockSensor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockSensor")
+            .field("sensor_type", &self.sensor_type)
+            .field("video_modes", &self.video_modes)
+            .field("horizontal_fov", &self.horizontal_fov)
+            .field("vertical_fov", &self.vertical_fov)
+            .field("timestamp_offset", &self.timestamp_offset)
+            .field("frame_limit", &self.frame_limit)
+            .field("failures", &self.failures)
+            .finish()
+    }
+}
+
+fn video_mode(pixel_format: PixelFormat, resolution_x: c_int, resolution_y: c_int, fps: c_int) -> VideoMode {
+    VideoMode { pixel_format, resolution_x, resolution_y, fps }
+}
+
+/// A `DeviceBackend` whose streams generate frames in software. See the
+/// module documentation.
+pub struct MockDevice {
+    info: DeviceInfo,
+    sensors: Vec<MockSensor>,
+    playback: bool,
+    shared: Arc<DeviceShared>,
+}
+
+// State shared by a device and its streams.
+struct DeviceShared {
+    settings: Mutex<DeviceSettings>,
+    // The streams of a recording move together when one of them seeks.
+    streams: Mutex<Vec<Weak<Shared>>>,
+}
+
+#[derive(Debug)]
+struct DeviceSettings {
+    image_registration: OniImageRegistrationMode,
+    color_depth_sync: bool,
+    // Playback speed, where a speed of 0 or less doesn't pace frames
+    speed: f32,
+    repeat: bool,
+}
+
+impl DeviceShared {
+    fn settings(&self) -> MutexGuard<'_, DeviceSettings> {
+        self.settings.lock().expect("Mock device settings poisoned")
+    }
+}
+
+impl MockDevice {
+    /// Creates a device with depth (`DEPTH_1_MM` or `DEPTH_100_UM`), color
+    /// (`RGB888` or `YUV422`) and IR (`GRAY16`) sensors, each at 640x480 or
+    /// 320x240 and 30 frames per second.
+    pub fn new() -> Self {
+        let modes = |formats: &[PixelFormat]| formats.iter()
+            .flat_map(|&format| vec![video_mode(format, 640, 480, 30), video_mode(format, 320, 240, 30)])
+            .collect::<Vec<_>>();
+        MockDevice {
+            info: DeviceInfo {
+                uri: "mock://".to_owned(),
+                vendor: "openni2-rs".to_owned(),
+                name: "MockDevice".to_owned(),
+                usb_vendor_id: 0,
+                usb_product_id: 0,
+            },
+            sensors: vec![
+                MockSensor::new(SensorType::DEPTH, modes(&[PixelFormat::DEPTH_1_MM, PixelFormat::DEPTH_100_UM])),
+                MockSensor::new(SensorType::COLOR, modes(&[PixelFormat::RGB888, PixelFormat::YUV422])),
+                MockSensor::new(SensorType::IR, modes(&[PixelFormat::GRAY16])),
+            ],
+            playback: false,
+            shared: Arc::new(DeviceShared {
+                settings: Mutex::new(DeviceSettings {
+                    image_registration: ONI_IMAGE_REGISTRATION_OFF,
+                    color_depth_sync: false,
+                    speed: PlaybackControl::MANUAL_SPEED,
+                    repeat: false,
+                }),
+                streams: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Sets the `DeviceInfo` returned by `Device::info`.
+    pub fn info(mut self, info: DeviceInfo) -> Self {
+        self.info = info;
+        self
+    }
+
+    /// Adds a sensor, replacing the device's sensor of the same type.
+    pub fn sensor(mut self, sensor: MockSensor) -> Self {
+        self.sensors.retain(|other| other.sensor_type != sensor.sensor_type);
+        self.sensors.push(sensor);
+        self
+    }
+
+    /// Removes the device's sensor of a type.
+    pub fn without_sensor(mut self, sensor_type: SensorType) -> Self {
+        self.sensors.retain(|sensor| sensor.sensor_type != sensor_type);
+        self
+    }
+
+    /// Renders the frames of every sensor with `generator`.
+    pub fn generator<G: FrameGenerator + 'static>(mut self, generator: G) -> Self {
+        let generator: Arc<dyn FrameGenerator> = Arc::new(generator);
+        for sensor in &mut self.sensors {
+            sensor.generator = generator.clone();
+        }
+        self
+    }
+
+    /// Sets the fields of view of every sensor, in radians.
+    pub fn field_of_view(mut self, horizontal_fov: f32, vertical_fov: f32) -> Self {
+        self.sensors = self.sensors.into_iter()
+            .map(|sensor| sensor.field_of_view(horizontal_fov, vertical_fov))
+            .collect();
+        self
+    }
+
+    /// Paces frames in real time, so that reading frame `n` blocks until
+    /// `n / fps` seconds after the stream was started. By default frames
+    /// can be read as fast as they are generated. Listener callbacks are
+    /// always paced in real time.
+    pub fn realtime(self, realtime: bool) -> Self {
+        self.shared.settings().speed = if realtime { 1.0 } else { PlaybackControl::MANUAL_SPEED };
+        self
+    }
+
+    /// Makes the device act like a recording, so that `Device::playback`
+    /// can seek its streams and change their speed. Seeking a stream moves
+    /// the device's other streams to their first frames stamped at or after
+    /// the frame it seeks to. Playback doesn't repeat unless turned on with
+    /// `PlaybackControl::set_repeat`.
+    pub fn playback(mut self, playback: bool) -> Self {
+        self.playback = playback;
+        self
+    }
+
+    fn find_sensor(&self, sensor_type: SensorType) -> Option<&MockSensor> {
+        self.sensors.iter().find(|sensor| sensor.sensor_type == sensor_type)
+    }
+
+    fn settings(&self) -> MutexGuard<'_, DeviceSettings> {
+        self.shared.settings()
+    }
+}
+
+impl fmt::Debug for MockDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockDevice")
+            .field("info", &self.info)
+            .field("sensors", &self.sensors)
+            .field("playback", &self.playback)
+            .field("settings", &*self.settings())
+            .finish()
+    }
+}
+
+impl Default for MockDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceBackend for MockDevice {
+    fn info(&self) -> Result<DeviceInfo, Status> {
+        Ok(self.info.clone())
+    }
+
+    fn sensor_info(&self, sensor_type: SensorType) -> Option<SensorInfo> {
+        self.find_sensor(sensor_type).map(|sensor| SensorInfo {
+            sensor_type,
+            video_modes: sensor.video_modes.clone(),
+        })
+    }
+
+    fn create_stream(&self, sensor_type: SensorType) -> Result<Box<dyn StreamBackend>, Status> {
+        let sensor = self.find_sensor(sensor_type).ok_or(Status::NotSupported)?;
+        Ok(Box::new(MockStream::new(sensor.clone(), self.shared.clone(), self.playback)))
+    }
+
+    fn is_property_supported(&self, property: OniDeviceProperty) -> bool {
+        matches!(
+            property,
+            ONI_DEVICE_PROPERTY_FIRMWARE_VERSION
+            | ONI_DEVICE_PROPERTY_DRIVER_VERSION
+            | ONI_DEVICE_PROPERTY_HARDWARE_VERSION
+            | ONI_DEVICE_PROPERTY_SERIAL_NUMBER
+            | ONI_DEVICE_PROPERTY_IMAGE_REGISTRATION
+        ) || (self.playback && matches!(
+            property,
+            ONI_DEVICE_PROPERTY_PLAYBACK_SPEED | ONI_DEVICE_PROPERTY_PLAYBACK_REPEAT_ENABLED
+        ))
+    }
+
+    fn get_property(&self, property: OniDeviceProperty, data: &mut [u8]) -> Result<(), Status> {
+        match property {
+            ONI_DEVICE_PROPERTY_FIRMWARE_VERSION => write_property(data, c_string(env!("CARGO_PKG_VERSION"))),
+            ONI_DEVICE_PROPERTY_DRIVER_VERSION => write_property(data, OniVersion {
+                major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+                minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+                maintenance: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+                build: 0,
+            }),
+            ONI_DEVICE_PROPERTY_HARDWARE_VERSION => write_property::<c_int>(data, 0),
+            ONI_DEVICE_PROPERTY_SERIAL_NUMBER => write_property(data, c_string("0000000000")),
+            ONI_DEVICE_PROPERTY_IMAGE_REGISTRATION => write_property(data, self.settings().image_registration),
+            ONI_DEVICE_PROPERTY_PLAYBACK_SPEED if self.playback => write_property::<c_float>(data, self.settings().speed),
+            ONI_DEVICE_PROPERTY_PLAYBACK_REPEAT_ENABLED if self.playback => {
+                write_property::<c_int>(data, self.settings().repeat as c_int)
+            },
+            _ => Err(Status::NotSupported),
+        }
+    }
+
+    fn set_property(&self, property: OniDeviceProperty, data: &[u8]) -> Result<(), Status> {
+        match property {
+            ONI_DEVICE_PROPERTY_IMAGE_REGISTRATION => {
+                let mode = read_property::<OniImageRegistrationMode>(data)?;
+                if mode != ONI_IMAGE_REGISTRATION_OFF && mode != ONI_IMAGE_REGISTRATION_DEPTH_TO_COLOR {
+                    return Err(Status::BadParameter);
+                }
+                self.settings().image_registration = mode;
+                Ok(())
+            },
+            ONI_DEVICE_PROPERTY_PLAYBACK_SPEED if self.playback => {
+                self.settings().speed = read_property::<c_float>(data)?;
+                Ok(())
+            },
+            ONI_DEVICE_PROPERTY_PLAYBACK_REPEAT_ENABLED if self.playback => {
+                self.settings().repeat = read_property::<c_int>(data)? != 0;
+                Ok(())
+            },
+            _ => Err(Status::NotSupported),
+        }
+    }
+
+    fn is_image_registration_mode_supported(&self, _mode: ImageRegistrationMode) -> bool {
+        true
+    }
+
+    fn color_depth_sync(&self) -> bool {
+        self.settings().color_depth_sync
+    }
+
+    fn enable_color_depth_sync(&self, enabled: bool) -> Status {
+        self.settings().color_depth_sync = enabled;
+        Status::Ok
+    }
+
+    // Streams seek with `StreamBackend::seek`, since the command's stream
+    // handle can't identify a backend's stream.
+    fn is_command_supported(&self, command: OniDeviceCommand) -> bool {
+        self.playback && command == ONI_DEVICE_COMMAND_SEEK
+    }
+}
+
+fn c_string(value: &str) -> [c_char; ONI_MAX_STR] {
+    let mut string = [0; ONI_MAX_STR];
+    for (c, &byte) in string.iter_mut().zip(value.as_bytes().iter().take(ONI_MAX_STR - 1)) {
+        *c = byte as c_char;
+    }
+    string
+}
+
+fn read_property<T: PropertyData>(data: &[u8]) -> Result<T, Status> {
+    if data.len() != mem::size_of::<T>() {
+        return Err(Status::BadParameter);
+    }
+    Ok(unsafe { ptr::read_unaligned(data.as_ptr() as *const T) })
+}
+
+fn write_property<T: PropertyData>(data: &mut [u8], value: T) -> Result<(), Status> {
+    if data.len() != mem::size_of::<T>() {
+        return Err(Status::BadParameter);
+    }
+    unsafe { ptr::write_unaligned(data.as_mut_ptr() as *mut T, value) };
+    Ok(())
+}
+
+/// A stream of a `MockDevice`'s sensor, created through
+/// `Device::create_stream`.
+pub struct MockStream {
+    shared: Arc<Shared>,
+    device: Arc<DeviceShared>,
+    playback: bool,
+}
+
+// State shared with the thread that raises new frame events, and with the
+// other streams of the device.
+struct Shared {
+    sensor: MockSensor,
+    state: Mutex<StreamState>,
+    state_changed: Condvar,
+    callbacks: Mutex<Callbacks>,
+    callbacks_idle: Condvar,
+}
+
+struct StreamState {
+    video_mode: VideoMode,
+    mirroring: bool,
+    cropping: Option<Cropping>,
+    settings: HashMap<OniStreamProperty, c_int>,
+    started: bool,
+    // Bumped on every start and stop, so that the event thread of an
+    // earlier start knows to exit.
+    generation: u64,
+    // When the stream started, or last seeked or repeated, and the frame it
+    // did so at
+    started_at: Instant,
+    first_index: usize,
+    next_index: usize,
+}
+
+struct Callbacks {
+    next_id: usize,
+    callbacks: Vec<(usize, Box<dyn FnMut() + Send>)>,
+    // The event thread, while it has taken the callbacks out to call them
+    raising: Option<ThreadId>,
+    // Callbacks unregistered from inside a callback, removed once they have
+    // all been called
+    unregistered: Vec<usize>,
+}
+
+impl MockStream {
+    fn new(sensor: MockSensor, device: Arc<DeviceShared>, playback: bool) -> Self {
+        let mut settings = HashMap::new();
+        if sensor.sensor_type == SensorType::COLOR {
+            settings.insert(ONI_STREAM_PROPERTY_AUTO_WHITE_BALANCE, 1);
+            settings.insert(ONI_STREAM_PROPERTY_AUTO_EXPOSURE, 1);
+            settings.insert(ONI_STREAM_PROPERTY_EXPOSURE, 0);
+            settings.insert(ONI_STREAM_PROPERTY_GAIN, 100);
+        }
+        let state = StreamState {
+            video_mode: sensor.video_modes[0],
+            mirroring: false,
+            cropping: None,
+            settings,
+            started: false,
+            generation: 0,
+            started_at: Instant::now(),
+            first_index: 0,
+            next_index: 0,
+        };
+        let shared = Arc::new(Shared {
+            sensor,
+            state: Mutex::new(state),
+            state_changed: Condvar::new(),
+            callbacks: Mutex::new(Callbacks { next_id: 0, callbacks: Vec::new(), raising: None, unregistered: Vec::new() }),
+            callbacks_idle: Condvar::new(),
+        });
+        {
+            let mut streams = device.streams.lock().expect("Mock device streams poisoned");
+            streams.retain(|stream| stream.strong_count() > 0);
+            streams.push(Arc::downgrade(&shared));
+        }
+        MockStream { shared, device, playback }
+    }
+
+    fn state(&self) -> MutexGuard<'_, StreamState> {
+        self.shared.state()
+    }
+
+    fn speed(&self) -> f32 {
+        self.device.settings().speed
+    }
+
+    fn repeat(&self) -> bool {
+        self.playback && self.device.settings().repeat
+    }
+
+    fn intrinsics(&self) -> Intrinsics {
+        let mode = self.state().video_mode;
+        Intrinsics::from_fov(self.shared.sensor.horizontal_fov, self.shared.sensor.vertical_fov, mode.resolution_x as u16, mode.resolution_y as u16)
+    }
+
+    fn timestamp(&self, state: &StreamState, index: usize) -> u64 {
+        self.shared.sensor.timestamp(index, state.video_mode.fps)
+    }
+
+    fn at_end(&self, state: &StreamState) -> bool {
+        self.shared.sensor.frame_limit.is_some_and(|limit| state.next_index >= limit)
+    }
+
+    fn depth_range(&self, format: PixelFormat) -> Option<(c_int, c_int)> {
+        match format {
+            PixelFormat::DEPTH_1_MM => Some((0, 10_000)),
+            PixelFormat::DEPTH_100_UM => Some((0, 65_535)),
+            PixelFormat::SHIFT_9_2 | PixelFormat::SHIFT_9_3 => Some((0, 2047)),
+            _ => None,
+        }
+    }
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, StreamState> {
+        self.state.lock().expect("Mock stream state poisoned")
+    }
+
+    // Makes frame `index` the next to be read, and paces the frames after
+    // it from now.
+    fn move_to(&self, index: usize) {
+        let mut state = self.state();
+        state.next_index = index;
+        state.first_index = index;
+        state.started_at = Instant::now();
+        self.state_changed.notify_all();
+    }
+}
+
+// Raises new frame events at the stream's frame rate, or its playback speed,
+// until the stream is stopped or restarted. A stream that runs out of frames
+// waits for a seek, or for repeat to be turned on.
+fn raise_events(shared: Arc<Shared>, device: Arc<DeviceShared>, playback: bool, generation: u64) {
+    let mut tick = 0;
+    let mut started_at = None;
+    'events: loop {
+        let (speed, repeat) = {
+            let settings = device.settings();
+            (if settings.speed > 0.0 { settings.speed } else { 1.0 }, playback && settings.repeat)
+        };
+        {
+            let mut state = shared.state();
+            loop {
+                if !state.started || state.generation != generation {
+                    return;
+                }
+                if started_at != Some(state.started_at) {
+                    // Started, seeked or repeated
+                    started_at = Some(state.started_at);
+                    tick = 0;
+                }
+                if !repeat && shared.sensor.frame_limit.is_some_and(|limit| state.next_index >= limit) {
+                    // Repeat is a device setting, which doesn't notify
+                    // `state_changed`, so check on it once a frame.
+                    let period = Duration::from_micros(1_000_000 / state.video_mode.fps.max(1) as u64);
+                    drop(shared.state_changed.wait_timeout(state, period).expect("Mock stream state poisoned"));
+                    continue 'events;
+                }
+                let due = {
+                    let first_index = state.first_index;
+                    shared.sensor.due_time(&state, first_index + tick, speed)
+                };
+                let now = Instant::now();
+                if now >= due {
+                    break;
+                }
+                state = shared.state_changed.wait_timeout(state, due - now).expect("Mock stream state poisoned").0;
+            }
+        }
+        tick += 1;
+        // Callbacks are called without the lock, so that they can register
+        // and unregister callbacks themselves
+        let mut raised = {
+            let mut callbacks = shared.callbacks.lock().expect("Mock stream callbacks poisoned");
+            callbacks.raising = Some(thread::current().id());
+            mem::take(&mut callbacks.callbacks)
+        };
+        for &mut (_, ref mut callback) in raised.iter_mut() {
+            callback();
+        }
+        let mut callbacks = shared.callbacks.lock().expect("Mock stream callbacks poisoned");
+        let unregistered = mem::take(&mut callbacks.unregistered);
+        raised.retain(|&(id, _)| !unregistered.contains(&id));
+        raised.append(&mut callbacks.callbacks);
+        callbacks.callbacks = raised;
+        callbacks.raising = None;
+        shared.callbacks_idle.notify_all();
+    }
+}
+
+unsafe impl StreamBackend for MockStream {
+    fn start(&self) -> Result<(), Status> {
+        let generation = {
+            let mut state = self.state();
+            if state.started {
+                return Ok(());
+            }
+            state.started = true;
+            state.generation += 1;
+            state.started_at = Instant::now();
+            state.first_index = state.next_index;
+            state.generation
+        };
+        let (shared, device, playback) = (self.shared.clone(), self.device.clone(), self.playback);
+        thread::Builder::new()
+            .name("openni2 mock stream".to_owned())
+            .spawn(move || raise_events(shared, device, playback, generation))
+            .map_err(|err| Status::Error(format!("Couldn't start mock stream thread: {}", err)))?;
+        Ok(())
+    }
+
+    fn stop(&self) {
+        let mut state = self.state();
+        if state.started {
+            state.started = false;
+            state.generation += 1;
+            self.shared.state_changed.notify_all();
+        }
+    }
+
+    fn sensor_info(&self) -> Option<SensorInfo> {
+        Some(SensorInfo {
+            sensor_type: self.shared.sensor.sensor_type,
+            video_modes: self.shared.sensor.video_modes.clone(),
+        })
+    }
+
+    fn is_property_supported(&self, property: OniStreamProperty) -> bool {
+        let state = self.state();
+        match property {
+            ONI_STREAM_PROPERTY_CROPPING
+            | ONI_STREAM_PROPERTY_HORIZONTAL_FOV
+            | ONI_STREAM_PROPERTY_VERTICAL_FOV
+            | ONI_STREAM_PROPERTY_VIDEO_MODE
+            | ONI_STREAM_PROPERTY_STRIDE
+            | ONI_STREAM_PROPERTY_MIRRORING => true,
+            ONI_STREAM_PROPERTY_MAX_VALUE | ONI_STREAM_PROPERTY_MIN_VALUE => {
+                self.depth_range(state.video_mode.pixel_format).is_some()
+            },
+            ONI_STREAM_PROPERTY_NUMBER_OF_FRAMES => self.shared.sensor.frame_limit.is_some(),
+            property => state.settings.contains_key(&property),
+        }
+    }
+
+    fn get_property(&self, property: OniStreamProperty, data: &mut [u8]) -> Result<(), Status> {
+        let state = self.state();
+        let mode = state.video_mode;
+        match property {
+            ONI_STREAM_PROPERTY_CROPPING => write_property(data, match state.cropping {
+                Some(cropping) => OniCropping {
+                    enabled: 1,
+                    width: c_int::from(cropping.width),
+                    height: c_int::from(cropping.height),
+                    originX: c_int::from(cropping.origin_x),
+                    originY: c_int::from(cropping.origin_y),
+                },
+                None => OniCropping { enabled: 0, width: 0, height: 0, originX: 0, originY: 0 },
+            }),
+            ONI_STREAM_PROPERTY_HORIZONTAL_FOV => write_property::<c_float>(data, self.shared.sensor.horizontal_fov),
+            ONI_STREAM_PROPERTY_VERTICAL_FOV => write_property::<c_float>(data, self.shared.sensor.vertical_fov),
+            ONI_STREAM_PROPERTY_VIDEO_MODE => write_property(data, OniVideoMode {
+                pixelFormat: mode.pixel_format as OniPixelFormat,
+                resolutionX: mode.resolution_x,
+                resolutionY: mode.resolution_y,
+                fps: mode.fps,
+            }),
+            ONI_STREAM_PROPERTY_STRIDE => {
+                let width = state.cropping.map_or(mode.resolution_x, |cropping| c_int::from(cropping.width));
+                write_property::<c_int>(data, width * pixel_size(mode.pixel_format) as c_int)
+            },
+            ONI_STREAM_PROPERTY_MIRRORING => write_property::<c_int>(data, state.mirroring as c_int),
+            ONI_STREAM_PROPERTY_MIN_VALUE | ONI_STREAM_PROPERTY_MAX_VALUE => {
+                let (min, max) = self.depth_range(mode.pixel_format).ok_or(Status::NotSupported)?;
+                write_property::<c_int>(data, if property == ONI_STREAM_PROPERTY_MIN_VALUE { min } else { max })
+            },
+            ONI_STREAM_PROPERTY_NUMBER_OF_FRAMES => {
+                let limit = self.shared.sensor.frame_limit.ok_or(Status::NotSupported)?;
+                write_property::<c_int>(data, limit as c_int)
+            },
+            property => match state.settings.get(&property) {
+                Some(&value) => write_property::<c_int>(data, value),
+                None => Err(Status::NotSupported),
+            },
+        }
+    }
+
+    fn set_property(&self, property: OniStreamProperty, data: &[u8]) -> Result<(), Status> {
+        let mut state = self.state();
+        match property {
+            ONI_STREAM_PROPERTY_CROPPING => {
+                let cropping = read_property::<OniCropping>(data)?;
+                if cropping.enabled == 0 {
+                    state.cropping = None;
+                    return Ok(());
+                }
+                let mode = state.video_mode;
+                if cropping.width <= 0 || cropping.height <= 0 || cropping.originX < 0 || cropping.originY < 0
+                    || cropping.originX + cropping.width > mode.resolution_x
+                    || cropping.originY + cropping.height > mode.resolution_y {
+                    return Err(Status::BadParameter);
+                }
+                state.cropping = Some(Cropping {
+                    width: cropping.width as u16,
+                    height: cropping.height as u16,
+                    origin_x: cropping.originX as u16,
+                    origin_y: cropping.originY as u16,
+                });
+                Ok(())
+            },
+            ONI_STREAM_PROPERTY_VIDEO_MODE => {
+                let mode: VideoMode = read_property::<OniVideoMode>(data)?.into();
+                let supported = self.shared.sensor.video_modes.iter().any(|other| {
+                    other.pixel_format == mode.pixel_format
+                        && other.resolution_x == mode.resolution_x
+                        && other.resolution_y == mode.resolution_y
+                        && other.fps == mode.fps
+                });
+                if !supported {
+                    return Err(Status::BadParameter);
+                }
+                if state.started {
+                    return Err(Status::OutOfFlow);
+                }
+                state.video_mode = mode;
+                state.cropping = None;
+                Ok(())
+            },
+            ONI_STREAM_PROPERTY_MIRRORING => {
+                state.mirroring = read_property::<c_int>(data)? != 0;
+                Ok(())
+            },
+            property if state.settings.contains_key(&property) => {
+                let value = read_property::<c_int>(data)?;
+                state.settings.insert(property, value);
+                Ok(())
+            },
+            _ => Err(Status::NotSupported),
+        }
+    }
+
+    fn read_frame(&self) -> Result<FrameData, Status> {
+        let (speed, repeat) = (self.speed(), self.repeat());
+        let (index, request, mirroring, cropping, due) = {
+            let mut state = self.state();
+            if !state.started {
+                return Err(Status::OutOfFlow);
+            }
+            if self.at_end(&state) {
+                if !repeat {
+                    return Err(Status::TimeOut);
+                }
+                state.next_index = 0;
+                state.first_index = 0;
+                state.started_at = Instant::now();
+            }
+            let index = state.next_index;
+            state.next_index += 1;
+            let request = FrameRequest {
+                sensor_type: self.shared.sensor.sensor_type,
+                video_mode: state.video_mode,
+                horizontal_fov: self.shared.sensor.horizontal_fov,
+                vertical_fov: self.shared.sensor.vertical_fov,
+                index,
+                timestamp: self.timestamp(&state, index),
+            };
+            (index, request, state.mirroring, state.cropping, self.shared.sensor.due_time(&state, index, speed))
+        };
+
+        if speed > 0.0 {
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        if let Some(status) = self.shared.sensor.failures.get(&index) {
+            return Err(status.clone());
+        }
+
+        let mode = request.video_mode;
+        let mut data = self.shared.sensor.generator.generate(&request)?;
+        let (width, height) = (mode.resolution_x.max(0) as usize, mode.resolution_y.max(0) as usize);
+        let pixel_size = pixel_size(mode.pixel_format);
+        if mode.pixel_format != PixelFormat::JPEG {
+            if data.len() != width * height * pixel_size {
+                return Err(Status::Error(format!(
+                    "Mock frame generator returned {} bytes for a {}x{} {:?} frame",
+                    data.len(), width, height, mode.pixel_format,
+                )));
+            }
+            if mirroring {
+                mirror(&mut data, width, mode.pixel_format);
+            }
+            if let Some(cropping) = cropping {
+                data = crop(&data, width, pixel_size, cropping);
+            }
+        }
+
+        let (width, height) = match cropping {
+            Some(cropping) => (cropping.width, cropping.height),
+            None => (width as u16, height as u16),
+        };
+        Ok(FrameData {
+            sensor_type: request.sensor_type,
+            data,
+            width,
+            height,
+            timestamp: request.timestamp,
+            index,
+            video_mode: mode,
+            cropping,
+        })
+    }
+
+    fn ready_frame_timestamp(&self) -> Option<u64> {
+        let (speed, repeat) = (self.speed(), self.repeat());
+        let state = self.state();
+        if !state.started {
+            return None;
+        }
+        if self.at_end(&state) {
+            // Reading repeats from the first frame right away
+            return if repeat { Some(self.timestamp(&state, 0)) } else { None };
+        }
+        if speed > 0.0 && Instant::now() < self.shared.sensor.due_time(&state, state.next_index, speed) {
+            return None;
+        }
+        Some(self.timestamp(&state, state.next_index))
+    }
+
+    fn register_new_frame_callback(&self, callback: Box<dyn FnMut() + Send>) -> Result<usize, Status> {
+        let mut callbacks = self.shared.callbacks.lock().expect("Mock stream callbacks poisoned");
+        let id = callbacks.next_id;
+        callbacks.next_id += 1;
+        callbacks.callbacks.push((id, callback));
+        Ok(id)
+    }
+
+    fn unregister_new_frame_callback(&self, id: usize) {
+        let mut callbacks = self.shared.callbacks.lock().expect("Mock stream callbacks poisoned");
+        if callbacks.raising == Some(thread::current().id()) {
+            // Called from a callback, which the event thread removes once it
+            // returns
+            callbacks.unregistered.push(id);
+            return;
+        }
+        // Wait for the event thread to stop calling back
+        while callbacks.raising.is_some() {
+            callbacks = self.shared.callbacks_idle.wait(callbacks).expect("Mock stream callbacks poisoned");
+        }
+        callbacks.callbacks.retain(|&(other, _)| other != id);
+    }
+
+    fn seek(&self, frame_index: usize) -> Result<(), Status> {
+        if !self.playback {
+            return Err(Status::NotSupported);
+        }
+        if self.shared.sensor.frame_limit.is_some_and(|limit| frame_index >= limit) {
+            return Err(Status::BadParameter);
+        }
+        let timestamp = {
+            let state = self.state();
+            self.timestamp(&state, frame_index)
+        };
+        let streams: Vec<Arc<Shared>> = self.device.streams.lock().expect("Mock device streams poisoned")
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for stream in streams {
+            if Arc::ptr_eq(&stream, &self.shared) {
+                stream.move_to(frame_index);
+            } else {
+                let fps = stream.state().video_mode.fps;
+                stream.move_to(stream.sensor.index_at(timestamp, fps));
+            }
+        }
+        Ok(())
+    }
+
+    fn depth_to_world(&self, depth: (f32, f32, f32)) -> Result<(f32, f32, f32), Status> {
+        let [x, y, z] = self.intrinsics().unproject(depth.0, depth.1, depth.2);
+        Ok((x, y, z))
+    }
+
+    fn world_to_depth(&self, world: (f32, f32, f32)) -> Result<(f32, f32, f32), Status> {
+        Ok(self.intrinsics().project([world.0, world.1, world.2]))
+    }
+
+    // The sensors of a mock device are registered, so a depth pixel maps to
+    // the color pixel at the same relative position in the frame.
+    fn depth_to_color(&self, color: &dyn StreamBackend, depth: (i32, i32, OniDepthPixel)) -> Result<(i32, i32), Status> {
+        let mut color_mode = OniVideoMode { pixelFormat: 0, resolutionX: 0, resolutionY: 0, fps: 0 };
+        color.get_property(ONI_STREAM_PROPERTY_VIDEO_MODE, as_bytes_mut(&mut color_mode))?;
+        let mode = self.state().video_mode;
+        Ok((
+            depth.0 * color_mode.resolutionX / mode.resolution_x.max(1),
+            depth.1 * color_mode.resolutionY / mode.resolution_y.max(1),
+        ))
+    }
+}
+
+impl Drop for MockStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl fmt::Debug for MockStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockStream")
+            .field("sensor", &self.shared.sensor)
+            .field("playback", &self.playback)
+            .finish()
+    }
+}
+
+// Flips each row of a frame horizontally. Pixel pairs of 4:2:2 formats
+// keep their shared chroma and swap their luma.
+fn mirror(data: &mut [u8], width: usize, pixel_format: PixelFormat) {
+    let row_size = width * pixel_size(pixel_format);
+    if row_size == 0 {
+        return;
+    }
+    let (chunk, luma) = match pixel_format {
+        PixelFormat::YUV422 => (4, Some((1, 3))),
+        PixelFormat::YUYV => (4, Some((0, 2))),
+        format => (bytes_per_pixel(format), None),
+    };
+    for row in data.chunks_mut(row_size) {
+        let pixels = row.len() / chunk;
+        for i in 0..pixels / 2 {
+            for byte in 0..chunk {
+                row.swap(i * chunk + byte, (pixels - 1 - i) * chunk + byte);
+            }
+        }
+        if let Some((first, second)) = luma {
+            for pair in row.chunks_mut(4) {
+                pair.swap(first, second);
+            }
+        }
+    }
+}
+
+fn crop(data: &[u8], width: usize, pixel_size: usize, cropping: Cropping) -> Vec<u8> {
+    let row_size = width * pixel_size;
+    let start = usize::from(cropping.origin_x) * pixel_size;
+    let len = usize::from(cropping.width) * pixel_size;
+    data.chunks(row_size)
+        .skip(usize::from(cropping.origin_y))
+        .take(usize::from(cropping.height))
+        .flat_map(|row| row[start..start + len].iter().cloned())
+        .collect()
+}
@@ -0,0 +1,257 @@
+use types::Status;
+
+/// Compression applied to the frames of a stream in an ONI file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Codec {
+    /// Uncompressed pixel data (`"NONE"`)
+    Uncompressed,
+    /// Lossless 16-bit difference encoding used for depth (`"16zP"`)
+    Depth16Z,
+    /// `Depth16Z` over indices into an embedded value table (`"16zT"`)
+    Depth16ZEmbeddedTable,
+    /// Lossless 8-bit difference encoding used for images (`"Im8z"`)
+    Image8Z,
+    /// JPEG compressed images (`"JPEG"`)
+    Jpeg,
+    /// Any other codec, by its four character code.
+    Other([u8; 4]),
+}
+
+impl Codec {
+    pub(crate) fn from_fourcc(fourcc: u32) -> Self {
+        match &fourcc.to_le_bytes() {
+            b"NONE" => Codec::Uncompressed,
+            b"16zP" => Codec::Depth16Z,
+            b"16zT" => Codec::Depth16ZEmbeddedTable,
+            b"Im8z" => Codec::Image8Z,
+            b"JPEG" => Codec::Jpeg,
+            &other => Codec::Other(other),
+        }
+    }
+
+    pub(crate) fn fourcc(self) -> u32 {
+        let bytes = match self {
+            Codec::Uncompressed => *b"NONE",
+            Codec::Depth16Z => *b"16zP",
+            Codec::Depth16ZEmbeddedTable => *b"16zT",
+            Codec::Image8Z => *b"Im8z",
+            Codec::Jpeg => *b"JPEG",
+            Codec::Other(fourcc) => fourcc,
+        };
+        u32::from_le_bytes(bytes)
+    }
+
+    /// Decompresses one frame of data into raw pixel bytes. Returns
+    /// `Err(Status::NotSupported)` for codecs that can't be decoded in
+    /// pure Rust (JPEG, `Image8Z` and unknown codecs), whose frames can
+    /// still be read as-is with `oni::Reader::read_frame_data`.
+    pub fn decode(self, data: &[u8]) -> Result<Vec<u8>, Status> {
+        match self {
+            Codec::Uncompressed => Ok(data.to_vec()),
+            Codec::Depth16Z => decode_16z(data).map(|values| u16s_to_bytes(&values)),
+            Codec::Depth16ZEmbeddedTable => decode_16z_embedded_table(data).map(|values| u16s_to_bytes(&values)),
+            Codec::Image8Z | Codec::Jpeg | Codec::Other(_) => Err(Status::NotSupported),
+        }
+    }
+
+    /// Compresses raw pixel bytes into one frame of data. The depth codecs
+    /// expect 16-bit little-endian pixels, and can't store values of 32768
+    /// or more (`Depth16Z`) or more than 32768 distinct values
+    /// (`Depth16ZEmbeddedTable`). Returns `Err(Status::NotSupported)` for
+    /// the same codecs as `Codec::decode`.
+    pub fn encode(self, data: &[u8]) -> Result<Vec<u8>, Status> {
+        match self {
+            Codec::Uncompressed => Ok(data.to_vec()),
+            Codec::Depth16Z => encode_16z(&bytes_to_u16s(data)?),
+            Codec::Depth16ZEmbeddedTable => encode_16z_embedded_table(&bytes_to_u16s(data)?),
+            Codec::Image8Z | Codec::Jpeg | Codec::Other(_) => Err(Status::NotSupported),
+        }
+    }
+}
+
+fn bytes_to_u16s(data: &[u8]) -> Result<Vec<u16>, Status> {
+    if data.len() / 2 * 2 != data.len() {
+        return Err(Status::Error(String::from("16-bit depth data has an odd number of bytes")));
+    }
+    Ok(data.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect())
+}
+
+const MAX_16Z_VALUE: u16 = 0x7FFF;
+
+fn corrupt() -> Status {
+    Status::Error(String::from("Corrupt ONI file (bad compressed frame data)"))
+}
+
+fn u16s_to_bytes(values: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * 2);
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+// The 16z format starts with one full little-endian value. After that, each
+// value is stored as its difference from the previous one: differences
+// within -6..=6 are packed two to a byte as nibbles (biased by 6), runs of
+// zero-difference pairs (0x66) are collapsed into a 0xE1..=0xEF count byte,
+// and anything else is escaped by a 0xF nibble, followed by either one byte
+// of difference (biased by 192, high bit set) or the full big-endian value.
+// A trailing 0xD nibble pads out an odd number of values. Since the high bit
+// tells the two escapes apart, full values must be below 0x8000.
+fn decode_16z(data: &[u8]) -> Result<Vec<u16>, Status> {
+    if data.len() < 2 {
+        return Err(corrupt());
+    }
+    let mut last = u16::from_le_bytes([data[0], data[1]]);
+    let mut values = Vec::with_capacity(data.len() * 2);
+    values.push(last);
+
+    let mut i = 2;
+    while i < data.len() {
+        let byte = data[i];
+        i += 1;
+        if byte < 0xE0 {
+            last = last.wrapping_sub(u16::from(byte >> 4)).wrapping_add(6);
+            values.push(last);
+            match byte & 0x0F {
+                0x0F => {
+                    last = read_escaped(data, &mut i, last)?;
+                    values.push(last);
+                },
+                0x0D => {},
+                nibble => {
+                    last = last.wrapping_sub(u16::from(nibble)).wrapping_add(6);
+                    values.push(last);
+                },
+            }
+        } else if byte == 0xFF {
+            last = read_escaped(data, &mut i, last)?;
+            values.push(last);
+        } else {
+            let zero_pairs = (byte - 0xE0) as usize;
+            let len = values.len() + zero_pairs * 2;
+            values.resize(len, last);
+        }
+    }
+    Ok(values)
+}
+
+fn read_escaped(data: &[u8], i: &mut usize, last: u16) -> Result<u16, Status> {
+    let first = *data.get(*i).ok_or_else(corrupt)?;
+    if first & 0x80 != 0 {
+        *i += 1;
+        Ok(last.wrapping_sub(u16::from(first)).wrapping_add(192))
+    } else {
+        let second = *data.get(*i + 1).ok_or_else(corrupt)?;
+        *i += 2;
+        Ok(u16::from_be_bytes([first, second]))
+    }
+}
+
+// 16zT starts with a little-endian count of table entries and the table of
+// distinct values in ascending order, followed by 16z data whose values are
+// indices into the table.
+fn decode_16z_embedded_table(data: &[u8]) -> Result<Vec<u16>, Status> {
+    if data.len() < 2 {
+        return Err(corrupt());
+    }
+    let table_len = u16::from_le_bytes([data[0], data[1]]) as usize;
+    let table_end = 2 + table_len * 2;
+    if data.len() < table_end {
+        return Err(corrupt());
+    }
+    let table: Vec<u16> = data[2..table_end]
+        .chunks(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    decode_16z(&data[table_end..])?
+        .into_iter()
+        .map(|index| table.get(index as usize).cloned().ok_or_else(corrupt))
+        .collect()
+}
+
+fn encode_16z(values: &[u16]) -> Result<Vec<u8>, Status> {
+    if values.iter().any(|&value| value > MAX_16Z_VALUE) {
+        return Err(Status::Error(format!("16z can't encode values over {}", MAX_16Z_VALUE)));
+    }
+    let mut output = Vec::with_capacity(values.len());
+    let mut values = values.iter();
+    let mut last = match values.next() {
+        Some(&first) => first,
+        None => return Ok(output),
+    };
+    output.extend_from_slice(&last.to_le_bytes());
+
+    // A half-filled byte waiting for its low nibble
+    let mut pending: Option<u8> = None;
+    let mut zero_pairs = 0u8;
+
+    for &value in values {
+        let diff = i32::from(last) - i32::from(value);
+        if diff.abs() <= 6 {
+            let nibble = (diff + 6) as u8;
+            match pending.take() {
+                None => pending = Some(nibble << 4),
+                Some(high) => {
+                    let byte = high | nibble;
+                    if byte == 0x66 {
+                        zero_pairs += 1;
+                        if zero_pairs == 15 {
+                            output.push(0xEF);
+                            zero_pairs = 0;
+                        }
+                    } else {
+                        flush_zero_pairs(&mut output, &mut zero_pairs);
+                        output.push(byte);
+                    }
+                },
+            }
+        } else {
+            flush_zero_pairs(&mut output, &mut zero_pairs);
+            output.push(match pending.take() {
+                None => 0xFF,
+                Some(high) => high | 0x0F,
+            });
+            if diff.abs() <= 63 {
+                output.push((diff + 192) as u8);
+            } else {
+                output.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        last = value;
+    }
+
+    if let Some(high) = pending {
+        flush_zero_pairs(&mut output, &mut zero_pairs);
+        output.push(high | 0x0D);
+    }
+    flush_zero_pairs(&mut output, &mut zero_pairs);
+    Ok(output)
+}
+
+fn flush_zero_pairs(output: &mut Vec<u8>, zero_pairs: &mut u8) {
+    if *zero_pairs != 0 {
+        output.push(0xE0 + *zero_pairs);
+        *zero_pairs = 0;
+    }
+}
+
+fn encode_16z_embedded_table(values: &[u16]) -> Result<Vec<u8>, Status> {
+    let mut table: Vec<u16> = values.to_vec();
+    table.sort_unstable();
+    table.dedup();
+
+    let mut lookup = vec![0u16; usize::from(table.last().cloned().unwrap_or(0)) + 1];
+    for (index, &value) in table.iter().enumerate() {
+        lookup[usize::from(value)] = index as u16;
+    }
+    let indices: Vec<u16> = values.iter().map(|&value| lookup[usize::from(value)]).collect();
+
+    let mut output = Vec::with_capacity(2 + table.len() * 2 + values.len());
+    output.extend_from_slice(&(table.len() as u16).to_le_bytes());
+    for value in &table {
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+    output.extend(encode_16z(&indices)?);
+    Ok(output)
+}
@@ -0,0 +1,24 @@
+//! Reading and writing ONI recording files in pure Rust, without the
+//! OpenNI2 runtime.
+//!
+//! ONI files hold a header followed by a sequence of records: stream
+//! declarations, stream properties, frames, and per-stream seek tables.
+//! `Reader` parses all of these, and `Writer` produces them. Both handle
+//! frames stored uncompressed or with OpenNI's lossless 16-bit depth codecs.
+
+mod codec;
+mod reader;
+mod records;
+mod writer;
+
+pub use self::codec::Codec;
+pub use self::reader::{Reader, Frames};
+pub use self::writer::Writer;
+pub use self::records::{
+    FileHeader,
+    StreamInfo,
+    Property,
+    PropertyValue,
+    SeekEntry,
+    FrameEntry,
+};
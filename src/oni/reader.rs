@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::any::type_name;
+use std::path::Path;
+
+use frame::{OwnedFrame, pixels_from_bytes};
+use types::{Status, Pixel, pixels_per_frame};
+use oni::codec::Codec;
+use oni::records::*;
+
+/// Reads ONI recording files, such as those written by `Recorder`, without
+/// the OpenNI2 runtime.
+///
+/// Opening a file scans every record in it once, collecting the declared
+/// streams, their properties and seek tables, and the location of every
+/// frame. Frame data is only read and decompressed when asked for.
+///
+/// # Example
+/// ```no_run
+/// # use openni2::{oni, OniDepthPixel, SensorType};
+/// # fn main() -> Result<(), openni2::Status> {
+/// let mut reader = oni::Reader::open("recording.oni")?;
+/// let depth = reader.streams()
+///     .iter()
+///     .find(|stream| stream.sensor_type() == Some(SensorType::DEPTH))
+///     .map(|stream| stream.node_id)
+///     .expect("No depth stream in recording");
+/// for frame in reader.frames::<OniDepthPixel>(depth) {
+///     let frame = frame?;
+///     println!("{}: {}x{}", frame.timestamp, frame.width, frame.height);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Reader<R: Read + Seek> {
+    input: R,
+    header: FileHeader,
+    streams: Vec<StreamInfo>,
+    frames: Vec<FrameEntry>,
+    // Indices into `frames` of each stream's frames, in order
+    node_frames: HashMap<u32, Vec<usize>>,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens and scans the ONI file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Status> {
+        let file = File::open(path.as_ref()).map_err(|_| {
+            Status::Error(format!("Couldn't open ONI file {:?}", path.as_ref()))
+        })?;
+        Reader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Scans an ONI file from any seekable source.
+    pub fn new(mut input: R) -> Result<Self, Status> {
+        let mut header_bytes = [0u8; FILE_HEADER_SIZE];
+        input.read_exact(&mut header_bytes).map_err(io_error)?;
+        let header = FileHeader::parse(&header_bytes)?;
+
+        let mut reader = Reader {
+            input,
+            header,
+            streams: Vec::new(),
+            frames: Vec::new(),
+            node_frames: HashMap::new(),
+        };
+        reader.scan()?;
+        Ok(reader)
+    }
+
+    fn scan(&mut self) -> Result<(), Status> {
+        let mut position = FILE_HEADER_SIZE as u64;
+        while let Some(record) = RecordHeader::read(&mut self.input)? {
+            let mut fields = vec![0u8; record.fields_size as usize - RECORD_HEADER_SIZE];
+            self.input.read_exact(&mut fields).map_err(io_error)?;
+            let payload_position = position + u64::from(record.fields_size);
+
+            match record.record_type {
+                RECORD_END => break,
+                RECORD_NEW_DATA => {
+                    self.add_frame(&record, &fields, payload_position)?;
+                    self.input.seek(SeekFrom::Current(i64::from(record.payload_size))).map_err(io_error)?;
+                },
+                _ => {
+                    let mut payload = vec![0u8; record.payload_size as usize];
+                    self.input.read_exact(&mut payload).map_err(io_error)?;
+                    self.handle_record(&record, &fields, &payload)?;
+                },
+            }
+            position = payload_position + u64::from(record.payload_size);
+        }
+        Ok(())
+    }
+
+    fn handle_record(&mut self, record: &RecordHeader, fields: &[u8], payload: &[u8]) -> Result<(), Status> {
+        let mut fields = Fields::new(fields);
+        match record.record_type {
+            RECORD_NODE_ADDED => {
+                let stream = StreamInfo {
+                    node_id: record.node_id,
+                    name: fields.string()?,
+                    node_type: fields.u32()?,
+                    codec: Codec::from_fourcc(fields.u32()?),
+                    number_of_frames: fields.u32()?,
+                    min_timestamp: fields.u64()?,
+                    max_timestamp: fields.u64()?,
+                    seek_table_position: fields.u64()?,
+                    properties: Vec::new(),
+                    seek_table: Vec::new(),
+                };
+                self.streams.retain(|existing| existing.node_id != stream.node_id);
+                self.streams.push(stream);
+            },
+            RECORD_INT_PROPERTY |
+            RECORD_REAL_PROPERTY |
+            RECORD_STRING_PROPERTY |
+            RECORD_GENERAL_PROPERTY => {
+                let name = fields.string()?;
+                // Property values may be split between the record's fields
+                // and its payload, so read them as one run of bytes.
+                let mut bytes = fields.remaining().to_vec();
+                bytes.extend_from_slice(payload);
+                let mut value_fields = Fields::new(&bytes);
+                let value = match record.record_type {
+                    RECORD_INT_PROPERTY => PropertyValue::Int(value_fields.u64()?),
+                    RECORD_REAL_PROPERTY => PropertyValue::Real(value_fields.f64()?),
+                    RECORD_STRING_PROPERTY => PropertyValue::String(value_fields.string()?),
+                    _ => {
+                        let len = value_fields.u32()? as usize;
+                        PropertyValue::General(value_fields.take(len)?.to_vec())
+                    },
+                };
+                if let Some(stream) = self.stream_mut(record.node_id) {
+                    stream.set_property(Property { name, value });
+                }
+            },
+            RECORD_NODE_DATA_BEGIN => {
+                let number_of_frames = fields.u32()?;
+                let max_timestamp = fields.u64()?;
+                if let Some(stream) = self.stream_mut(record.node_id) {
+                    stream.number_of_frames = number_of_frames;
+                    stream.max_timestamp = max_timestamp;
+                }
+            },
+            RECORD_SEEK_TABLE => {
+                let mut bytes = fields.remaining().to_vec();
+                bytes.extend_from_slice(payload);
+                let mut entries = Fields::new(&bytes);
+                let mut seek_table = Vec::with_capacity(bytes.len() / SEEK_ENTRY_SIZE);
+                while entries.remaining().len() >= SEEK_ENTRY_SIZE {
+                    seek_table.push(SeekEntry {
+                        timestamp: entries.u64()?,
+                        configuration_id: entries.u32()?,
+                        position: entries.u64()?,
+                    });
+                }
+                if let Some(stream) = self.stream_mut(record.node_id) {
+                    // The first entry stands for frame 0, which doesn't exist
+                    if seek_table.len() == stream.number_of_frames as usize + 1 {
+                        seek_table.remove(0);
+                    }
+                    stream.seek_table = seek_table;
+                }
+            },
+            // Node removal and state changes don't affect what can be read.
+            RECORD_NODE_REMOVED | RECORD_NODE_STATE_READY => {},
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn add_frame(&mut self, record: &RecordHeader, fields: &[u8], payload_position: u64) -> Result<(), Status> {
+        let mut fields = Fields::new(fields);
+        let timestamp = fields.u64()?;
+        let frame_id = fields.u32()?;
+        let video_mode = self.stream(record.node_id).and_then(|stream| stream.video_mode());
+        let node_frames = self.node_frames.entry(record.node_id).or_default();
+        let number = node_frames.len();
+        node_frames.push(self.frames.len());
+        self.frames.push(FrameEntry {
+            node_id: record.node_id,
+            number,
+            frame_id,
+            timestamp,
+            video_mode,
+            data_position: payload_position,
+            data_size: record.payload_size,
+        });
+        Ok(())
+    }
+
+    fn stream_mut(&mut self, node_id: u32) -> Option<&mut StreamInfo> {
+        self.streams.iter_mut().find(|stream| stream.node_id == node_id)
+    }
+
+    /// The header of the file.
+    pub fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    /// Every stream declared in the file. Streams that produce frames have a
+    /// `StreamInfo::sensor_type`.
+    pub fn streams(&self) -> &[StreamInfo] {
+        &self.streams
+    }
+
+    /// Returns the stream with the given node id.
+    pub fn stream(&self, node_id: u32) -> Option<&StreamInfo> {
+        self.streams.iter().find(|stream| stream.node_id == node_id)
+    }
+
+    /// The entries of every frame in the file, in the order they were recorded.
+    pub fn frame_entries(&self) -> &[FrameEntry] {
+        &self.frames
+    }
+
+    /// The number of frames of a stream found in the file.
+    pub fn number_of_frames(&self, node_id: u32) -> usize {
+        self.node_frames.get(&node_id).map_or(0, Vec::len)
+    }
+
+    /// Returns the entry of the `number`th frame of a stream.
+    pub fn frame_entry(&self, node_id: u32, number: usize) -> Option<&FrameEntry> {
+        let index = *self.node_frames.get(&node_id)?.get(number)?;
+        self.frames.get(index)
+    }
+
+    /// Reads a frame's data exactly as stored in the file, without
+    /// decompressing it.
+    pub fn read_frame_data(&mut self, entry: &FrameEntry) -> Result<Vec<u8>, Status> {
+        let mut data = vec![0u8; entry.data_size as usize];
+        self.input.seek(SeekFrom::Start(entry.data_position)).map_err(io_error)?;
+        self.input.read_exact(&mut data).map_err(io_error)?;
+        Ok(data)
+    }
+
+    /// Reads and decompresses the `number`th frame of a stream.
+    ///
+    /// Returns `Err` if the frame doesn't exist, if its codec can't be
+    /// decoded (see `Codec::decode`), or if `P` doesn't support the stream's
+    /// pixel format.
+    pub fn read_frame<P: Pixel>(&mut self, node_id: u32, number: usize) -> Result<OwnedFrame<P>, Status> {
+        let entry = *self.frame_entry(node_id, number).ok_or(Status::BadParameter)?;
+        let codec = self.stream(node_id).map(|stream| stream.codec).ok_or(Status::BadParameter)?;
+        let video_mode = entry.video_mode.ok_or_else(|| {
+            Status::Error(format!("ONI stream {} has no recorded video mode", node_id))
+        })?;
+
+        if !P::supports_format(video_mode.pixel_format) {
+            return Err(Status::Error(format!("Pixel type parameter {} can't hold pixels of the recorded pixel format {:?}", type_name::<P>(), video_mode.pixel_format)));
+        }
+
+        let data = codec.decode(&self.read_frame_data(&entry)?)?;
+        let (width, height) = (video_mode.resolution_x as u16, video_mode.resolution_y as u16);
+        let pixels = pixels_from_bytes::<P>(&data)
+            .filter(|pixels| pixels.len() == pixels_per_frame(video_mode.pixel_format, width as usize, height as usize))
+            .ok_or_else(|| Status::Error(format!("Frame {} of ONI stream {} has {} bytes, which doesn't match its {}x{} video mode", number, node_id, data.len(), width, height)))?;
+
+        Ok(OwnedFrame {
+            pixels,
+            width,
+            height,
+            timestamp: entry.timestamp,
+            index: entry.frame_id as usize,
+            video_mode,
+            cropping: None,
+        })
+    }
+
+    /// Iterates over the frames of a stream, in recorded order.
+    pub fn frames<P: Pixel>(&mut self, node_id: u32) -> Frames<'_, R, P> {
+        Frames {
+            reader: self,
+            node_id,
+            number: 0,
+            _pixel_type: PhantomData,
+        }
+    }
+}
+
+/// Iterator over the frames of one stream of an ONI file. See `Reader::frames`.
+#[derive(Debug)]
+pub struct Frames<'a, R: 'a + Read + Seek, P: Pixel> {
+    reader: &'a mut Reader<R>,
+    node_id: u32,
+    number: usize,
+    _pixel_type: PhantomData<P>,
+}
+
+impl<'a, R: Read + Seek, P: Pixel> Iterator for Frames<'a, R, P> {
+    type Item = Result<OwnedFrame<P>, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.number >= self.reader.number_of_frames(self.node_id) {
+            return None;
+        }
+        let frame = self.reader.read_frame(self.node_id, self.number);
+        self.number += 1;
+        Some(frame)
+    }
+}
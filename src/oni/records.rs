@@ -0,0 +1,380 @@
+use std::io::{Read, Write};
+use types::{Status, SensorType, PixelFormat, VideoMode};
+use oni::codec::Codec;
+
+// Layout of ONI files, as written by OpenNI2's `oniCreateRecorder`. Every
+// integer is little-endian, and every struct is packed.
+
+pub const FILE_MAGIC: [u8; 4] = *b"NI10";
+pub const FILE_HEADER_SIZE: usize = 24;
+
+pub const RECORD_MAGIC: u32 = 0x5265_634E;
+pub const RECORD_HEADER_SIZE: usize = 28;
+
+pub const RECORD_INT_PROPERTY: u32 = 0x03;
+pub const RECORD_REAL_PROPERTY: u32 = 0x04;
+pub const RECORD_STRING_PROPERTY: u32 = 0x05;
+pub const RECORD_GENERAL_PROPERTY: u32 = 0x06;
+pub const RECORD_NODE_REMOVED: u32 = 0x07;
+pub const RECORD_NODE_DATA_BEGIN: u32 = 0x08;
+pub const RECORD_NODE_STATE_READY: u32 = 0x09;
+pub const RECORD_NEW_DATA: u32 = 0x0A;
+pub const RECORD_END: u32 = 0x0B;
+pub const RECORD_NODE_ADDED: u32 = 0x0D;
+pub const RECORD_SEEK_TABLE: u32 = 0x0E;
+
+pub const NODE_TYPE_DEPTH: u32 = 2;
+pub const NODE_TYPE_IMAGE: u32 = 3;
+pub const NODE_TYPE_IR: u32 = 5;
+
+pub const PROPERTY_MAP_OUTPUT_MODE: &str = "xnMapOutputMode";
+pub const PROPERTY_ONI_PIXEL_FORMAT: &str = "oniPixelFormat";
+pub const PROPERTY_XN_PIXEL_FORMAT: &str = "xnPixelFormat";
+pub const PROPERTY_FIELD_OF_VIEW: &str = "xnFOV";
+pub const PROPERTY_MAX_DEPTH: &str = "xnDeviceMaxDepth";
+
+/// The header at the start of every ONI file.
+#[derive(Debug, Copy, Clone)]
+pub struct FileHeader {
+    /// Version of the recorder that wrote the file, as
+    /// `(major, minor, maintenance, build)`.
+    pub version: (u8, u8, u16, u32),
+    /// Timestamp of the last frame in the file, across all streams.
+    pub max_timestamp: u64,
+    /// Highest node id used by any stream in the file.
+    pub max_node_id: u32,
+}
+
+impl FileHeader {
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self, Status> {
+        if bytes.len() < FILE_HEADER_SIZE || bytes[0..4] != FILE_MAGIC {
+            return Err(Status::Error(String::from("Not an ONI file (bad magic number)")));
+        }
+        let mut fields = Fields::new(&bytes[4..]);
+        Ok(FileHeader {
+            version: (fields.u8()?, fields.u8()?, fields.u16()?, fields.u32()?),
+            max_timestamp: fields.u64()?,
+            max_node_id: fields.u32()?,
+        })
+    }
+
+    pub(crate) fn write<W: Write>(&self, output: &mut W) -> Result<(), Status> {
+        let mut bytes = Vec::with_capacity(FILE_HEADER_SIZE);
+        bytes.extend_from_slice(&FILE_MAGIC);
+        bytes.push(self.version.0);
+        bytes.push(self.version.1);
+        bytes.extend_from_slice(&self.version.2.to_le_bytes());
+        bytes.extend_from_slice(&self.version.3.to_le_bytes());
+        bytes.extend_from_slice(&self.max_timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.max_node_id.to_le_bytes());
+        output.write_all(&bytes).map_err(io_error)
+    }
+}
+
+/// The fixed-size header that precedes every record in an ONI file.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct RecordHeader {
+    pub record_type: u32,
+    pub node_id: u32,
+    /// Size of the header plus the record's fields.
+    pub fields_size: u32,
+    pub payload_size: u32,
+}
+
+impl RecordHeader {
+    /// Reads a record header, or returns `None` at a clean end of file.
+    pub fn read<R: Read>(input: &mut R) -> Result<Option<Self>, Status> {
+        let mut bytes = [0u8; RECORD_HEADER_SIZE];
+        let mut filled = 0;
+        while filled < bytes.len() {
+            match input.read(&mut bytes[filled..]).map_err(io_error)? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(Status::Error(String::from("ONI file ends in the middle of a record"))),
+                n => filled += n,
+            }
+        }
+        let mut fields = Fields::new(&bytes);
+        if fields.u32()? != RECORD_MAGIC {
+            return Err(Status::Error(String::from("Corrupt ONI file (bad record magic number)")));
+        }
+        let header = RecordHeader {
+            record_type: fields.u32()?,
+            node_id: fields.u32()?,
+            fields_size: fields.u32()?,
+            payload_size: fields.u32()?,
+        };
+        // The last field points at a record to replay when seeking backwards,
+        // which is only needed by players that rewind property changes.
+        fields.u64()?;
+        if (header.fields_size as usize) < RECORD_HEADER_SIZE {
+            return Err(Status::Error(format!("Corrupt ONI file (record fields size {})", header.fields_size)));
+        }
+        Ok(Some(header))
+    }
+
+    /// Writes a record header. `undo_record_position` is always 0, since
+    /// records are never rewound.
+    pub fn write<W: Write>(&self, output: &mut W) -> Result<(), Status> {
+        let mut bytes = Vec::with_capacity(RECORD_HEADER_SIZE);
+        bytes.extend_from_slice(&RECORD_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&self.record_type.to_le_bytes());
+        bytes.extend_from_slice(&self.node_id.to_le_bytes());
+        bytes.extend_from_slice(&self.fields_size.to_le_bytes());
+        bytes.extend_from_slice(&self.payload_size.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        output.write_all(&bytes).map_err(io_error)
+    }
+}
+
+/// The value of a property record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Int(u64),
+    Real(f64),
+    String(String),
+    /// Any other property, stored as the raw bytes of a C struct.
+    General(Vec<u8>),
+}
+
+/// A named stream property saved in an ONI file, such as `"xnMapOutputMode"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+/// A stream declared in an ONI file, along with the latest value of every
+/// property recorded for it.
+#[derive(Debug, Clone)]
+pub struct StreamInfo {
+    /// Identifies the stream's records within the file.
+    pub node_id: u32,
+    /// The name the recorder gave the stream, e.g. `"Depth"`.
+    pub name: String,
+    /// The recorder's node type. See `StreamInfo::sensor_type`.
+    pub node_type: u32,
+    /// How the stream's frames are compressed.
+    pub codec: Codec,
+    /// Number of frames in the stream, as declared by the recorder.
+    pub number_of_frames: u32,
+    pub min_timestamp: u64,
+    pub max_timestamp: u64,
+    /// File offset of the stream's seek table, or 0 if it has none.
+    pub seek_table_position: u64,
+    pub properties: Vec<Property>,
+    /// Entries of the stream's seek table, if the file had one, starting
+    /// with the stream's first frame.
+    pub seek_table: Vec<SeekEntry>,
+}
+
+impl StreamInfo {
+    /// The sensor that the stream was recorded from, or `None` for nodes
+    /// like the device node that don't produce frames.
+    pub fn sensor_type(&self) -> Option<SensorType> {
+        match self.node_type {
+            NODE_TYPE_DEPTH => Some(SensorType::DEPTH),
+            NODE_TYPE_IMAGE => Some(SensorType::COLOR),
+            NODE_TYPE_IR => Some(SensorType::IR),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a property, if it was recorded.
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.iter().find(|property| property.name == name).map(|property| &property.value)
+    }
+
+    pub(crate) fn set_property(&mut self, property: Property) {
+        match self.properties.iter_mut().find(|existing| existing.name == property.name) {
+            Some(existing) => existing.value = property.value,
+            None => self.properties.push(property),
+        }
+    }
+
+    /// The `VideoMode` of the stream, built from its recorded output mode and
+    /// pixel format properties.
+    pub fn video_mode(&self) -> Option<VideoMode> {
+        let mut output_mode = match self.property(PROPERTY_MAP_OUTPUT_MODE) {
+            Some(PropertyValue::General(bytes)) => Fields::new(bytes),
+            _ => return None,
+        };
+        let resolution_x = output_mode.u32().ok()? as i32;
+        let resolution_y = output_mode.u32().ok()? as i32;
+        let fps = output_mode.u32().ok()? as i32;
+        Some(VideoMode {
+            pixel_format: self.pixel_format()?,
+            resolution_x,
+            resolution_y,
+            fps,
+        })
+    }
+
+    fn pixel_format(&self) -> Option<PixelFormat> {
+        if let Some(&PropertyValue::Int(format)) = self.property(PROPERTY_ONI_PIXEL_FORMAT) {
+            return pixel_format_from_int(format as i32);
+        }
+        match (self.node_type, self.property(PROPERTY_XN_PIXEL_FORMAT)) {
+            (NODE_TYPE_DEPTH, _) => Some(PixelFormat::DEPTH_1_MM),
+            (_, Some(&PropertyValue::Int(1))) => Some(PixelFormat::RGB888),
+            (_, Some(&PropertyValue::Int(2))) => Some(PixelFormat::YUV422),
+            (_, Some(&PropertyValue::Int(3))) => Some(PixelFormat::GRAY8),
+            (_, Some(&PropertyValue::Int(4))) => Some(PixelFormat::GRAY16),
+            (_, Some(&PropertyValue::Int(5))) => Some(PixelFormat::JPEG),
+            (NODE_TYPE_IR, None) => Some(PixelFormat::GRAY16),
+            (NODE_TYPE_IMAGE, None) => Some(PixelFormat::RGB888),
+            _ => None,
+        }
+    }
+
+    /// Horizontal and vertical field of view, in radians.
+    pub fn field_of_view(&self) -> Option<(f32, f32)> {
+        match self.property(PROPERTY_FIELD_OF_VIEW) {
+            Some(PropertyValue::General(bytes)) => {
+                let mut fov = Fields::new(bytes);
+                Some((fov.f64().ok()? as f32, fov.f64().ok()? as f32))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// One entry of a stream's seek table, which maps a frame to the file
+/// offset of its record.
+#[derive(Debug, Copy, Clone)]
+pub struct SeekEntry {
+    pub timestamp: u64,
+    pub configuration_id: u32,
+    pub position: u64,
+}
+
+pub const SEEK_ENTRY_SIZE: usize = 20;
+
+/// The location and metadata of a single frame record in an ONI file.
+#[derive(Debug, Copy, Clone)]
+pub struct FrameEntry {
+    /// Node id of the stream the frame belongs to.
+    pub node_id: u32,
+    /// Index of the frame within its stream, starting at 0.
+    pub number: usize,
+    /// The frame's number in the file. Recorders number each stream's frames
+    /// from 1.
+    pub frame_id: u32,
+    pub timestamp: u64,
+    /// The stream's video mode when the frame was recorded.
+    pub video_mode: Option<VideoMode>,
+    pub(crate) data_position: u64,
+    pub(crate) data_size: u32,
+}
+
+pub(crate) fn pixel_format_from_int(value: i32) -> Option<PixelFormat> {
+    use openni2_sys::*;
+    match value {
+        ONI_PIXEL_FORMAT_DEPTH_1_MM => Some(PixelFormat::DEPTH_1_MM),
+        ONI_PIXEL_FORMAT_DEPTH_100_UM => Some(PixelFormat::DEPTH_100_UM),
+        ONI_PIXEL_FORMAT_SHIFT_9_2 => Some(PixelFormat::SHIFT_9_2),
+        ONI_PIXEL_FORMAT_SHIFT_9_3 => Some(PixelFormat::SHIFT_9_3),
+        ONI_PIXEL_FORMAT_RGB888 => Some(PixelFormat::RGB888),
+        ONI_PIXEL_FORMAT_YUV422 => Some(PixelFormat::YUV422),
+        ONI_PIXEL_FORMAT_GRAY8 => Some(PixelFormat::GRAY8),
+        ONI_PIXEL_FORMAT_GRAY16 => Some(PixelFormat::GRAY16),
+        ONI_PIXEL_FORMAT_JPEG => Some(PixelFormat::JPEG),
+        ONI_PIXEL_FORMAT_YUYV => Some(PixelFormat::YUYV),
+        _ => None,
+    }
+}
+
+pub(crate) fn io_error(error: ::std::io::Error) -> Status {
+    Status::Error(format!("ONI file I/O error: {}", error))
+}
+
+/// Little-endian cursor over the fields of a record.
+pub(crate) struct Fields<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Fields { bytes }
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], Status> {
+        if len > self.bytes.len() {
+            return Err(Status::Error(String::from("Corrupt ONI file (record is truncated)")));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Status> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Status> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Status> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Status> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, Status> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    /// Strings are stored as a `u32` length (counting the trailing NUL),
+    /// followed by the NUL-terminated characters.
+    pub fn string(&mut self) -> Result<String, Status> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+/// Little-endian builder for the fields of a record, the inverse of `Fields`.
+#[derive(Default)]
+pub(crate) struct FieldsWriter {
+    pub bytes: Vec<u8>,
+}
+
+impl FieldsWriter {
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn f64(&mut self, value: f64) -> &mut Self {
+        self.u64(value.to_bits())
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.u32(value.len() as u32 + 1);
+        self.bytes.extend_from_slice(value.as_bytes());
+        self.bytes.push(0);
+        self
+    }
+
+    pub fn raw(&mut self, value: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(value);
+        self
+    }
+}
@@ -0,0 +1,361 @@
+use std::any::type_name;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use frame::OwnedFrame;
+use mock::{DEPTH_FOV, COLOR_FOV};
+use types::{Status, SensorType, PixelFormat, VideoMode, Pixel, bytes_per_pixel, pixels_per_frame};
+use oni::codec::Codec;
+use oni::records::*;
+
+/// Writes ONI recording files from Rust-side frames, such as processed or
+/// synthetic depth, so that they can be played back with `Device::open_uri`
+/// or read with `oni::Reader`.
+///
+/// Declare each stream with `Writer::add_stream`, write its frames in order,
+/// then call `Writer::finish` to write the seek tables and fill in the frame
+/// counts. A file that isn't finished can't be played back.
+///
+/// Like OpenNI2's recorder, the writer numbers each stream's frames from 1 in
+/// the order they are written, and starts each seek table with an entry for
+/// the nonexistent frame 0, which players expect.
+///
+/// # Example
+/// ```no_run
+/// # use openni2::{oni, OwnedFrame, OniDepthPixel, PixelFormat, SensorType, VideoMode};
+/// # fn main() -> Result<(), openni2::Status> {
+/// let video_mode = VideoMode {
+///     pixel_format: PixelFormat::DEPTH_1_MM,
+///     resolution_x: 320,
+///     resolution_y: 240,
+///     fps: 30,
+/// };
+/// let mut writer = oni::Writer::create("synthetic.oni")?;
+/// let depth = writer.add_stream(SensorType::DEPTH, video_mode, oni::Codec::Depth16ZEmbeddedTable)?;
+/// for i in 0..30 {
+///     let frame: OwnedFrame<OniDepthPixel> = OwnedFrame {
+///         pixels: vec![1000 + i * 10; 320 * 240],
+///         width: 320,
+///         height: 240,
+///         timestamp: i as u64 * 33_333,
+///         index: i as usize,
+///         video_mode,
+///         cropping: None,
+///     };
+///     writer.write_frame(depth, &frame)?;
+/// }
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Writer<W: Write + Seek> {
+    output: W,
+    position: u64,
+    streams: Vec<WriterStream>,
+    max_timestamp: u64,
+}
+
+#[derive(Debug)]
+struct WriterStream {
+    node_id: u32,
+    name: String,
+    node_type: u32,
+    codec: Codec,
+    video_mode: VideoMode,
+    node_added_position: u64,
+    data_begin_position: u64,
+    seek_table: Vec<SeekEntry>,
+}
+
+impl Writer<BufWriter<File>> {
+    /// Creates a new ONI file at `path`. Like `Recorder::create`, parent
+    /// directories are created if they don't already exist.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Status> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|_| {
+                Status::Error(format!("Couldn't create parent directory {:?}", parent))
+            })?;
+        }
+        let file = File::create(path).map_err(|_| {
+            Status::Error(format!("Couldn't create ONI file {:?}", path))
+        })?;
+        Writer::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Starts writing an ONI file to any seekable destination.
+    pub fn new(mut output: W) -> Result<Self, Status> {
+        Self::file_header(0, 0).write(&mut output)?;
+        Ok(Writer {
+            output,
+            position: FILE_HEADER_SIZE as u64,
+            streams: Vec::new(),
+            max_timestamp: 0,
+        })
+    }
+
+    fn file_header(max_timestamp: u64, max_node_id: u32) -> FileHeader {
+        FileHeader {
+            version: (1, 0, 1, 0),
+            max_timestamp,
+            max_node_id,
+        }
+    }
+
+    /// Declares a new stream of frames in the file, and returns the node id
+    /// to pass to `Writer::write_frame`. The stream's field of view is
+    /// recorded as a PS1080's, unless changed with
+    /// `Writer::set_field_of_view`.
+    ///
+    /// Only `Codec::Uncompressed` can be used for every pixel format; the
+    /// `Depth16Z` codecs require 16-bit pixels, and other codecs can't be
+    /// written.
+    pub fn add_stream(&mut self, sensor_type: SensorType, video_mode: VideoMode, codec: Codec) -> Result<u32, Status> {
+        let encodable = match codec {
+            Codec::Uncompressed => true,
+            Codec::Depth16Z | Codec::Depth16ZEmbeddedTable => bytes_per_pixel(video_mode.pixel_format) == 2,
+            _ => false,
+        };
+        if !encodable {
+            return Err(Status::NotSupported);
+        }
+
+        let (name, node_type) = match sensor_type {
+            SensorType::DEPTH => ("Depth", NODE_TYPE_DEPTH),
+            SensorType::COLOR => ("Image", NODE_TYPE_IMAGE),
+            SensorType::IR => ("IR", NODE_TYPE_IR),
+        };
+        let node_id = self.streams.len() as u32 + 1;
+        let mut stream = WriterStream {
+            node_id,
+            name: format!("{}{}", name, node_id),
+            node_type,
+            codec,
+            video_mode,
+            node_added_position: self.position,
+            data_begin_position: 0,
+            seek_table: Vec::new(),
+        };
+
+        let node_added = Self::node_added_fields(&stream, 0, 0, 0, 0);
+        self.write_record(RECORD_NODE_ADDED, node_id, &node_added, &[])?;
+
+        let mut output_mode = FieldsWriter::default();
+        output_mode
+            .u32(video_mode.resolution_x as u32)
+            .u32(video_mode.resolution_y as u32)
+            .u32(video_mode.fps as u32);
+        self.write_general_property(node_id, PROPERTY_MAP_OUTPUT_MODE, &output_mode.bytes)?;
+        self.write_int_property(node_id, PROPERTY_ONI_PIXEL_FORMAT, video_mode.pixel_format as u64)?;
+        if let Some(format) = xn_pixel_format(video_mode.pixel_format) {
+            self.write_int_property(node_id, PROPERTY_XN_PIXEL_FORMAT, format)?;
+        }
+        let (horizontal_fov, vertical_fov) = match sensor_type {
+            SensorType::COLOR => COLOR_FOV,
+            SensorType::DEPTH | SensorType::IR => DEPTH_FOV,
+        };
+        self.set_field_of_view(node_id, horizontal_fov, vertical_fov)?;
+
+        stream.data_begin_position = self.position;
+        let data_begin = Self::data_begin_fields(0, 0);
+        self.write_record(RECORD_NODE_DATA_BEGIN, node_id, &data_begin, &[])?;
+
+        self.streams.push(stream);
+        Ok(node_id)
+    }
+
+    /// Records the horizontal and vertical field of view of a stream, in
+    /// radians. Players report these through `Stream::get_horizontal_fov`
+    /// and `Stream::get_vertical_fov`.
+    pub fn set_field_of_view(&mut self, node_id: u32, horizontal: f32, vertical: f32) -> Result<(), Status> {
+        let mut fov = FieldsWriter::default();
+        fov.f64(f64::from(horizontal)).f64(f64::from(vertical));
+        self.write_general_property(node_id, PROPERTY_FIELD_OF_VIEW, &fov.bytes)
+    }
+
+    /// Records the maximum depth value of a depth stream. Players report this
+    /// through `Stream::get_max_value`.
+    pub fn set_max_depth(&mut self, node_id: u32, max_depth: u64) -> Result<(), Status> {
+        self.write_int_property(node_id, PROPERTY_MAX_DEPTH, max_depth)
+    }
+
+    /// Records an arbitrary property of a stream.
+    pub fn set_property(&mut self, node_id: u32, property: &Property) -> Result<(), Status> {
+        match property.value {
+            PropertyValue::Int(value) => self.write_int_property(node_id, &property.name, value),
+            PropertyValue::Real(value) => {
+                let mut fields = FieldsWriter::default();
+                fields.string(&property.name).f64(value);
+                self.write_record(RECORD_REAL_PROPERTY, node_id, &fields.bytes, &[])
+            },
+            PropertyValue::String(ref value) => {
+                let mut fields = FieldsWriter::default();
+                fields.string(&property.name).string(value);
+                self.write_record(RECORD_STRING_PROPERTY, node_id, &fields.bytes, &[])
+            },
+            PropertyValue::General(ref value) => self.write_general_property(node_id, &property.name, value),
+        }
+    }
+
+    /// Writes a frame to a stream. The frame's dimensions must match the
+    /// video mode the stream was declared with, and `P` must support its
+    /// pixel format. The frame is numbered by its position in the stream, so
+    /// its `index` isn't recorded.
+    pub fn write_frame<P: Pixel>(&mut self, node_id: u32, frame: &OwnedFrame<P>) -> Result<(), Status> {
+        let video_mode = self.stream(node_id)?.video_mode;
+        if !P::supports_format(video_mode.pixel_format) {
+            return Err(Status::Error(format!("Pixel type parameter {} can't hold pixels of the stream's pixel format {:?}", type_name::<P>(), video_mode.pixel_format)));
+        }
+        if frame.width as i32 != video_mode.resolution_x ||
+            frame.height as i32 != video_mode.resolution_y ||
+            frame.pixels.len() != pixels_per_frame(video_mode.pixel_format, frame.width as usize, frame.height as usize) {
+            return Err(Status::Error(format!("{}x{} frame doesn't match the stream's {}x{} video mode", frame.width, frame.height, video_mode.resolution_x, video_mode.resolution_y)));
+        }
+        self.write_frame_data(node_id, frame.timestamp, frame.data())
+    }
+
+    /// Writes a frame to a stream from raw, uncompressed pixel bytes.
+    pub fn write_frame_data(&mut self, node_id: u32, timestamp: u64, data: &[u8]) -> Result<(), Status> {
+        let (codec, video_mode, frame_id) = {
+            let stream = self.stream(node_id)?;
+            (stream.codec, stream.video_mode, stream.seek_table.len() as u32 + 1)
+        };
+        let pixels = pixels_per_frame(video_mode.pixel_format, video_mode.resolution_x as usize, video_mode.resolution_y as usize);
+        let expected_size = pixels * bytes_per_pixel(video_mode.pixel_format);
+        if data.len() != expected_size {
+            return Err(Status::Error(format!("{} bytes of frame data don't match the stream's video mode ({} bytes)", data.len(), expected_size)));
+        }
+
+        let compressed = codec.encode(data)?;
+        let record_position = self.position;
+        let mut fields = FieldsWriter::default();
+        fields.u64(timestamp).u32(frame_id);
+        self.write_record(RECORD_NEW_DATA, node_id, &fields.bytes, &compressed)?;
+
+        self.max_timestamp = self.max_timestamp.max(timestamp);
+        self.stream_mut(node_id)?.seek_table.push(SeekEntry {
+            timestamp,
+            configuration_id: 0,
+            position: record_position,
+        });
+        Ok(())
+    }
+
+    /// Writes each stream's seek table and the end of the file, then goes
+    /// back to fill in frame counts and timestamps that weren't known while
+    /// frames were being written. Returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Status> {
+        let mut seek_table_positions = Vec::with_capacity(self.streams.len());
+        for i in 0..self.streams.len() {
+            let mut payload = FieldsWriter::default();
+            // The entry for frame 0
+            payload.u64(0).u32(0).u64(0);
+            for entry in &self.streams[i].seek_table {
+                payload.u64(entry.timestamp).u32(entry.configuration_id).u64(entry.position);
+            }
+            seek_table_positions.push(self.position);
+            let node_id = self.streams[i].node_id;
+            self.write_record(RECORD_SEEK_TABLE, node_id, &[], &payload.bytes)?;
+        }
+        self.write_record(RECORD_END, 0, &[], &[])?;
+
+        for (stream, &seek_table_position) in self.streams.iter().zip(&seek_table_positions) {
+            let number_of_frames = stream.seek_table.len() as u32;
+            let min_timestamp = stream.seek_table.iter().map(|entry| entry.timestamp).min().unwrap_or(0);
+            let max_timestamp = stream.seek_table.iter().map(|entry| entry.timestamp).max().unwrap_or(0);
+
+            let node_added = Self::node_added_fields(stream, number_of_frames, min_timestamp, max_timestamp, seek_table_position);
+            rewrite_record(&mut self.output, stream.node_added_position, RECORD_NODE_ADDED, stream.node_id, &node_added)?;
+
+            let data_begin = Self::data_begin_fields(number_of_frames, max_timestamp);
+            rewrite_record(&mut self.output, stream.data_begin_position, RECORD_NODE_DATA_BEGIN, stream.node_id, &data_begin)?;
+        }
+
+        self.output.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        Self::file_header(self.max_timestamp, self.streams.len() as u32).write(&mut self.output)?;
+        self.output.flush().map_err(io_error)?;
+        Ok(self.output)
+    }
+
+    fn node_added_fields(stream: &WriterStream, number_of_frames: u32, min_timestamp: u64, max_timestamp: u64, seek_table_position: u64) -> Vec<u8> {
+        let mut fields = FieldsWriter::default();
+        fields
+            .string(&stream.name)
+            .u32(stream.node_type)
+            .u32(stream.codec.fourcc())
+            .u32(number_of_frames)
+            .u64(min_timestamp)
+            .u64(max_timestamp)
+            .u64(seek_table_position);
+        fields.bytes
+    }
+
+    fn data_begin_fields(number_of_frames: u32, max_timestamp: u64) -> Vec<u8> {
+        let mut fields = FieldsWriter::default();
+        fields.u32(number_of_frames).u64(max_timestamp);
+        fields.bytes
+    }
+
+    fn write_int_property(&mut self, node_id: u32, name: &str, value: u64) -> Result<(), Status> {
+        let mut fields = FieldsWriter::default();
+        fields.string(name).u64(value);
+        self.write_record(RECORD_INT_PROPERTY, node_id, &fields.bytes, &[])
+    }
+
+    fn write_general_property(&mut self, node_id: u32, name: &str, value: &[u8]) -> Result<(), Status> {
+        let mut fields = FieldsWriter::default();
+        fields.string(name).u32(value.len() as u32).raw(value);
+        self.write_record(RECORD_GENERAL_PROPERTY, node_id, &fields.bytes, &[])
+    }
+
+    fn write_record(&mut self, record_type: u32, node_id: u32, fields: &[u8], payload: &[u8]) -> Result<(), Status> {
+        let header = RecordHeader {
+            record_type,
+            node_id,
+            fields_size: (RECORD_HEADER_SIZE + fields.len()) as u32,
+            payload_size: payload.len() as u32,
+        };
+        header.write(&mut self.output)?;
+        self.output.write_all(fields).map_err(io_error)?;
+        self.output.write_all(payload).map_err(io_error)?;
+        self.position += (RECORD_HEADER_SIZE + fields.len() + payload.len()) as u64;
+        Ok(())
+    }
+
+    fn stream(&self, node_id: u32) -> Result<&WriterStream, Status> {
+        self.streams.iter().find(|stream| stream.node_id == node_id).ok_or(Status::BadParameter)
+    }
+
+    fn stream_mut(&mut self, node_id: u32) -> Result<&mut WriterStream, Status> {
+        self.streams.iter_mut().find(|stream| stream.node_id == node_id).ok_or(Status::BadParameter)
+    }
+}
+
+// Overwrites a payload-less record in place. The new fields must be the same
+// size as the ones originally written.
+fn rewrite_record<W: Write + Seek>(output: &mut W, position: u64, record_type: u32, node_id: u32, fields: &[u8]) -> Result<(), Status> {
+    output.seek(SeekFrom::Start(position)).map_err(io_error)?;
+    RecordHeader {
+        record_type,
+        node_id,
+        fields_size: (RECORD_HEADER_SIZE + fields.len()) as u32,
+        payload_size: 0,
+    }.write(output)?;
+    output.write_all(fields).map_err(io_error)
+}
+
+// OpenNI 1.x pixel format codes, still read by some players.
+fn xn_pixel_format(format: PixelFormat) -> Option<u64> {
+    match format {
+        PixelFormat::RGB888 => Some(1),
+        PixelFormat::YUV422 => Some(2),
+        PixelFormat::GRAY8 => Some(3),
+        PixelFormat::GRAY16 => Some(4),
+        PixelFormat::JPEG => Some(5),
+        _ => None,
+    }
+}
@@ -0,0 +1,112 @@
+use std::mem;
+use std::os::raw::c_int;
+
+use openni2_sys::*;
+use device::{Device, PropertyData};
+use frame::Frame;
+use stream::Stream;
+use types::{Status, Pixel};
+
+// Argument to `ONI_DEVICE_COMMAND_SEEK`, which openni2-sys doesn't declare.
+// The padding that C leaves before `stream` is spelled out, so that a
+// backend can be handed the argument as bytes.
+#[allow(non_snake_case)]
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct OniSeek {
+    frameIndex: c_int,
+    _padding: [u8; SEEK_PADDING],
+    stream: OniStreamHandle,
+}
+
+const SEEK_PADDING: usize = mem::align_of::<OniStreamHandle>().saturating_sub(mem::size_of::<c_int>());
+
+unsafe impl PropertyData for OniSeek {}
+
+/// Seeks and steps through the streams of a recording. Obtained with
+/// `Device::playback` from a `Device` opened from a file, or from a
+/// `mock::MockDevice` with playback turned on, such as an image sequence.
+///
+/// By default a recording plays back in real time, like a live device. In
+/// manual mode (see `PlaybackControl::set_manual_stepping`) a stream only
+/// advances by one frame each time a frame is read from it.
+#[derive(Debug)]
+pub struct PlaybackControl<'device> {
+    device: &'device Device,
+}
+
+impl<'device> PlaybackControl<'device> {
+    /// The playback speed that turns on manual stepping.
+    pub const MANUAL_SPEED: f32 = -1.0;
+
+    pub(crate) fn new(device: &'device Device) -> Result<Self, Status> {
+        if device.is_command_supported(ONI_DEVICE_COMMAND_SEEK) {
+            Ok(PlaybackControl { device })
+        } else {
+            Err(Status::NotSupported)
+        }
+    }
+
+    /// Moves `stream` to the frame with index `frame_index`. All other
+    /// streams of the recording move to the frames recorded at the same time.
+    pub fn seek(&self, stream: &Stream, frame_index: i32) -> Result<(), Status> {
+        if let Some(backend) = stream.backend() {
+            if frame_index < 0 {
+                return Err(Status::BadParameter);
+            }
+            return backend.seek(frame_index as usize);
+        }
+        let mut seek = OniSeek {
+            frameIndex: frame_index,
+            _padding: [0; SEEK_PADDING],
+            stream: stream.handle(),
+        };
+        self.device.invoke_command(ONI_DEVICE_COMMAND_SEEK, &mut seek)
+    }
+
+    /// Returns the total number of frames of `stream` in the recording.
+    pub fn number_of_frames(&self, stream: &Stream) -> Result<i32, Status> {
+        stream.get_number_of_frames()
+    }
+
+    /// Gets the playback speed, where 1.0 is real time. Returns
+    /// `PlaybackControl::MANUAL_SPEED` when manual stepping is on.
+    pub fn speed(&self) -> Result<f32, Status> {
+        self.device.get_playback_speed()
+    }
+
+    /// Sets the playback speed, where 1.0 is real time and 2.0 is twice as fast.
+    pub fn set_speed(&self, speed: f32) -> Result<(), Status> {
+        self.device.set_playback_speed(speed)
+    }
+
+    /// Returns whether manual stepping is on.
+    pub fn manual_stepping(&self) -> Result<bool, Status> {
+        Ok(self.speed()? == Self::MANUAL_SPEED)
+    }
+
+    /// Turns manual stepping on, or returns to real time playback.
+    pub fn set_manual_stepping(&self, manual: bool) -> Result<(), Status> {
+        self.set_speed(if manual { Self::MANUAL_SPEED } else { 1.0 })
+    }
+
+    /// Returns whether playback starts over after the last frame.
+    pub fn repeat(&self) -> Result<bool, Status> {
+        self.device.get_playback_repeat_enabled()
+    }
+
+    /// Sets whether playback starts over after the last frame.
+    pub fn set_repeat(&self, repeat: bool) -> Result<(), Status> {
+        self.device.set_playback_repeat_enabled(repeat)
+    }
+
+    /// Reads the next frame of `stream`, turning on manual stepping first if
+    /// it isn't already, so that each call advances by exactly one frame.
+    /// The stream must be started.
+    pub fn step<P: Pixel>(&self, stream: &Stream) -> Result<Frame<P>, Status> {
+        if !self.manual_stepping()? {
+            self.set_manual_stepping(true)?;
+        }
+        stream.read_frame::<P>()
+    }
+}
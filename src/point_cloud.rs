@@ -0,0 +1,143 @@
+use openni2_sys::*;
+use frame::Frame;
+use intrinsics::Intrinsics;
+use stream::Stream;
+use types::{Status, PixelFormat};
+
+/// A set of 3D points in millimeters, in the same coordinate system as
+/// `Stream::depth_to_world`: x increases to the right, y increases upward,
+/// and z is the distance from the sensor.
+#[derive(Debug, Clone)]
+pub struct PointCloud {
+    /// The points of the cloud. For an organized cloud, there is one point
+    /// per depth pixel in row order, and pixels with no depth reading are
+    /// `[NaN, NaN, NaN]`. A dense cloud only contains valid points.
+    pub points: Vec<[f32; 3]>,
+    /// The color of each point, if the cloud was built with
+    /// `PointCloudBuilder::build_colored`.
+    pub colors: Option<Vec<OniRGB888Pixel>>,
+    /// Width of the depth frame the cloud was built from.
+    pub width: u16,
+    /// Height of the depth frame the cloud was built from.
+    pub height: u16,
+    /// Whether `points` has one entry per pixel of the depth frame.
+    pub organized: bool,
+}
+
+/// Converts whole depth frames into `PointCloud`s in a single pass, instead
+/// of calling `Stream::depth_to_world` on every pixel.
+///
+/// # Example
+/// ```
+/// # use openni2::{Device, SensorType, OniDepthPixel, PointCloudBuilder};
+/// # fn main() -> Result<(), openni2::Status> {
+/// # /*
+/// let device = Device::open_default()?;
+/// # */
+/// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+/// let stream = device.create_stream(SensorType::DEPTH)?;
+/// let builder = PointCloudBuilder::from_stream(&stream)?.organized(true);
+/// stream.start()?;
+/// let frame = stream.read_frame::<OniDepthPixel>()?;
+/// let cloud = builder.build(&frame);
+/// assert_eq!(cloud.points.len(), frame.width() as usize * frame.height() as usize);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct PointCloudBuilder {
+    horizontal_fov: f32,
+    vertical_fov: f32,
+    organized: bool,
+}
+
+impl PointCloudBuilder {
+    /// Creates a builder for a depth sensor with the given fields of view,
+    /// in radians. Builds dense clouds by default.
+    pub fn new(horizontal_fov: f32, vertical_fov: f32) -> Self {
+        PointCloudBuilder {
+            horizontal_fov,
+            vertical_fov,
+            organized: false,
+        }
+    }
+
+    /// Creates a builder using a depth stream's fields of view.
+    pub fn from_stream(stream: &Stream) -> Result<Self, Status> {
+        Ok(Self::new(stream.get_horizontal_fov()?, stream.get_vertical_fov()?))
+    }
+
+    /// Sets whether to build organized clouds, with one point per pixel, or
+    /// dense clouds without the pixels that have no depth reading.
+    pub fn organized(mut self, organized: bool) -> Self {
+        self.organized = organized;
+        self
+    }
+
+    /// Builds a cloud from a depth frame.
+    pub fn build(&self, depth: &Frame<OniDepthPixel>) -> PointCloud {
+        self.build_from(depth, None)
+    }
+
+    /// Builds a cloud from a depth frame, taking the color of each point from
+    /// the same pixel of a color frame. The depth stream should be aligned to
+    /// the color stream with `Device::set_image_registration`.
+    ///
+    /// Returns `Err(Status::BadParameter)` if the frames' dimensions differ.
+    pub fn build_colored(&self, depth: &Frame<OniDepthPixel>, color: &Frame<OniRGB888Pixel>) -> Result<PointCloud, Status> {
+        if depth.dimensions() != color.dimensions() {
+            return Err(Status::BadParameter);
+        }
+        Ok(self.build_from(depth, Some(color.pixels())))
+    }
+
+    fn build_from(&self, depth: &Frame<OniDepthPixel>, color: Option<&[OniRGB888Pixel]>) -> PointCloud {
+        let (width, height) = depth.dimensions();
+        let video_mode = depth.video_mode();
+        let z_scale = match video_mode.pixel_format {
+            PixelFormat::DEPTH_100_UM => 0.1,
+            _ => 1.0,
+        };
+
+        let intrinsics = Intrinsics::from_fov(
+            self.horizontal_fov,
+            self.vertical_fov,
+            video_mode.resolution_x as u16,
+            video_mode.resolution_y as u16,
+        ).for_frame(depth);
+        let column_factors: Vec<f32> = (0..width)
+            .map(|x| (f32::from(x) - intrinsics.cx) / intrinsics.fx)
+            .collect();
+        let row_factors: Vec<f32> = (0..height)
+            .map(|y| (intrinsics.cy - f32::from(y)) / intrinsics.fy)
+            .collect();
+
+        let capacity = width as usize * height as usize;
+        let mut points = Vec::with_capacity(capacity);
+        let mut colors = color.map(|_| Vec::with_capacity(capacity));
+        for (i, &z) in depth.pixels().iter().enumerate() {
+            let (x, y) = (i % width as usize, i / width as usize);
+            let point = if z == 0 {
+                if !self.organized {
+                    continue;
+                }
+                [f32::NAN; 3]
+            } else {
+                let z = f32::from(z) * z_scale;
+                [column_factors[x] * z, row_factors[y] * z, z]
+            };
+            points.push(point);
+            if let (Some(colors), Some(color)) = (colors.as_mut(), color) {
+                colors.push(color[i]);
+            }
+        }
+
+        PointCloud {
+            points,
+            colors,
+            width,
+            height,
+            organized: self.organized,
+        }
+    }
+}
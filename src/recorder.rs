@@ -48,7 +48,14 @@ impl Recorder {
     ///
     /// There is no practical limit to the number of streams that can be attached
     /// to the recorder at once.
+    ///
+    /// Streams of a `DeviceBackend` can't be recorded by OpenNI2; attaching
+    /// one returns `Err(Status::NotSupported)`. Their frames can be written
+    /// with `oni::Writer` instead.
     pub fn attach_stream(&self, stream: &Stream, lossy: bool) -> Result<(), Status> {
+        if stream.handle().is_null() {
+            return Err(Status::NotSupported);
+        }
         let lossy = if lossy { 1 } else { 0 };
         let status = unsafe {
             oniRecorderAttachStream(self.handle, stream.handle(), lossy)
@@ -0,0 +1,669 @@
+//! Synthetic scenes of planes, boxes and spheres, ray-cast into depth, IR
+//! and color frames, for testing processing code against ground truth.
+//!
+//! Positions are in millimeters, in the coordinates `Stream::depth_to_world`
+//! uses: x to the right, y up, and z away from the camera. A `Scene` is
+//! rendered through a pinhole camera with the `Intrinsics` of the requested
+//! `VideoMode` and field of view, so the depth at pixel `(x, y)` is the `z`
+//! that `Intrinsics::unproject` expects there. Objects and the camera can
+//! move over time, following a `Motion` evaluated at each frame's timestamp,
+//! and `Noise` adds depth error, holes and intensity noise like a PS1080's.
+//! Noise is deterministic: the same frame of the same scene always renders
+//! the same way.
+//!
+//! A `Scene` is a `mock::FrameGenerator`, and `Scene::device` makes a
+//! `MockDevice` that renders it with a registered color sensor, i.e. one
+//! that sees the scene from the same viewpoint as the depth sensor.
+//!
+//! # Example
+//! ```
+//! # use openni2::{Device, SensorType, Status, OniDepthPixel, OniRGB888Pixel, Intrinsics};
+//! # use openni2::scene::{Scene, Object, Shape, Motion};
+//! # fn main() -> Result<(), Status> {
+//! let scene = Scene::new()
+//!     .object(Object::new(Shape::Plane { point: [0.0, 0.0, 2000.0], normal: [0.0, 0.0, -1.0] }))
+//!     .object(Object::new(Shape::Sphere { center: [0.0, 0.0, 1000.0], radius: 200.0 })
+//!         .color(OniRGB888Pixel { r: 255, g: 0, b: 0 })
+//!         .motion(Motion::Linear { velocity: [0.0, 0.0, 300.0] }));
+//! let device = Device::from_backend(scene.clone().device());
+//! let stream = device.create_stream(SensorType::DEPTH)?;
+//! stream.start()?;
+//!
+//! let frame = stream.read_frame::<OniDepthPixel>()?;
+//! let truth = scene.ground_truth(&Intrinsics::from_stream(&stream)?, frame.timestamp());
+//! assert_eq!(frame.pixels(), &truth[..]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::f32::consts::PI;
+
+use openni2_sys::*;
+use intrinsics::Intrinsics;
+use mock::{FrameGenerator, FrameRequest, MockDevice, DEPTH_FOV};
+use types::{Status, SensorType, PixelFormat};
+
+// IR intensity of a white surface facing the camera 1 m away.
+const IR_SCALE: f32 = 512.0;
+// Largest value of a PS1080's 10-bit IR pixels.
+const IR_MAX: f32 = 1023.0;
+// Fraction of a color surface's brightness that doesn't depend on its angle
+// to the camera.
+const AMBIENT: f32 = 0.3;
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 { scale(a, 1.0 / length) } else { a }
+}
+
+/// A rigid transform: a rotation followed by a translation, in millimeters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pose {
+    /// Rotation matrix, by rows
+    pub rotation: [[f32; 3]; 3],
+    pub position: [f32; 3],
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Pose {
+    pub fn identity() -> Self {
+        Pose {
+            rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            position: [0.0; 3],
+        }
+    }
+
+    pub fn translation(offset: [f32; 3]) -> Self {
+        Pose { position: offset, ..Self::identity() }
+    }
+
+    /// A rotation by `angle` radians around `axis`, which goes through the
+    /// origin. Positive angles turn counterclockwise when looking down the
+    /// axis towards the origin.
+    pub fn rotation(axis: [f32; 3], angle: f32) -> Self {
+        let [x, y, z] = normalize(axis);
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        Pose {
+            rotation: [
+                [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+                [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+                [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+            ],
+            position: [0.0; 3],
+        }
+    }
+
+    /// The pose of a camera at `eye` that looks at `target`, with its image
+    /// upright: the camera's x axis stays level, in the world's x-z plane.
+    pub fn look_at(eye: [f32; 3], target: [f32; 3]) -> Self {
+        let forward = normalize(sub(target, eye));
+        let mut right = normalize(cross([0.0, 1.0, 0.0], forward));
+        if dot(right, right) == 0.0 {
+            // Looking straight up or down
+            right = [1.0, 0.0, 0.0];
+        }
+        let up = cross(forward, right);
+        Pose {
+            rotation: [
+                [right[0], up[0], forward[0]],
+                [right[1], up[1], forward[1]],
+                [right[2], up[2], forward[2]],
+            ],
+            position: eye,
+        }
+    }
+
+    /// Returns the pose that applies `self`, then `next`.
+    pub fn then(&self, next: &Pose) -> Self {
+        let mut rotation = [[0.0; 3]; 3];
+        for (row, next_row) in rotation.iter_mut().zip(&next.rotation) {
+            for (col, value) in row.iter_mut().enumerate() {
+                *value = dot(*next_row, [self.rotation[0][col], self.rotation[1][col], self.rotation[2][col]]);
+            }
+        }
+        Pose {
+            rotation,
+            position: next.transform_point(self.position),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        let r = self.rotation;
+        let rotation = [
+            [r[0][0], r[1][0], r[2][0]],
+            [r[0][1], r[1][1], r[2][1]],
+            [r[0][2], r[1][2], r[2][2]],
+        ];
+        let inverse = Pose { rotation, position: [0.0; 3] };
+        Pose {
+            position: scale(inverse.transform_vector(self.position), -1.0),
+            ..inverse
+        }
+    }
+
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        add(self.transform_vector(point), self.position)
+    }
+
+    /// Rotates a direction, without translating it.
+    pub fn transform_vector(&self, vector: [f32; 3]) -> [f32; 3] {
+        let r = self.rotation;
+        [dot(r[0], vector), dot(r[1], vector), dot(r[2], vector)]
+    }
+}
+
+/// The geometry of an `Object`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Shape {
+    /// An infinite plane through `point`, facing either way.
+    Plane { point: [f32; 3], normal: [f32; 3] },
+    /// A box of `size` millimeters along its x, y and z axes, centered on
+    /// the position of `pose`.
+    Cuboid { pose: Pose, size: [f32; 3] },
+    Sphere { center: [f32; 3], radius: f32 },
+}
+
+impl Shape {
+    /// Returns the shape moved by `pose`.
+    pub fn transformed(&self, pose: &Pose) -> Shape {
+        match *self {
+            Shape::Plane { point, normal } => Shape::Plane {
+                point: pose.transform_point(point),
+                normal: pose.transform_vector(normal),
+            },
+            Shape::Cuboid { pose: own, size } => Shape::Cuboid { pose: own.then(pose), size },
+            Shape::Sphere { center, radius } => Shape::Sphere { center: pose.transform_point(center), radius },
+        }
+    }
+
+    // Returns the smallest positive `t` at which the ray `origin + t *
+    // direction` meets the shape, with the unit surface normal there.
+    fn intersect(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<(f32, [f32; 3])> {
+        const EPSILON: f32 = 1e-4;
+        match *self {
+            Shape::Plane { point, normal } => {
+                let denominator = dot(direction, normal);
+                if denominator.abs() < EPSILON {
+                    return None;
+                }
+                let t = dot(sub(point, origin), normal) / denominator;
+                if t > EPSILON { Some((t, normalize(normal))) } else { None }
+            },
+            Shape::Sphere { center, radius } => {
+                let offset = sub(origin, center);
+                let a = dot(direction, direction);
+                let b = dot(offset, direction);
+                let discriminant = b * b - a * (dot(offset, offset) - radius * radius);
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                let t = if (-b - root) / a > EPSILON { (-b - root) / a } else { (-b + root) / a };
+                if t > EPSILON {
+                    Some((t, normalize(sub(add(origin, scale(direction, t)), center))))
+                } else {
+                    None
+                }
+            },
+            Shape::Cuboid { pose, size } => {
+                let inverse = pose.inverse();
+                let (origin, direction) = (inverse.transform_point(origin), inverse.transform_vector(direction));
+                let (mut near, mut far) = ((f32::NEG_INFINITY, 0), (f32::INFINITY, 0));
+                for axis in 0..3 {
+                    let half = size[axis] / 2.0;
+                    if direction[axis].abs() < EPSILON {
+                        if origin[axis].abs() > half {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let (t1, t2) = ((-half - origin[axis]) / direction[axis], (half - origin[axis]) / direction[axis]);
+                    if t1.min(t2) > near.0 {
+                        near = (t1.min(t2), axis);
+                    }
+                    if t1.max(t2) < far.0 {
+                        far = (t1.max(t2), axis);
+                    }
+                }
+                if near.0 > far.0 || far.0 < EPSILON {
+                    return None;
+                }
+                // Inside the box, the ray meets its far side
+                let (t, axis) = if near.0 > EPSILON { near } else { far };
+                let mut normal = [0.0; 3];
+                normal[axis] = 1.0;
+                Some((t, pose.transform_vector(normal)))
+            },
+        }
+    }
+}
+
+/// How an object or camera moves, as a function of time in seconds.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Motion {
+    #[default]
+    Static,
+    /// Constant velocity, in millimeters per second
+    Linear { velocity: [f32; 3] },
+    /// Back and forth along `amplitude`, starting at the object's original
+    /// position and moving `amplitude` millimeters away from it and back
+    /// once every `period` seconds
+    Oscillate { amplitude: [f32; 3], period: f32 },
+    /// Rotation around an axis through `center`, at `rate` radians per
+    /// second
+    Rotate { center: [f32; 3], axis: [f32; 3], rate: f32 },
+}
+
+impl Motion {
+    /// Returns how far the motion has moved its subject after `seconds`.
+    pub fn pose_at(&self, seconds: f32) -> Pose {
+        match *self {
+            Motion::Static => Pose::identity(),
+            Motion::Linear { velocity } => Pose::translation(scale(velocity, seconds)),
+            Motion::Oscillate { amplitude, period } => {
+                Pose::translation(scale(amplitude, (2.0 * PI * seconds / period).sin()))
+            },
+            Motion::Rotate { center, axis, rate } => Pose::translation(scale(center, -1.0))
+                .then(&Pose::rotation(axis, rate * seconds))
+                .then(&Pose::translation(center)),
+        }
+    }
+}
+
+/// A shape in a `Scene`, and how it looks to the color and IR sensors.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Object {
+    shape: Shape,
+    color: OniRGB888Pixel,
+    reflectivity: f32,
+    motion: Motion,
+}
+
+impl Object {
+    /// Creates a static, light gray object that reflects all IR light.
+    pub fn new(shape: Shape) -> Self {
+        Object {
+            shape,
+            color: OniRGB888Pixel { r: 200, g: 200, b: 200 },
+            reflectivity: 1.0,
+            motion: Motion::Static,
+        }
+    }
+
+    pub fn color(mut self, color: OniRGB888Pixel) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the fraction of IR light the object reflects, from 0 to 1.
+    pub fn reflectivity(mut self, reflectivity: f32) -> Self {
+        self.reflectivity = reflectivity;
+        self
+    }
+
+    pub fn motion(mut self, motion: Motion) -> Self {
+        self.motion = motion;
+        self
+    }
+
+    /// Returns the object's shape `seconds` into its motion.
+    pub fn shape_at(&self, seconds: f32) -> Shape {
+        self.shape.transformed(&self.motion.pose_at(seconds))
+    }
+}
+
+/// Imperfections added to rendered frames. `Noise::none()`, the default,
+/// renders the ground truth.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Noise {
+    /// Standard deviation of depth error at 1 m, in millimeters. It grows
+    /// with the square of the depth, as a structured light sensor's does.
+    pub depth_sigma: f32,
+    /// Fraction of depth pixels, chosen at random, that have no reading
+    pub dropout: f32,
+    /// Surfaces seen at a larger angle than this, in radians, have no depth
+    /// reading
+    pub max_incidence_angle: f32,
+    /// Closest depth with a reading, in millimeters
+    pub min_depth: f32,
+    /// Farthest depth with a reading, in millimeters
+    pub max_depth: f32,
+    /// Standard deviation of IR and color values, in pixel values
+    pub intensity_sigma: f32,
+    /// Seed of the pseudo-random noise; scenes with different seeds render
+    /// different noise
+    pub seed: u64,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl Noise {
+    pub fn none() -> Self {
+        Noise {
+            depth_sigma: 0.0,
+            dropout: 0.0,
+            max_incidence_angle: PI / 2.0,
+            min_depth: 0.0,
+            max_depth: f32::INFINITY,
+            intensity_sigma: 0.0,
+            seed: 0,
+        }
+    }
+
+    /// Noise like that of a PS1080: about 1.5 mm of depth error at 1 m,
+    /// readings from 0.5 m to 6 m, and holes on steep surfaces.
+    pub fn ps1080() -> Self {
+        Noise {
+            depth_sigma: 1.5,
+            dropout: 0.01,
+            max_incidence_angle: 75f32.to_radians(),
+            min_depth: 500.0,
+            max_depth: 6000.0,
+            intensity_sigma: 2.0,
+            seed: 0,
+        }
+    }
+}
+
+// Deterministic pseudo-random numbers for one pixel of one frame, from a
+// splitmix64 sequence.
+struct Dice(u64);
+
+impl Dice {
+    fn new(noise: &Noise, request: &FrameRequest, pixel: usize) -> Self {
+        let mut dice = Dice(noise.seed);
+        for &value in &[request.sensor_type as u64, request.index as u64, pixel as u64] {
+            dice.0 ^= dice.next() ^ value;
+        }
+        dice
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn uniform(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Standard normal, by the Box-Muller transform
+    fn gaussian(&mut self) -> f32 {
+        let (u1, u2) = (1.0 - self.uniform(), self.uniform());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+/// Where a camera ray meets a `Scene`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit {
+    /// Distance along the camera's z axis, in millimeters
+    pub depth: f32,
+    /// Unit surface normal, in camera coordinates, facing the camera
+    pub normal: [f32; 3],
+    /// Angle between the surface normal and the ray, in radians
+    pub incidence: f32,
+    /// Index of the object, in the order they were added to the scene
+    pub object: usize,
+}
+
+/// A set of objects seen by a moving camera. See the module documentation.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    objects: Vec<Object>,
+    camera: Pose,
+    camera_motion: Motion,
+    noise: Noise,
+    background: OniRGB888Pixel,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene {
+    /// Creates an empty scene with the camera at the origin, looking along
+    /// the z axis, and no noise.
+    pub fn new() -> Self {
+        Scene {
+            objects: Vec::new(),
+            camera: Pose::identity(),
+            camera_motion: Motion::Static,
+            noise: Noise::none(),
+            background: OniRGB888Pixel { r: 0, g: 0, b: 0 },
+        }
+    }
+
+    pub fn object(mut self, object: Object) -> Self {
+        self.objects.push(object);
+        self
+    }
+
+    /// Sets the pose of the camera in the scene, e.g. `Pose::look_at(eye,
+    /// target)`.
+    pub fn camera(mut self, pose: Pose) -> Self {
+        self.camera = pose;
+        self
+    }
+
+    pub fn camera_motion(mut self, motion: Motion) -> Self {
+        self.camera_motion = motion;
+        self
+    }
+
+    pub fn noise(mut self, noise: Noise) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// Sets the color seen where no object is.
+    pub fn background(mut self, color: OniRGB888Pixel) -> Self {
+        self.background = color;
+        self
+    }
+
+    /// Casts a ray through every pixel of a camera with `intrinsics`, at
+    /// `timestamp` microseconds, and returns where they hit the scene, by
+    /// rows.
+    pub fn cast(&self, intrinsics: &Intrinsics, timestamp: u64) -> Vec<Option<Hit>> {
+        let seconds = (timestamp as f64 / 1e6) as f32;
+        let camera = self.camera.then(&self.camera_motion.pose_at(seconds));
+        let world_to_camera = camera.inverse();
+        let shapes: Vec<Shape> = self.objects.iter().map(|object| object.shape_at(seconds)).collect();
+
+        let (width, height) = (usize::from(intrinsics.width), usize::from(intrinsics.height));
+        let mut hits = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                // At depth 1, so that the ray's t is the depth of the hit
+                let ray = intrinsics.unproject(x as f32, y as f32, 1.0);
+                let direction = camera.transform_vector(ray);
+                let nearest = shapes.iter().enumerate()
+                    .filter_map(|(object, shape)| shape.intersect(camera.position, direction).map(|(t, normal)| (t, normal, object)))
+                    .fold(None, |nearest: Option<(f32, [f32; 3], usize)>, hit| match nearest {
+                        Some(nearest) if nearest.0 <= hit.0 => Some(nearest),
+                        _ => Some(hit),
+                    });
+                hits.push(nearest.map(|(depth, normal, object)| {
+                    let mut normal = world_to_camera.transform_vector(normal);
+                    let cos = dot(normal, normalize(ray));
+                    if cos > 0.0 {
+                        normal = scale(normal, -1.0);
+                    }
+                    Hit { depth, normal, incidence: cos.abs().min(1.0).acos(), object }
+                }));
+            }
+        }
+        hits
+    }
+
+    /// Returns the noise-free depth of every pixel of a camera with
+    /// `intrinsics`, at `timestamp` microseconds, in millimeters: the frame
+    /// a `DEPTH_1_MM` stream of this scene reads without `Noise`.
+    pub fn ground_truth(&self, intrinsics: &Intrinsics, timestamp: u64) -> Vec<OniDepthPixel> {
+        self.cast(intrinsics, timestamp).iter()
+            .map(|hit| hit.map_or(0, |hit| depth_value(hit.depth, 1.0)))
+            .collect()
+    }
+
+    /// Makes a `MockDevice` that renders this scene on all its sensors,
+    /// which all have the depth sensor's field of view, so color frames are
+    /// registered to depth frames.
+    pub fn device(self) -> MockDevice {
+        MockDevice::new()
+            .generator(self)
+            .field_of_view(DEPTH_FOV.0, DEPTH_FOV.1)
+    }
+
+    fn depth(&self, request: &FrameRequest, hits: &[Option<Hit>], units: f32) -> Vec<u8> {
+        let noise = &self.noise;
+        let mut data = Vec::with_capacity(hits.len() * 2);
+        for (pixel, hit) in hits.iter().enumerate() {
+            let mut dice = Dice::new(noise, request, pixel);
+            let depth = hit
+                .filter(|hit| hit.incidence <= noise.max_incidence_angle)
+                .filter(|_| noise.dropout <= 0.0 || dice.uniform() >= noise.dropout)
+                .map(|hit| hit.depth + noise.depth_sigma * (hit.depth / 1000.0).powi(2) * dice.gaussian())
+                .filter(|&depth| depth >= noise.min_depth && depth <= noise.max_depth)
+                .map_or(0, |depth| depth_value(depth, units));
+            data.extend_from_slice(&depth.to_le_bytes());
+        }
+        data
+    }
+
+    fn ir(&self, request: &FrameRequest, hits: &[Option<Hit>]) -> Vec<f32> {
+        hits.iter().enumerate().map(|(pixel, hit)| {
+            let intensity = hit.map_or(0.0, |hit| {
+                let meters = hit.depth / 1000.0;
+                IR_SCALE * self.objects[hit.object].reflectivity * hit.incidence.cos() / (meters * meters)
+            });
+            let noise = self.noise.intensity_sigma * Dice::new(&self.noise, request, pixel).gaussian();
+            (intensity + noise).clamp(0.0, IR_MAX)
+        }).collect()
+    }
+
+    fn color(&self, request: &FrameRequest, hits: &[Option<Hit>]) -> Vec<OniRGB888Pixel> {
+        hits.iter().enumerate().map(|(pixel, hit)| {
+            let (color, shade) = hit.map_or((self.background, 1.0), |hit| {
+                (self.objects[hit.object].color, AMBIENT + (1.0 - AMBIENT) * hit.incidence.cos())
+            });
+            let mut dice = Dice::new(&self.noise, request, pixel);
+            let mut channel = |value: u8| {
+                let noise = self.noise.intensity_sigma * dice.gaussian();
+                (f32::from(value) * shade + noise).round().clamp(0.0, 255.0) as u8
+            };
+            OniRGB888Pixel { r: channel(color.r), g: channel(color.g), b: channel(color.b) }
+        }).collect()
+    }
+}
+
+// Rounds a depth in millimeters to a pixel in units of `units` millimeters,
+// or 0 if it doesn't fit.
+fn depth_value(depth: f32, units: f32) -> OniDepthPixel {
+    let value = (depth / units).round();
+    if value >= 0.0 && value <= f32::from(OniDepthPixel::MAX) { value as OniDepthPixel } else { 0 }
+}
+
+fn luma(px: OniRGB888Pixel) -> u8 {
+    ((299 * u32::from(px.r) + 587 * u32::from(px.g) + 114 * u32::from(px.b)) / 1000) as u8
+}
+
+// Returns y1, u, y2, v of a pair of pixels, by the full-range BT.601
+// transform that `convert` inverts.
+fn yuv_pair(first: OniRGB888Pixel, second: OniRGB888Pixel) -> [u8; 4] {
+    let (r, g, b) = (
+        (f32::from(first.r) + f32::from(second.r)) / 2.0,
+        (f32::from(first.g) + f32::from(second.g)) / 2.0,
+        (f32::from(first.b) + f32::from(second.b)) / 2.0,
+    );
+    let chroma = |value: f32| (value + 128.0).round().clamp(0.0, 255.0) as u8;
+    [
+        luma(first),
+        chroma(-0.168_736 * r - 0.331_264 * g + 0.5 * b),
+        luma(second),
+        chroma(0.5 * r - 0.418_688 * g - 0.081_312 * b),
+    ]
+}
+
+/// Renders depth frames in `DEPTH_1_MM` and `DEPTH_100_UM`, IR frames in
+/// `GRAY16` and `GRAY8`, and color frames in `RGB888`, `YUV422`, `YUYV` and
+/// `GRAY8`, and returns `Err(Status::NotSupported)` for other formats.
+///
+/// IR intensity falls off with the square of the distance from the camera,
+/// which is also the light source: a white surface facing the camera 1 m
+/// away has a `GRAY16` value of 512, out of at most 1023. Color surfaces are
+/// lit the same way, but without the falloff, and with some ambient light.
+impl FrameGenerator for Scene {
+    fn generate(&self, request: &FrameRequest) -> Result<Vec<u8>, Status> {
+        let mode = request.video_mode;
+        let intrinsics = Intrinsics::from_fov(request.horizontal_fov, request.vertical_fov, mode.resolution_x as u16, mode.resolution_y as u16);
+        let render = || self.cast(&intrinsics, request.timestamp);
+
+        match (request.sensor_type, mode.pixel_format) {
+            (SensorType::DEPTH, PixelFormat::DEPTH_1_MM) => Ok(self.depth(request, &render(), 1.0)),
+            (SensorType::DEPTH, PixelFormat::DEPTH_100_UM) => Ok(self.depth(request, &render(), 0.1)),
+            (SensorType::IR, PixelFormat::GRAY16) => Ok(self.ir(request, &render()).iter()
+                .flat_map(|&value| (value.round() as u16).to_le_bytes())
+                .collect()),
+            (SensorType::IR, PixelFormat::GRAY8) => Ok(self.ir(request, &render()).iter()
+                .map(|&value| (value / 4.0).round() as u8)
+                .collect()),
+            (SensorType::COLOR, PixelFormat::RGB888) => Ok(self.color(request, &render()).iter()
+                .flat_map(|px| [px.r, px.g, px.b])
+                .collect()),
+            (SensorType::COLOR, PixelFormat::GRAY8) => Ok(self.color(request, &render()).into_iter().map(luma).collect()),
+            (SensorType::COLOR, PixelFormat::YUV422) | (SensorType::COLOR, PixelFormat::YUYV) => {
+                let pixels = self.color(request, &render());
+                let mut data = Vec::with_capacity(pixels.len() * 2);
+                for row in pixels.chunks(usize::from(intrinsics.width).max(1)) {
+                    for pair in row.chunks(2) {
+                        let [y1, u, y2, v] = yuv_pair(pair[0], pair[pair.len() - 1]);
+                        if mode.pixel_format == PixelFormat::YUV422 {
+                            data.extend_from_slice(&[u, y1, v, y2]);
+                        } else {
+                            data.extend_from_slice(&[y1, u, y2, v]);
+                        }
+                    }
+                }
+                Ok(data)
+            },
+            _ => Err(Status::NotSupported),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::os::raw::c_double;
+
+use openni2_sys::*;
+use frame::{Frame, OwnedFrame};
+use stream::Stream;
+use oni::{StreamInfo, Property, PropertyValue};
+use types::{Status, PixelFormat, VideoMode, ShiftPixel};
+
+// Depth stream properties of the PS1080 driver (PS1080.h), along with the
+// names they are saved under in ONI recordings.
+const XN_STREAM_PROPERTY_CONST_SHIFT: OniStreamProperty = 0x1080_FF49;
+const XN_STREAM_PROPERTY_PIXEL_SIZE_FACTOR: OniStreamProperty = 0x1080_FF4A;
+const XN_STREAM_PROPERTY_MAX_SHIFT: OniStreamProperty = 0x1080_FF4B;
+const XN_STREAM_PROPERTY_PARAM_COEFF: OniStreamProperty = 0x1080_FF4C;
+const XN_STREAM_PROPERTY_SHIFT_SCALE: OniStreamProperty = 0x1080_FF4D;
+const XN_STREAM_PROPERTY_ZERO_PLANE_DISTANCE: OniStreamProperty = 0x1080_FF50;
+const XN_STREAM_PROPERTY_ZERO_PLANE_PIXEL_SIZE: OniStreamProperty = 0x1080_FF51;
+const XN_STREAM_PROPERTY_EMITTER_DCMOS_DISTANCE: OniStreamProperty = 0x1080_FF52;
+
+const PROPERTY_CONST_SHIFT: &str = "ConstShift";
+const PROPERTY_PIXEL_SIZE_FACTOR: &str = "PixelSizeFactor";
+const PROPERTY_MAX_SHIFT: &str = "MaxShift";
+const PROPERTY_PARAM_COEFF: &str = "ParamCoeff";
+const PROPERTY_SHIFT_SCALE: &str = "ShiftScale";
+const PROPERTY_ZERO_PLANE_DISTANCE: &str = "ZPD";
+const PROPERTY_ZERO_PLANE_PIXEL_SIZE: &str = "ZPPS";
+const PROPERTY_EMITTER_DCMOS_DISTANCE: &str = "LDDIS";
+const PROPERTY_DEVICE_MAX_DEPTH: &str = "xnDeviceMaxDepth";
+
+/// The maximum depth, in millimeters, that PS1080 devices report.
+const PS1080_MAX_DEPTH: u64 = 10000;
+
+/// The calibration parameters that a PS1080-based sensor (Kinect, Xtion,
+/// Carmine) uses to turn disparity shift into depth.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShiftCalibration {
+    /// Distance from the sensor to the reference plane, in millimeters
+    pub zero_plane_distance: u64,
+    /// Size of a pixel at the reference plane, in millimeters
+    pub zero_plane_pixel_size: f64,
+    /// Distance between the IR emitter and the depth camera, in centimeters
+    pub emitter_dcmos_distance: f64,
+    pub const_shift: u64,
+    pub param_coeff: u64,
+    pub shift_scale: u64,
+    pub pixel_size_factor: u64,
+    /// Shifts at or above this value have no depth
+    pub max_shift: u64,
+    /// Depths at or above this value, in millimeters, are discarded
+    pub max_depth: u64,
+}
+
+impl ShiftCalibration {
+    /// Reads the calibration parameters from a depth stream of a device
+    /// using the PS1080 driver. Other drivers return `Err`.
+    pub fn from_stream(stream: &Stream) -> Result<Self, Status> {
+        Ok(ShiftCalibration {
+            zero_plane_distance: stream.get_property::<u64>(XN_STREAM_PROPERTY_ZERO_PLANE_DISTANCE)?,
+            zero_plane_pixel_size: stream.get_property::<c_double>(XN_STREAM_PROPERTY_ZERO_PLANE_PIXEL_SIZE)?,
+            emitter_dcmos_distance: stream.get_property::<c_double>(XN_STREAM_PROPERTY_EMITTER_DCMOS_DISTANCE)?,
+            const_shift: stream.get_property::<u64>(XN_STREAM_PROPERTY_CONST_SHIFT)?,
+            param_coeff: stream.get_property::<u64>(XN_STREAM_PROPERTY_PARAM_COEFF)?,
+            shift_scale: stream.get_property::<u64>(XN_STREAM_PROPERTY_SHIFT_SCALE)?,
+            pixel_size_factor: stream.get_property::<u64>(XN_STREAM_PROPERTY_PIXEL_SIZE_FACTOR)?,
+            max_shift: stream.get_property::<u64>(XN_STREAM_PROPERTY_MAX_SHIFT)?,
+            max_depth: PS1080_MAX_DEPTH,
+        })
+    }
+
+    /// Reads the calibration parameters recorded for a stream of an ONI file.
+    /// Returns `Err` naming the first parameter that wasn't recorded.
+    pub fn from_oni_stream(stream: &StreamInfo) -> Result<Self, Status> {
+        let int = |name: &str| match stream.property(name) {
+            Some(&PropertyValue::Int(value)) => Ok(value),
+            _ => Err(Status::Error(format!("ONI stream {} has no {} property", stream.node_id, name))),
+        };
+        let real = |name: &str| match stream.property(name) {
+            Some(&PropertyValue::Real(value)) => Ok(value),
+            _ => Err(Status::Error(format!("ONI stream {} has no {} property", stream.node_id, name))),
+        };
+        Ok(ShiftCalibration {
+            zero_plane_distance: int(PROPERTY_ZERO_PLANE_DISTANCE)?,
+            zero_plane_pixel_size: real(PROPERTY_ZERO_PLANE_PIXEL_SIZE)?,
+            emitter_dcmos_distance: real(PROPERTY_EMITTER_DCMOS_DISTANCE)?,
+            const_shift: int(PROPERTY_CONST_SHIFT)?,
+            param_coeff: int(PROPERTY_PARAM_COEFF)?,
+            shift_scale: int(PROPERTY_SHIFT_SCALE)?,
+            pixel_size_factor: int(PROPERTY_PIXEL_SIZE_FACTOR)?,
+            max_shift: int(PROPERTY_MAX_SHIFT)?,
+            max_depth: int(PROPERTY_DEVICE_MAX_DEPTH).unwrap_or(PS1080_MAX_DEPTH),
+        })
+    }
+
+    /// Returns the parameters as ONI properties, which can be saved with
+    /// `oni::Writer::set_property` and read back with
+    /// `ShiftCalibration::from_oni_stream`.
+    pub fn to_properties(&self) -> Vec<Property> {
+        let property = |name: &str, value| Property { name: name.to_string(), value };
+        vec![
+            property(PROPERTY_ZERO_PLANE_DISTANCE, PropertyValue::Int(self.zero_plane_distance)),
+            property(PROPERTY_ZERO_PLANE_PIXEL_SIZE, PropertyValue::Real(self.zero_plane_pixel_size)),
+            property(PROPERTY_EMITTER_DCMOS_DISTANCE, PropertyValue::Real(self.emitter_dcmos_distance)),
+            property(PROPERTY_CONST_SHIFT, PropertyValue::Int(self.const_shift)),
+            property(PROPERTY_PARAM_COEFF, PropertyValue::Int(self.param_coeff)),
+            property(PROPERTY_SHIFT_SCALE, PropertyValue::Int(self.shift_scale)),
+            property(PROPERTY_PIXEL_SIZE_FACTOR, PropertyValue::Int(self.pixel_size_factor)),
+            property(PROPERTY_MAX_SHIFT, PropertyValue::Int(self.max_shift)),
+            property(PROPERTY_DEVICE_MAX_DEPTH, PropertyValue::Int(self.max_depth)),
+        ]
+    }
+}
+
+/// A lookup table from raw disparity shift (`PixelFormat::SHIFT_9_2` and
+/// `SHIFT_9_3`) to depth in millimeters, built from a `ShiftCalibration` the
+/// same way the PS1080 driver builds its own.
+///
+/// Recording raw shift keeps the sensor's full precision; a `ShiftToDepth`
+/// can convert it to depth at any later time.
+///
+/// # Example
+/// ```no_run
+/// # use openni2::{Device, SensorType, ShiftToDepth, Shift92};
+/// # fn main() -> Result<(), openni2::Status> {
+/// let device = Device::open_default()?;
+/// let stream = device.create_typed_stream::<Shift92>(SensorType::DEPTH)?;
+/// let shift_to_depth = ShiftToDepth::from_stream(&stream)?;
+/// stream.start()?;
+/// let depth = shift_to_depth.convert(&stream.read_frame()?);
+/// println!("{}mm", depth.pixels[0]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShiftToDepth {
+    table: Vec<u16>,
+}
+
+impl ShiftToDepth {
+    /// Builds the lookup table for a calibration. Returns `Err` if
+    /// `param_coeff * const_shift` overflows.
+    pub fn new(calibration: &ShiftCalibration) -> Result<Self, Status> {
+        let pixel_size_factor = calibration.pixel_size_factor.max(1);
+        let param_coeff = calibration.param_coeff.max(1) as f64;
+        let plane_pixel_size = calibration.zero_plane_pixel_size * pixel_size_factor as f64;
+        let plane_distance = calibration.zero_plane_distance as f64;
+        let plane_dcl = calibration.emitter_dcmos_distance;
+        // The driver divides in integers, truncating the constant shift
+        let const_shift = calibration.param_coeff.checked_mul(calibration.const_shift)
+            .map(|shift| (shift / pixel_size_factor) as f64)
+            .ok_or_else(|| Status::Error(format!(
+                "Shift calibration overflows: param_coeff {} * const_shift {}",
+                calibration.param_coeff, calibration.const_shift)))?;
+
+        let mut table = vec![0u16; calibration.max_shift as usize];
+        for (shift, depth) in table.iter_mut().enumerate().skip(1) {
+            let fixed_ref_x = (shift as f64 - const_shift) / param_coeff - 0.375;
+            let metric = fixed_ref_x * plane_pixel_size;
+            let value = calibration.shift_scale as f64 * (metric * plane_distance / (plane_dcl - metric) + plane_distance);
+            if value > 0.0 && value < calibration.max_depth as f64 {
+                *depth = value as u16;
+            }
+        }
+        Ok(ShiftToDepth { table })
+    }
+
+    /// Reads the calibration of a PS1080 depth stream and builds its lookup
+    /// table. See `ShiftCalibration::from_stream`.
+    pub fn from_stream(stream: &Stream) -> Result<Self, Status> {
+        Self::new(&ShiftCalibration::from_stream(stream)?)
+    }
+
+    /// The lookup table, indexed by shift. A depth of 0 means the shift has
+    /// no valid depth.
+    pub fn table(&self) -> &[u16] {
+        &self.table
+    }
+
+    /// Returns the depth of a shift value in millimeters, or 0 if it has none.
+    pub fn depth(&self, shift: ShiftPixel) -> OniDepthPixel {
+        self.table.get(shift.0 as usize).cloned().unwrap_or(0)
+    }
+
+    /// Converts a frame of shift values into an `OwnedFrame` of depth in
+    /// millimeters, whose video mode is `PixelFormat::DEPTH_1_MM`.
+    pub fn convert(&self, frame: &Frame<ShiftPixel>) -> OwnedFrame<OniDepthPixel> {
+        OwnedFrame {
+            pixels: frame.pixels().iter().map(|&shift| self.depth(shift)).collect(),
+            width: frame.width(),
+            height: frame.height(),
+            timestamp: frame.timestamp(),
+            index: frame.index(),
+            video_mode: depth_video_mode(frame.video_mode()),
+            cropping: frame.cropping(),
+        }
+    }
+
+    /// Like `ShiftToDepth::convert`, for frames that have already been
+    /// copied or read from a recording.
+    pub fn convert_owned(&self, frame: &OwnedFrame<ShiftPixel>) -> OwnedFrame<OniDepthPixel> {
+        OwnedFrame {
+            pixels: frame.pixels.iter().map(|&shift| self.depth(shift)).collect(),
+            width: frame.width,
+            height: frame.height,
+            timestamp: frame.timestamp,
+            index: frame.index,
+            video_mode: depth_video_mode(frame.video_mode),
+            cropping: frame.cropping,
+        }
+    }
+}
+
+fn depth_video_mode(video_mode: VideoMode) -> VideoMode {
+    VideoMode {
+        pixel_format: PixelFormat::DEPTH_1_MM,
+        ..video_mode
+    }
+}
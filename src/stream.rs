@@ -1,10 +1,14 @@
 use std::marker::PhantomData;
 use std::os::raw::{c_int, c_float, c_void};
 use std::{ptr, fmt, mem, slice};
+use std::ops::Deref;
+use crossbeam_channel::{self as channel, Receiver, TrySendError};
 
 use openni2_sys::*;
-use device::Device;
-use frame::{Frame, frame_from_pointer};
+use device::{Device, DeviceHandle, PropertyData, as_bytes, backend_get};
+use backend::StreamBackend;
+use cookie::{Cookie, CookiePool};
+use frame::{Frame, DynFrame, OwnedFrame, frame_from_pointer, dyn_frame_from_pointer, frame_from_data, dyn_frame_from_data};
 use types::{Status, SensorType, VideoMode, SensorInfo, Pixel};
 
 /// A video stream that pulls frame from a single sensor on a `Device`.
@@ -34,31 +38,61 @@ use types::{Status, SensorType, VideoMode, SensorInfo, Pixel};
 /// "stopped" state, set with `Stream::stop()`. You don't need to worry
 /// about manually stopping a stream before it falls out of scope.
 pub struct Stream<'device> {
-    stream_handle: OniStreamHandle,
+    stream_handle: StreamHandle,
     sensor_type: SensorType,
+    // Cookies of dropped listeners, freed once the stream is destroyed
+    listener_cookies: CookiePool<dyn FnMut()>,
     _pixel_type: PhantomData<&'device ()>,
 }
 
+// A stream is either created through OpenNI2 or by a `DeviceBackend`.
+enum StreamHandle {
+    OpenNI(OniStreamHandle),
+    Backend(Box<dyn StreamBackend>),
+}
+
 impl<'device> Stream<'device> {
     #[doc(hidden)]
     pub fn create(device: &'device Device, sensor_type: SensorType) -> Result<Self, Status> {
+        let device_handle = match device.handle {
+            DeviceHandle::OpenNI(handle) => handle,
+            DeviceHandle::Backend(ref backend) => return Ok(Stream {
+                stream_handle: StreamHandle::Backend(backend.create_stream(sensor_type)?),
+                sensor_type,
+                listener_cookies: CookiePool::new(),
+                _pixel_type: PhantomData,
+            }),
+        };
         let mut stream_handle: OniStreamHandle = ptr::null_mut();
         let status = unsafe {
-            oniDeviceCreateStream(device.handle, sensor_type as i32, &mut stream_handle)
+            oniDeviceCreateStream(device_handle, sensor_type as i32, &mut stream_handle)
         }.into();
         match status {
             Status::Ok => Ok(Stream {
-                stream_handle,
+                stream_handle: StreamHandle::OpenNI(stream_handle),
                 sensor_type,
+                listener_cookies: CookiePool::new(),
                 _pixel_type: PhantomData,
             }),
             _ => Err(status)
         }
     }
 
+    /// The stream's OpenNI2 handle, which is null for a stream of a
+    /// `DeviceBackend`.
     #[doc(hidden)]
     pub fn handle(&self) -> OniStreamHandle {
-        self.stream_handle
+        match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(_) => ptr::null_mut(),
+        }
+    }
+
+    pub(crate) fn backend(&self) -> Option<&dyn StreamBackend> {
+        match self.stream_handle {
+            StreamHandle::OpenNI(_) => None,
+            StreamHandle::Backend(ref backend) => Some(&**backend),
+        }
     }
 
     /// The `SensorType` that the stream is pulling frames from (color,
@@ -70,7 +104,11 @@ impl<'device> Stream<'device> {
     /// Starts the stream. If successful, the stream can then read
     /// frames from the device. Stop the stream with `Stream::stop`.
     pub fn start(&self) -> Result<(), Status> {
-        let res = unsafe { oniStreamStart(self.stream_handle) }.into();
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.start(),
+        };
+        let res = unsafe { oniStreamStart(handle) }.into();
         match res {
             Status::Ok => Ok(()),
             _ => Err(res),
@@ -79,12 +117,17 @@ impl<'device> Stream<'device> {
 
     /// Stops the stream. It can be restarted with `Stream::start` at any time.
     pub fn stop(&self) {
-        unsafe { oniStreamStop(self.stream_handle) };
+        match self.stream_handle {
+            StreamHandle::OpenNI(handle) => unsafe { oniStreamStop(handle) },
+            StreamHandle::Backend(ref backend) => backend.stop(),
+        }
     }
 
     pub fn is_property_supported(&self, property: OniStreamProperty) -> bool {
-        let res = unsafe { oniStreamIsPropertySupported(self.stream_handle, property) };
-        res == 1
+        match self.stream_handle {
+            StreamHandle::OpenNI(handle) => unsafe { oniStreamIsPropertySupported(handle, property) == 1 },
+            StreamHandle::Backend(ref backend) => backend.is_property_supported(property),
+        }
     }
 
     /// Return the stream's current `Cropping` which represents the
@@ -113,10 +156,13 @@ impl<'device> Stream<'device> {
     /// crop's width, height, and origin.
     ///
     /// # Example
-    /// ```no_run
+    /// ```
     /// # use openni2::{Device, SensorType, Cropping, OniRGB888Pixel};
     /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
     /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
     /// let stream = device.create_stream(SensorType::COLOR)?;
     /// let crop = Cropping {
     ///     width: 100,
@@ -256,13 +302,17 @@ impl<'device> Stream<'device> {
         self.set_property::<c_int>(ONI_STREAM_PROPERTY_GAIN, &value)
     }
 
-    fn get_property<T>(&self, property: OniStreamProperty) -> Result<T, Status> {
+    pub(crate) fn get_property<T: PropertyData>(&self, property: OniStreamProperty) -> Result<T, Status> {
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend_get(|data| backend.get_property(property, data)),
+        };
         let mut data = mem::MaybeUninit::<T>::uninit();
         let mut len = mem::size_of::<T>() as c_int;
 
         let status = unsafe {
             oniStreamGetProperty(
-                self.stream_handle,
+                handle,
                 property,
                 data.as_mut_ptr() as *mut c_void,
                 &mut len as *mut c_int,
@@ -275,11 +325,15 @@ impl<'device> Stream<'device> {
         }
     }
 
-    fn set_property<T>(&self, property: OniStreamProperty, value: &T) -> Result<(), Status> {
+    fn set_property<T: PropertyData>(&self, property: OniStreamProperty, value: &T) -> Result<(), Status> {
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.set_property(property, as_bytes(value)),
+        };
         let len = mem::size_of::<T>() as c_int;
         let status = unsafe {
             oniStreamSetProperty(
-                self.stream_handle,
+                handle,
                 property,
                 value as *const T as *const c_void,
                 len,
@@ -314,8 +368,12 @@ impl<'device> Stream<'device> {
     /// # }
     /// ```
     pub fn sensor_info(&self) -> Result<SensorInfo, Status> {
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.sensor_info().ok_or(Status::OutOfFlow),
+        };
         unsafe {
-            let ptr: *const OniSensorInfo = oniStreamGetSensorInfo(self.stream_handle);
+            let ptr: *const OniSensorInfo = oniStreamGetSensorInfo(handle);
             if ptr.is_null() {
                 Err(Status::OutOfFlow)
             } else {
@@ -355,7 +413,8 @@ impl<'device> Stream<'device> {
     /// this method with the correct pixel type that the stream is returning
     /// (which can be changed at runtime with `Stream::set_video_mode`).
     /// `Stream::read_frame` will panic if the byte sizes reported by the
-    /// `Frame` and type parameter `P: Pixel` do not match.
+    /// `Frame` and type parameter `P: Pixel` do not match. Use
+    /// `Stream::read_frame_any` if the pixel format can't be known ahead of time.
     /// ```no_run
     /// # use openni2::{Device, SensorType, OniDepthPixel};
     /// # fn main() -> Result<(), openni2::Status> {
@@ -368,14 +427,36 @@ impl<'device> Stream<'device> {
     /// # }
     /// ```
     pub fn read_frame<P: Pixel>(&self) -> Result<Frame<P>, Status> {
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.read_frame().map(frame_from_data),
+        };
         let mut pointer = ptr::null_mut();
-        let status = unsafe { oniStreamReadFrame(self.stream_handle, &mut pointer) }.into();
+        let status = unsafe { oniStreamReadFrame(handle, &mut pointer) }.into();
         match status {
             Status::Ok => unsafe { Ok(frame_from_pointer(pointer)) },
             _ => Err(status),
         }
     }
 
+    /// Reads the next frame from the stream without committing to a pixel
+    /// type ahead of time. The returned `DynFrame` is chosen by the frame's
+    /// own `PixelFormat`, so this won't panic if the stream's `VideoMode`
+    /// was changed since the last read. Like `Stream::read_frame`, this
+    /// will block until a frame is ready.
+    pub fn read_frame_any(&self) -> Result<DynFrame, Status> {
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.read_frame().map(dyn_frame_from_data),
+        };
+        let mut pointer = ptr::null_mut();
+        let status = unsafe { oniStreamReadFrame(handle, &mut pointer) }.into();
+        match status {
+            Status::Ok => unsafe { Ok(dyn_frame_from_pointer(pointer)) },
+            _ => Err(status),
+        }
+    }
+
     // pub fn is_command_supported(&self, command: OniStreamCommand) -> bool {
     //     let res = unsafe { oniStreamIsCommandSupported(self.stream_handle, command) }
     //     res == 1
@@ -383,8 +464,12 @@ impl<'device> Stream<'device> {
 
     pub fn depth_to_world(&self, depth: (f32, f32, f32)) -> Result<(f32, f32, f32), Status> {
         // TODO: assert this is a depth stream
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.depth_to_world(depth),
+        };
         let mut result = (0.0, 0.0, 0.0);
-        let status = unsafe { oniCoordinateConverterDepthToWorld(self.stream_handle, depth.0, depth.1, depth.2, &mut result.0, &mut result.1, &mut result.2) }.into();
+        let status = unsafe { oniCoordinateConverterDepthToWorld(handle, depth.0, depth.1, depth.2, &mut result.0, &mut result.1, &mut result.2) }.into();
         if let Status::Ok = status {
             Ok(result)
         } else {
@@ -394,8 +479,12 @@ impl<'device> Stream<'device> {
 
     pub fn world_to_depth(&self, world: (f32, f32, f32)) -> Result<(f32, f32, f32), Status> {
         // TODO: assert this is a depth stream
+        let handle = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => handle,
+            StreamHandle::Backend(ref backend) => return backend.world_to_depth(world),
+        };
         let mut result = (0.0, 0.0, 0.0);
-        let status = unsafe { oniCoordinateConverterWorldToDepth(self.stream_handle, world.0, world.1, world.2, &mut result.0, &mut result.1, &mut result.2) }.into();
+        let status = unsafe { oniCoordinateConverterWorldToDepth(handle, world.0, world.1, world.2, &mut result.0, &mut result.1, &mut result.2) }.into();
         if let Status::Ok = status {
             Ok(result)
         } else {
@@ -403,7 +492,152 @@ impl<'device> Stream<'device> {
         }
     }
 
-    // todo: depth to color (requires 2 streams)
+    /// Converts a depth pixel coordinate and depth value from this depth
+    /// stream into the pixel coordinates of `color`, a color stream of the
+    /// same device. This lets a color be sampled for a depth pixel without
+    /// turning on `Device::set_image_registration`.
+    ///
+    /// # Example
+    /// ```
+    /// # use openni2::{Device, SensorType, OniDepthPixel, OniRGB888Pixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
+    /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let depth = device.create_stream(SensorType::DEPTH)?;
+    /// let color = device.create_stream(SensorType::COLOR)?;
+    /// depth.start()?;
+    /// color.start()?;
+    /// let depth_frame = depth.read_frame::<OniDepthPixel>()?;
+    /// let z = depth_frame.pixels()[240 * 640 + 320];
+    /// let (x, y) = depth.depth_to_color(&color, (320, 240, z))?;
+    /// println!("Depth pixel (320, 240) is color pixel ({}, {})", x, y);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn depth_to_color(&self, color: &Stream, depth: (i32, i32, OniDepthPixel)) -> Result<(i32, i32), Status> {
+        let (handle, color_handle) = match (&self.stream_handle, &color.stream_handle) {
+            (&StreamHandle::OpenNI(handle), &StreamHandle::OpenNI(color_handle)) => (handle, color_handle),
+            (StreamHandle::Backend(backend), StreamHandle::Backend(color)) => return backend.depth_to_color(&**color, depth),
+            _ => return Err(Status::NotSupported),
+        };
+        let mut result = (0, 0);
+        let status = unsafe { oniCoordinateConverterDepthToColor(handle, color_handle, depth.0, depth.1, depth.2, &mut result.0, &mut result.1) }.into();
+        if let Status::Ok = status {
+            Ok(result)
+        } else {
+            Err(status)
+        }
+    }
+
+    /// Converts every pixel of a frame read from this depth stream into the
+    /// pixel coordinates of `color`, a color stream of the same device. See
+    /// `Stream::depth_to_color`.
+    ///
+    /// The result has one entry per depth pixel, in row order. Pixels with no
+    /// depth reading, or that fall outside the color frame, are `None`.
+    pub fn depth_frame_to_color(&self, color: &Stream, frame: &Frame<OniDepthPixel>) -> Result<Vec<Option<(u16, u16)>>, Status> {
+        let color_mode = color.get_video_mode()?;
+        let width = frame.width() as usize;
+        let (origin_x, origin_y) = (i32::from(frame.origin_x()), i32::from(frame.origin_y()));
+        frame.pixels()
+            .iter()
+            .enumerate()
+            .map(|(i, &z)| {
+                if z == 0 {
+                    return Ok(None);
+                }
+                let x = (i % width) as i32 + origin_x;
+                let y = (i / width) as i32 + origin_y;
+                let (color_x, color_y) = self.depth_to_color(color, (x, y, z))?;
+                if color_x < 0 || color_y < 0 || color_x >= color_mode.resolution_x || color_y >= color_mode.resolution_y {
+                    Ok(None)
+                } else {
+                    Ok(Some((color_x as u16, color_y as u16)))
+                }
+            })
+            .collect()
+    }
+
+    /// Returns an iterator that blocks for each new frame, like calling
+    /// `Stream::read_frame` in a loop. The iterator never ends on its own.
+    ///
+    /// # Panics
+    /// The iterator panics under the same conditions as `Stream::read_frame`.
+    ///
+    /// # Example
+    /// ```
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
+    /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// stream.start()?;
+    /// for frame in stream.frames::<OniDepthPixel>().take(30) {
+    ///     println!("{}", frame?.timestamp());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn frames<P: Pixel>(&self) -> Frames<'_, 'device, P> {
+        Frames {
+            stream: self,
+            _pixel_type: PhantomData,
+        }
+    }
+
+    /// Copies every new frame into a channel that can be read from any
+    /// thread. The channel holds at most `capacity` frames; when it is full,
+    /// the oldest frame is dropped to make room for the newest, so a slow
+    /// consumer always sees recent frames.
+    ///
+    /// Frames whose pixel format `P` doesn't support are skipped. Frames stop
+    /// arriving when the `Subscription` is dropped, after which receivers
+    /// cloned from it disconnect once they have drained the channel.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::thread;
+    /// # use openni2::{Device, SensorType, OniDepthPixel};
+    /// # fn main() -> Result<(), openni2::Status> {
+    /// # /*
+    /// let device = Device::open_default()?;
+    /// # */
+    /// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+    /// let stream = device.create_stream(SensorType::DEPTH)?;
+    /// let subscription = stream.subscribe::<OniDepthPixel>(2)?;
+    /// stream.start()?;
+    /// let receiver = subscription.receiver().clone();
+    /// let worker = thread::spawn(move || {
+    ///     for frame in receiver.iter().take(30) {
+    ///         println!("{}", frame.timestamp);
+    ///     }
+    /// });
+    /// worker.join().unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe<P: Pixel + Send + 'static>(&self, capacity: usize) -> Result<Subscription<'_, P>, Status> {
+        let (sender, receiver) = channel::bounded(capacity.max(1));
+        let oldest = receiver.clone();
+        let listener = self.listener(move |stream| {
+            let mut frame = match stream.read_frame_any().map(|frame| frame.downcast::<P>()) {
+                Ok(Ok(frame)) => frame.to_owned(),
+                _ => return,
+            };
+            while let Err(TrySendError::Full(rejected)) = sender.try_send(frame) {
+                let _ = oldest.try_recv();
+                frame = rejected;
+            }
+        })?;
+        Ok(Subscription {
+            receiver,
+            _listener: listener,
+        })
+    }
 
     /// Register a callback to execute when the stream has a frame immediately
     /// available.
@@ -412,8 +646,13 @@ impl<'device> Stream<'device> {
     /// itself. The callback can call `Stream::read_frame` on the stream,
     /// and it should not block.
     ///
+    /// The callback runs on a thread owned by OpenNI2, so it has to be `Send`,
+    /// and it can't borrow from the caller since it may outlive the current
+    /// scope. If the callback panics, the panic is caught before it reaches
+    /// OpenNI2 and the frame is skipped.
+    ///
     /// This method returns a `StreamListener` handle, which unregisters
-    /// the callback when it falls out of scope.
+    /// the callback and frees it when it falls out of scope.
     ///
     /// # Example
     /// ```no_run
@@ -430,13 +669,14 @@ impl<'device> Stream<'device> {
     ///     let stream = device.create_stream(SensorType::DEPTH)?;
     ///     let display = SomeHypotheticalDisplay::new(); // pretend it's Minifb
     ///
-    ///     let callback = |stream: &Stream| {
+    ///     // The callback takes ownership of the display
+    ///     let callback = move |stream: &Stream| {
     ///         let frame = stream.read_frame::<OniDepthPixel>().unwrap();
     ///         display.update_from_buffer(&frame.pixels());
     ///     };
     ///
     ///     // Keep the callback registered until this falls out of scope
-    ///     let _listener = stream.listener(&callback)?;
+    ///     let _listener = stream.listener(callback)?;
     ///
     ///     let one_second = time::Duration::from_millis(1000);
     ///     loop {
@@ -445,36 +685,64 @@ impl<'device> Stream<'device> {
     ///     Ok(())
     /// # }
     /// ```
-    pub fn listener<F: FnMut(&Stream)>(&self, mut callback: F) -> Result<StreamListener, Status> {
+    pub fn listener<F: FnMut(&Stream) + Send + 'static>(&self, callback: F) -> Result<StreamListener<'_>, Status> {
+        self.register_listener(callback)
+    }
+
+    // Registers any closure that can live as long as the stream. Callers
+    // must make sure that whatever the closure captures can be used from
+    // OpenNI2's thread.
+    pub(crate) fn register_listener<'stream, F: FnMut(&Stream) + 'stream>(&'stream self, mut callback: F) -> Result<StreamListener<'stream>, Status> {
         // Yowzers https://stackoverflow.com/questions/32270030/how-do-i-convert-a-rust-closure-to-a-c-style-callback
         let mut callback_handle: OniCallbackHandle = ptr::null_mut();
 
         extern "C" fn callback_wrapper(_: OniStreamHandle, cookie: *mut c_void) {
-            // cookie, here, is a pointer to a Box<dyn FnMut()>
-            let closure: &mut dyn FnMut() = unsafe { &mut *(cookie as *mut Box<dyn FnMut()>) };
-            closure();
+            // cookie, here, is a pointer to the listener's Cookie
+            let cookie = unsafe { &*(cookie as *const Cookie<dyn FnMut()>) };
+            cookie.call(|closure| closure());
         }
 
-        let closure: Box<Box<dyn FnMut()>> = Box::new(Box::new(move || {
-            callback(&self);
-        }));
-
-        let status = unsafe {
-            oniStreamRegisterNewFrameCallback(
-                self.stream_handle,
-                Some(callback_wrapper),
-                Box::into_raw(closure) as *mut _,
-                &mut callback_handle,
-            )
-        }.into();
+        let closure: Box<dyn FnMut() + 'stream> = Box::new(move || {
+            callback(self);
+        });
+        // The listener empties the cookie when it's dropped, before 'stream
+        // ends.
+        let closure: Box<dyn FnMut()> = unsafe { mem::transmute(closure) };
+        let cookie = self.listener_cookies.cookie(closure);
+        let cookie_ptr = cookie as *mut c_void;
+
+        let status = match self.stream_handle {
+            StreamHandle::OpenNI(handle) => unsafe {
+                oniStreamRegisterNewFrameCallback(
+                    handle,
+                    Some(callback_wrapper),
+                    cookie_ptr,
+                    &mut callback_handle,
+                )
+            }.into(),
+            StreamHandle::Backend(ref backend) => {
+                let wrapper_cookie = SendCookie(cookie_ptr);
+                let wrapper = Box::new(move || callback_wrapper(ptr::null_mut(), wrapper_cookie.0));
+                match backend.register_new_frame_callback(wrapper) {
+                    Ok(id) => {
+                        return Ok(StreamListener {
+                            stream: self,
+                            callback_handle: CallbackHandle::Backend(id),
+                            cookie,
+                        });
+                    },
+                    Err(status) => status,
+                }
+            },
+        };
         if let Status::Ok = status {
             Ok(StreamListener {
-                stream_handle: &self.stream_handle,
-                callback_handle,
-                // closure_ptr,
-                // _pixel_type: PhantomData
+                stream: self,
+                callback_handle: CallbackHandle::OpenNI(callback_handle),
+                cookie,
             })
         } else {
+            self.listener_cookies.retire(cookie);
             Err(status)
         }
     }
@@ -482,19 +750,27 @@ impl<'device> Stream<'device> {
 
 impl<'device> fmt::Debug for Stream<'device> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Stream {{ stream_handle: {:p} }}", &self.stream_handle)
+        match self.stream_handle {
+            StreamHandle::OpenNI(ref handle) => write!(f, "Stream {{ stream_handle: {:p} }}", handle),
+            StreamHandle::Backend(_) => write!(f, "Stream {{ stream_handle: StreamBackend, sensor_type: {:?} }}", self.sensor_type),
+        }
     }
 }
 
 impl<'device> Drop for Stream<'device> {
     fn drop(&mut self) {
         self.stop();
-        unsafe { oniStreamDestroy(self.stream_handle) };
+        if let StreamHandle::OpenNI(handle) = self.stream_handle {
+            unsafe { oniStreamDestroy(handle) };
+        }
+        // Destroyed streams raise no more events
+        unsafe { self.listener_cookies.free() };
     }
 }
 
 /// Dimensions to crop a `Stream` to. See `Stream::set_cropping`
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Cropping {
     pub width: u16,
     pub height: u16,
@@ -502,23 +778,77 @@ pub struct Cropping {
     pub origin_y: u16,
 }
 
+/// A blocking iterator over a `Stream`'s frames. See `Stream::frames`.
+pub struct Frames<'stream, 'device: 'stream, P: Pixel> {
+    stream: &'stream Stream<'device>,
+    _pixel_type: PhantomData<P>,
+}
+
+impl<'stream, 'device, P: Pixel> Iterator for Frames<'stream, 'device, P> {
+    type Item = Result<Frame<P>, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.stream.read_frame())
+    }
+}
+
+/// A channel of frames copied from a `Stream`. See `Stream::subscribe`.
+///
+/// A `Subscription` dereferences to its `Receiver`. The subscription itself
+/// has to stay on the thread that owns the `Stream`, but clones of its
+/// `Receiver` can be sent to other threads.
+pub struct Subscription<'stream, P: Pixel> {
+    receiver: Receiver<OwnedFrame<P>>,
+    _listener: StreamListener<'stream>,
+}
+
+impl<'stream, P: Pixel> Subscription<'stream, P> {
+    /// The receiving end of the channel.
+    pub fn receiver(&self) -> &Receiver<OwnedFrame<P>> {
+        &self.receiver
+    }
+}
+
+impl<'stream, P: Pixel> Deref for Subscription<'stream, P> {
+    type Target = Receiver<OwnedFrame<P>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
 /// Unregisters a `Stream`'s "new frame" callback when it falls
-/// out of scope.
+/// out of scope, then frees the callback once it has stopped running.
+/// Dropped from inside its own callback, it frees the callback once the
+/// callback returns.
 pub struct StreamListener<'stream> {
-    stream_handle: &'stream OniStreamHandle,
-    callback_handle: OniCallbackHandle,
-    // closure_ptr: *mut c_void,
-    // _pixel_type: PhantomData<P>,
+    stream: &'stream Stream<'stream>,
+    callback_handle: CallbackHandle,
+    cookie: *mut Cookie<dyn FnMut()>,
 }
 
+enum CallbackHandle {
+    OpenNI(OniCallbackHandle),
+    Backend(usize),
+}
+
+// Lets a backend's callback carry the listener's cookie pointer to
+// whichever thread the backend raises events on, like OpenNI2's cookie.
+struct SendCookie(*mut c_void);
+
+unsafe impl Send for SendCookie {}
+
 impl<'stream> Drop for StreamListener<'stream> {
     fn drop(&mut self) {
-        unsafe {
-            oniStreamUnregisterNewFrameCallback(
-                *self.stream_handle,
-                self.callback_handle,
-            );
+        match (&self.stream.stream_handle, &self.callback_handle) {
+            (&StreamHandle::OpenNI(handle), &CallbackHandle::OpenNI(callback_handle)) => unsafe {
+                oniStreamUnregisterNewFrameCallback(handle, callback_handle);
+            },
+            (StreamHandle::Backend(backend), &CallbackHandle::Backend(id)) => {
+                backend.unregister_new_frame_callback(id);
+            },
+            _ => unreachable!(),
         }
-        // let _: Box<Box<FnMut()>> = unsafe { Box::from_raw(self.closure_ptr as *mut _) };
+        self.stream.listener_cookies.retire(self.cookie);
     }
 }
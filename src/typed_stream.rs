@@ -0,0 +1,152 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::fmt;
+
+use openni2_sys::*;
+use frame::Frame;
+use stream::{Stream, StreamListener};
+use types::{Status, PixelFormat, VideoMode, Pixel, DepthMmPixel, Depth100UmPixel, Gray16Pixel, ShiftPixel};
+
+/// Marker trait tying a `PixelFormat` to the `Pixel` type that frames of
+/// that format contain. Used to parameterize a `TypedStream`.
+pub trait StreamFormat {
+    /// The pixel type of frames read from a stream of this format.
+    type Pixel: Pixel;
+    /// The pixel format a stream must be set to.
+    const PIXEL_FORMAT: PixelFormat;
+}
+
+macro_rules! streamFormat {
+    ($($(#[$attr:meta])* $name:ident => ($pixel:ty, $format:ident)),+) => (
+        $(
+            $(#[$attr])*
+            #[derive(Debug, Copy, Clone)]
+            pub enum $name {}
+
+            impl StreamFormat for $name {
+                type Pixel = $pixel;
+                const PIXEL_FORMAT: PixelFormat = PixelFormat::$format;
+            }
+        )+
+    )
+}
+
+streamFormat!(
+    /// Depth in millimeters (`PixelFormat::DEPTH_1_MM`)
+    DepthMm => (DepthMmPixel, DEPTH_1_MM),
+    /// Depth in 100 micrometer units (`PixelFormat::DEPTH_100_UM`)
+    Depth100Um => (Depth100UmPixel, DEPTH_100_UM),
+    /// Packed 24-bit color (`PixelFormat::RGB888`)
+    Rgb888 => (OniRGB888Pixel, RGB888),
+    /// Pairs of pixels in UYVY order (`PixelFormat::YUV422`)
+    Yuv422 => (OniYUV422DoublePixel, YUV422),
+    /// Pairs of pixels in YUYV order (`PixelFormat::YUYV`)
+    Yuyv => (OniYUV422DoublePixel, YUYV),
+    /// 8-bit grayscale (`PixelFormat::GRAY8`)
+    Gray8 => (OniGrayscale8Pixel, GRAY8),
+    /// 16-bit grayscale, usually from an IR sensor (`PixelFormat::GRAY16`)
+    Gray16 => (Gray16Pixel, GRAY16),
+    /// Raw PrimeSense disparity (`PixelFormat::SHIFT_9_2`)
+    Shift92 => (ShiftPixel, SHIFT_9_2),
+    /// Raw PrimeSense disparity (`PixelFormat::SHIFT_9_3`)
+    Shift93 => (ShiftPixel, SHIFT_9_3)
+);
+
+/// A `Stream` whose pixel format is fixed by the type parameter `F`, so that
+/// reading frames doesn't need a type annotation that could be wrong.
+///
+/// A `TypedStream` dereferences to its `Stream`, so every property getter
+/// and setter is still available. Its own `TypedStream::set_video_mode`
+/// refuses video modes with a different pixel format than `F`.
+///
+/// # Example
+/// ```
+/// # use openni2::{Device, SensorType, DepthMm};
+/// # fn main() -> Result<(), openni2::Status> {
+/// # /*
+/// let device = Device::open_default()?;
+/// # */
+/// # let device = Device::from_backend(openni2::mock::MockDevice::new());
+/// let stream = device.create_typed_stream::<DepthMm>(SensorType::DEPTH)?;
+/// stream.start()?;
+/// let frame = stream.read_frame()?;
+/// println!("{:?}", frame.pixels()[0]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedStream<'device, F: StreamFormat> {
+    stream: Stream<'device>,
+    _format: PhantomData<F>,
+}
+
+impl<'device, F: StreamFormat> TypedStream<'device, F> {
+    /// Wraps a `Stream`, switching it to a video mode of pixel format `F` if
+    /// it isn't already using one. A supported video mode with the stream's
+    /// current resolution and frame rate is preferred.
+    ///
+    /// Returns `Err(Status::NotSupported)` if the stream's sensor doesn't
+    /// support pixel format `F` at all.
+    pub fn from_stream(stream: Stream<'device>) -> Result<Self, Status> {
+        let current = stream.get_video_mode()?;
+        if current.pixel_format != F::PIXEL_FORMAT {
+            let video_modes = stream.sensor_info()?.video_modes;
+            let compatible = video_modes.iter().filter(|mode| mode.pixel_format == F::PIXEL_FORMAT);
+            let same_dimensions = compatible.clone().find(|mode| {
+                mode.resolution_x == current.resolution_x &&
+                mode.resolution_y == current.resolution_y &&
+                mode.fps == current.fps
+            });
+            match same_dimensions.or_else(|| compatible.clone().next()) {
+                Some(&video_mode) => stream.set_video_mode(video_mode)?,
+                None => return Err(Status::NotSupported),
+            }
+        }
+        Ok(TypedStream {
+            stream,
+            _format: PhantomData,
+        })
+    }
+
+    /// Unwraps the underlying `Stream`.
+    pub fn into_inner(self) -> Stream<'device> {
+        self.stream
+    }
+
+    /// Sets the stream to a specific `VideoMode`. Fails with
+    /// `Err(Status::BadParameter)` if the video mode's pixel format isn't
+    /// the one this stream is typed with. See `Stream::set_video_mode`.
+    pub fn set_video_mode(&self, value: VideoMode) -> Result<(), Status> {
+        if value.pixel_format != F::PIXEL_FORMAT {
+            return Err(Status::BadParameter);
+        }
+        self.stream.set_video_mode(value)
+    }
+
+    /// Reads the next `Frame` from the stream, blocking until it is ready.
+    /// See `Stream::read_frame`.
+    pub fn read_frame(&self) -> Result<Frame<F::Pixel>, Status> {
+        self.stream.read_frame::<F::Pixel>()
+    }
+
+    /// Register a callback to execute when the stream has a frame immediately
+    /// available. The callback receives the `TypedStream` itself, so it can
+    /// call `TypedStream::read_frame` without a type annotation. See
+    /// `Stream::listener`.
+    pub fn listener<C: FnMut(&TypedStream<'device, F>) + Send + 'static>(&self, mut callback: C) -> Result<StreamListener<'_>, Status> {
+        self.stream.register_listener(move |_| callback(self))
+    }
+}
+
+impl<'device, F: StreamFormat> Deref for TypedStream<'device, F> {
+    type Target = Stream<'device>;
+
+    fn deref(&self) -> &Stream<'device> {
+        &self.stream
+    }
+}
+
+impl<'device, F: StreamFormat> fmt::Debug for TypedStream<'device, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TypedStream<{:?}> {{ stream: {:?} }}", F::PIXEL_FORMAT, self.stream)
+    }
+}
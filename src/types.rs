@@ -1,5 +1,6 @@
 use std::os::raw::c_int;
-use std::fmt;
+use std::time::Duration;
+use std::{cmp, fmt};
 use openni2_sys::*;
 use super::get_extended_error;
 
@@ -62,7 +63,7 @@ impl From<c_int> for Status {
 
 /// One of the supported sensor types of a device
 #[allow(non_camel_case_types)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i32)]
 pub enum SensorType {
     IR = ONI_SENSOR_IR,
@@ -89,7 +90,8 @@ impl From<c_int> for SensorType {
 
 /// One of the pixel formats that a `Stream` can use
 #[allow(non_camel_case_types)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(i32)]
 pub enum PixelFormat {
     // Depth
@@ -148,6 +150,16 @@ pub fn bytes_per_pixel(format: PixelFormat) -> usize {
     }
 }
 
+// Number of `Pixel`s in a frame of `width` by `height` coordinates. 4:2:2
+// formats pack two coordinates into each of their `bytes_per_pixel` pixels.
+#[doc(hidden)]
+pub fn pixels_per_frame(format: PixelFormat, width: usize, height: usize) -> usize {
+    match format {
+        PixelFormat::YUV422 | PixelFormat::YUYV => width * height / 2,
+        _ => width * height,
+    }
+}
+
 /// Current state of a device.
 #[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone)]
@@ -204,12 +216,21 @@ impl From<c_int> for ImageRegistrationMode {
     }
 }
 
+/// How long to block while waiting for a stream to have a frame ready.
+/// See `openni2::wait_for_any_stream`.
+///
+/// Since `MILLISECONDS` was added, `Timeout` is no longer `#[repr(i32)]`, so
+/// `timeout as i32` doesn't compile. Use `Timeout::as_int` for the value
+/// that OpenNI2 takes instead.
 #[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone)]
-#[repr(i32)]
 pub enum Timeout {
-    NONE = ONI_TIMEOUT_NONE,
-    FOREVER = ONI_TIMEOUT_FOREVER,
+    /// Return immediately
+    NONE,
+    /// Block until a frame is ready
+    FOREVER,
+    /// Block for at most this many milliseconds
+    MILLISECONDS(u32),
 }
 
 impl Timeout {
@@ -217,9 +238,20 @@ impl Timeout {
         match value {
             ONI_TIMEOUT_NONE => Timeout::NONE,
             ONI_TIMEOUT_FOREVER => Timeout::FOREVER,
+            ms if ms > 0 => Timeout::MILLISECONDS(ms as u32),
             _ => panic!("Unknown timeout {}", value),
         }
     }
+
+    /// The timeout in OpenNI2's representation: `ONI_TIMEOUT_NONE`,
+    /// `ONI_TIMEOUT_FOREVER`, or a number of milliseconds.
+    pub fn as_int(self) -> c_int {
+        match self {
+            Timeout::NONE => ONI_TIMEOUT_NONE,
+            Timeout::FOREVER => ONI_TIMEOUT_FOREVER,
+            Timeout::MILLISECONDS(ms) => cmp::min(ms, c_int::MAX as u32) as c_int,
+        }
+    }
 }
 
 impl From<c_int> for Timeout {
@@ -228,11 +260,21 @@ impl From<c_int> for Timeout {
     }
 }
 
+impl From<Duration> for Timeout {
+    fn from(duration: Duration) -> Self {
+        let ms = duration.as_secs()
+            .saturating_mul(1000)
+            .saturating_add(u64::from(duration.subsec_millis()));
+        Timeout::MILLISECONDS(cmp::min(ms, u64::from(u32::MAX)) as u32)
+    }
+}
+
 /// Dimensions, pixel format, and framerate of a stream.
 ///
 /// Returned as current video mode of a stream, or passed as
 /// the desired video mode when updating a stream.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct VideoMode {
     pub pixel_format: PixelFormat,
     pub resolution_x: c_int,
@@ -272,14 +314,91 @@ pub struct SensorInfo {
 }
 
 macro_rules! isPixel {
-    ($($in:ty),+) => (
+    ($($in:ty => [$($format:ident),+]),+) => (
         /// Common trait for structs/primitives that can be returned
         /// as pixel data in a `Frame`
-        pub trait Pixel: Copy + fmt::Debug {}
-        $(impl Pixel for $in {})+
+        ///
+        /// # Safety
+        /// Implementors must be plain old data: no padding bytes, and any
+        /// bit pattern of `size_of::<Self>()` bytes must be a valid value,
+        /// since pixels are read directly out of OpenNI2's frame buffers.
+        pub unsafe trait Pixel: Copy + fmt::Debug {
+            /// Returns true if frames of pixel format `format` can be read
+            /// as pixels of this type.
+            fn supports_format(format: PixelFormat) -> bool;
+        }
+        $(
+            unsafe impl Pixel for $in {
+                fn supports_format(format: PixelFormat) -> bool {
+                    match format {
+                        $(PixelFormat::$format)|+ => true,
+                        _ => false,
+                    }
+                }
+            }
+        )+
+    )
+}
+
+macro_rules! pixelNewtype {
+    ($($(#[$attr:meta])* $name:ident),+) => (
+        $(
+            $(#[$attr])*
+            #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #[repr(transparent)]
+            pub struct $name(pub u16);
+
+            impl From<u16> for $name {
+                fn from(value: u16) -> Self {
+                    $name(value)
+                }
+            }
+
+            impl From<$name> for u16 {
+                fn from(pixel: $name) -> u16 {
+                    pixel.0
+                }
+            }
+        )+
     )
 }
-isPixel!(OniDepthPixel, /*OniGrayscale16Pixel,*/ OniGrayscale8Pixel, OniRGB888Pixel, OniYUV422DoublePixel);
+
+pixelNewtype!(
+    /// A depth pixel in millimeters (`PixelFormat::DEPTH_1_MM`). 0 means
+    /// there is no depth reading for the pixel.
+    DepthMmPixel,
+    /// A depth pixel in 100 micrometer units (`PixelFormat::DEPTH_100_UM`).
+    /// 0 means there is no depth reading for the pixel.
+    Depth100UmPixel,
+    /// A 16-bit grayscale pixel (`PixelFormat::GRAY16`), as produced by IR
+    /// sensors.
+    Gray16Pixel,
+    /// A raw disparity pixel (`PixelFormat::SHIFT_9_2` or
+    /// `PixelFormat::SHIFT_9_3`), before conversion to depth.
+    ShiftPixel
+);
+
+/// A byte of a compressed `PixelFormat::JPEG` frame. `Frame::pixels` of a
+/// JPEG frame returns all of its compressed bytes, rather than one pixel per
+/// coordinate.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct JpegByte(pub u8);
+
+// The `Oni*` pixel types are plain aliases, so `OniDepthPixel` and
+// `OniGrayscale16Pixel` are both `u16`, which accepts any 16-bit format.
+// The newtypes only accept their own.
+isPixel!(
+    OniDepthPixel => [DEPTH_1_MM, DEPTH_100_UM, SHIFT_9_2, SHIFT_9_3, GRAY16],
+    OniGrayscale8Pixel => [GRAY8],
+    OniRGB888Pixel => [RGB888],
+    OniYUV422DoublePixel => [YUV422, YUYV],
+    DepthMmPixel => [DEPTH_1_MM],
+    Depth100UmPixel => [DEPTH_100_UM],
+    Gray16Pixel => [GRAY16],
+    ShiftPixel => [SHIFT_9_2, SHIFT_9_3],
+    JpegByte => [JPEG]
+);
 
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -0,0 +1,205 @@
+//! Rendering depth frames into RGB images for display.
+//!
+//! Rendering happens in two steps. A `DepthScale` maps each depth to a value
+//! between 0.0 (far) and 1.0 (near), or to `None` for pixels without a depth
+//! reading. A `Colormap` then turns that value into a color.
+//!
+//! * `DepthHistogram` equalizes depth with a cumulative histogram, the way
+//!   the OpenNI2 samples do. Every frame gets the full brightness range
+//!   however deep the scene is.
+//! * `DepthRange` maps a fixed depth range linearly, e.g. the range reported
+//!   by `Stream::get_min_value` and `Stream::get_max_value`, so a surface
+//!   keeps its color from frame to frame.
+//!
+//! # Example
+//! ```
+//! # use openni2::{Device, SensorType, OniDepthPixel};
+//! # use openni2::visualize::{self, DepthHistogram, DepthRange, Colormap};
+//! # fn main() -> Result<(), openni2::Status> {
+//! # /*
+//! let device = Device::open_default()?;
+//! # */
+//! # let device = Device::from_backend(openni2::mock::MockDevice::new());
+//! let stream = device.create_stream(SensorType::DEPTH)?;
+//! stream.start()?;
+//! let frame = stream.read_frame::<OniDepthPixel>()?;
+//!
+//! let histogram = DepthHistogram::from_frame(&frame);
+//! let equalized = visualize::colorize(&frame, &histogram, Colormap::GRAYSCALE);
+//!
+//! let range = DepthRange::from_stream(&stream)?;
+//! let colored = visualize::colorize(&frame, &range, Colormap::TURBO);
+//! # Ok(())
+//! # }
+//! ```
+
+use openni2_sys::*;
+use frame::Frame;
+use stream::Stream;
+use types::Status;
+
+/// Maps depth values to a scale from 0.0 (far) to 1.0 (near).
+pub trait DepthScale {
+    /// Returns where `depth` falls on the scale, or `None` if the pixel has
+    /// no depth reading.
+    fn scale(&self, depth: OniDepthPixel) -> Option<f32>;
+}
+
+/// A cumulative histogram of the depths in a frame. Scaling a depth by the
+/// histogram gives the fraction of pixels that are farther away, which
+/// spreads the scale evenly over the depths actually in view.
+///
+/// The histogram can be recomputed for each new frame with
+/// `DepthHistogram::update`, which reuses its allocation.
+#[derive(Debug, Clone, Default)]
+pub struct DepthHistogram {
+    table: Vec<f32>,
+}
+
+impl DepthHistogram {
+    /// Creates an empty histogram, which scales every depth as `None`.
+    pub fn new() -> Self {
+        DepthHistogram { table: Vec::new() }
+    }
+
+    /// Computes the histogram of a frame.
+    pub fn from_frame(frame: &Frame<OniDepthPixel>) -> Self {
+        let mut histogram = Self::new();
+        histogram.update(frame);
+        histogram
+    }
+
+    /// Recomputes the histogram from a new frame.
+    pub fn update(&mut self, frame: &Frame<OniDepthPixel>) {
+        let pixels = frame.pixels();
+        let max = pixels.iter().cloned().max().unwrap_or(0) as usize;
+        self.table.clear();
+        self.table.resize(max + 1, 0.0);
+
+        let mut count = 0usize;
+        for &px in pixels {
+            if px != 0 {
+                self.table[px as usize] += 1.0;
+                count += 1;
+            }
+        }
+
+        for i in 1..self.table.len() {
+            self.table[i] += self.table[i - 1];
+        }
+        if count > 0 {
+            for value in self.table.iter_mut().skip(1) {
+                *value = 1.0 - (*value / count as f32);
+            }
+        }
+    }
+}
+
+impl DepthScale for DepthHistogram {
+    fn scale(&self, depth: OniDepthPixel) -> Option<f32> {
+        match depth {
+            0 => None,
+            depth => self.table.get(depth as usize).cloned(),
+        }
+    }
+}
+
+/// A fixed range of depths, scaled linearly from 1.0 at `min` to 0.0 at
+/// `max`. Depths outside the range are clamped to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DepthRange {
+    pub min: OniDepthPixel,
+    pub max: OniDepthPixel,
+}
+
+impl DepthRange {
+    pub fn new(min: OniDepthPixel, max: OniDepthPixel) -> Self {
+        DepthRange { min, max }
+    }
+
+    /// The range of depth values a stream can report, from
+    /// `Stream::get_min_value` and `Stream::get_max_value`.
+    pub fn from_stream(stream: &Stream) -> Result<Self, Status> {
+        let clamp = |value: i32| value.clamp(0, i32::from(u16::MAX)) as OniDepthPixel;
+        Ok(DepthRange {
+            min: clamp(stream.get_min_value()?),
+            max: clamp(stream.get_max_value()?),
+        })
+    }
+}
+
+impl DepthScale for DepthRange {
+    fn scale(&self, depth: OniDepthPixel) -> Option<f32> {
+        if depth == 0 {
+            return None;
+        }
+        if self.max <= self.min {
+            return Some(1.0);
+        }
+        let depth = depth.clamp(self.min, self.max);
+        Some(1.0 - f32::from(depth - self.min) / f32::from(self.max - self.min))
+    }
+}
+
+/// A mapping from a value between 0.0 and 1.0 to a color.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Colormap {
+    /// Black to white
+    GRAYSCALE,
+    /// Blue to cyan, yellow and red, as in MATLAB
+    JET,
+    /// Google's perceptually smoother replacement for jet
+    TURBO,
+    /// Perceptually uniform dark blue to green and yellow, as in matplotlib
+    VIRIDIS,
+}
+
+impl Colormap {
+    /// Returns the color of `value`, which is clamped between 0.0 and 1.0.
+    pub fn color(self, value: f32) -> OniRGB888Pixel {
+        let t = value.clamp(0.0, 1.0);
+        let (r, g, b) = match self {
+            Colormap::GRAYSCALE => (t, t, t),
+            Colormap::JET => (
+                1.5 - (4.0 * t - 3.0).abs(),
+                1.5 - (4.0 * t - 2.0).abs(),
+                1.5 - (4.0 * t - 1.0).abs(),
+            ),
+            // Polynomial fit of Turbo by its author, Anton Mikhailov
+            Colormap::TURBO => (
+                polynomial(t, &[0.135_721_38, 4.615_392_6, -42.660_32, 132.131_08, -152.942_4, 59.286_38]),
+                polynomial(t, &[0.091_402_61, 2.194_188_4, 4.842_966_6, -14.185_033, 4.277_298_5, 2.829_566]),
+                polynomial(t, &[0.106_673_3, 12.641_946, -60.582_05, 110.362_77, -89.903_11, 27.348_25]),
+            ),
+            // Polynomial fit of viridis by Matt Zucker
+            Colormap::VIRIDIS => (
+                polynomial(t, &[0.277_727_33, 0.105_093_04, -0.330_861_83, -4.634_230_6, 6.228_27, 4.776_385, -5.435_456]),
+                polynomial(t, &[0.005_407_344_5, 1.404_613_5, 0.214_847_56, -5.799_101, 14.179_933, -13.745_145, 4.645_852_6]),
+                polynomial(t, &[0.334_099_8, 1.384_590_2, 0.095_095_16, -19.332_441, 56.690_55, -65.353_03, 26.312_435]),
+            ),
+        };
+        OniRGB888Pixel {
+            r: channel(r),
+            g: channel(g),
+            b: channel(b),
+        }
+    }
+}
+
+fn polynomial(t: f32, coefficients: &[f32]) -> f32 {
+    coefficients.iter().rev().fold(0.0, |sum, &coefficient| sum * t + coefficient)
+}
+
+fn channel(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Renders a depth frame into RGB pixels, in row order. Pixels without a
+/// depth reading are black.
+pub fn colorize<S: DepthScale>(frame: &Frame<OniDepthPixel>, scale: &S, colormap: Colormap) -> Vec<OniRGB888Pixel> {
+    let black = OniRGB888Pixel { r: 0, g: 0, b: 0 };
+    frame.pixels().iter()
+        .map(|&depth| scale.scale(depth).map_or(black, |value| colormap.color(value)))
+        .collect()
+}
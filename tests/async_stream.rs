@@ -0,0 +1,27 @@
+#![cfg(feature = "async")]
+extern crate futures;
+extern crate openni2;
+
+use std::thread;
+
+use openni2::{Device, OniDepthPixel, SensorType};
+use openni2::mock::MockDevice;
+
+#[test]
+fn frame_streams_can_be_polled_on_another_thread() {
+    let device = Device::from_backend(MockDevice::new());
+    let stream = device.create_stream(SensorType::DEPTH).unwrap();
+    let frames = stream.frame_stream::<OniDepthPixel>(4).unwrap();
+    stream.start().unwrap();
+
+    let indices: Vec<usize> = thread::scope(|scope| {
+        scope.spawn(move || {
+            futures::executor::block_on_stream(frames)
+                .take(3)
+                .map(|frame| frame.unwrap().index)
+                .collect()
+        }).join().unwrap()
+    });
+    assert_eq!(indices.len(), 3);
+    assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "image-sequence")]
+
+extern crate image;
+extern crate openni2;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use image::{ImageBuffer, Luma, RgbImage};
+use openni2::{ImageSequence, SensorType};
+
+// Makes an empty folder for one test's images.
+fn temp_folder(name: &str) -> PathBuf {
+    let folder = env::temp_dir().join(format!("openni2-image-sequence-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+#[test]
+fn a_depth_folder_plays_back_on_its_own() {
+    let root = temp_folder("depth-only");
+    fs::create_dir(root.join("depth")).unwrap();
+    for n in 0..3 {
+        ImageBuffer::<Luma<u16>, _>::from_pixel(4, 3, Luma([1_000])).save(root.join("depth").join(format!("{}.png", n))).unwrap();
+    }
+
+    let sequence = ImageSequence::open(&root).unwrap();
+    assert_eq!(sequence.number_of_frames(SensorType::DEPTH), 3);
+    assert_eq!(sequence.number_of_frames(SensorType::COLOR), 0);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn nyu_dumps_play_back_only_their_color() {
+    let root = temp_folder("nyu");
+    for n in 0..2 {
+        RgbImage::new(4, 3).save(root.join(format!("r-{}.5-{}.ppm", n, n))).unwrap();
+        // Raw disparity, which would play back as wrong depths
+        fs::write(root.join(format!("d-{}.5-{}.pgm", n, n)), b"P5 4 3 2047\n").unwrap();
+    }
+
+    let sequence = ImageSequence::open(&root).unwrap();
+    assert_eq!(sequence.number_of_frames(SensorType::COLOR), 2);
+    assert_eq!(sequence.number_of_frames(SensorType::DEPTH), 0);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,247 @@
+extern crate openni2;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use openni2::{Cropping, Device, FrameSetReader, Stream, StreamListener, OniDepthPixel, OniRGB888Pixel, OniYUV422DoublePixel, PixelFormat, SensorType, Status, VideoMode};
+use openni2::mock::{MockDevice, MockSensor};
+
+fn sensor(sensor_type: SensorType, pixel_format: PixelFormat, fps: i32) -> MockSensor {
+    MockSensor::new(sensor_type, vec![VideoMode { pixel_format, resolution_x: 80, resolution_y: 60, fps }])
+}
+
+// Polls `condition` for a few seconds, and returns whether it became true.
+fn eventually<F: FnMut() -> bool>(mut condition: F) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn listeners_are_called_until_dropped() {
+    let device = Device::from_backend(MockDevice::new().sensor(sensor(SensorType::DEPTH, PixelFormat::DEPTH_1_MM, 30)));
+    let stream = device.create_stream(SensorType::DEPTH).unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let listener = {
+        let calls = calls.clone();
+        stream.listener(move |stream| {
+            stream.read_frame::<OniDepthPixel>().unwrap();
+            calls.fetch_add(1, Ordering::SeqCst);
+        }).unwrap()
+    };
+    stream.start().unwrap();
+    assert!(eventually(|| calls.load(Ordering::SeqCst) >= 3));
+
+    drop(listener);
+    let after_drop = calls.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(calls.load(Ordering::SeqCst), after_drop);
+}
+
+// Holds a listener for its own callback to drop. Listeners aren't `Send`,
+// but this one is only ever dropped on the mock's event thread.
+struct ListenerSlot(Mutex<Option<StreamListener<'static>>>);
+
+unsafe impl Send for ListenerSlot {}
+unsafe impl Sync for ListenerSlot {}
+
+#[test]
+fn listeners_can_be_dropped_from_their_own_callback() {
+    let device: &'static Device = Box::leak(Box::new(Device::from_backend(MockDevice::new())));
+    let stream: &'static Stream = Box::leak(Box::new(device.create_stream(SensorType::DEPTH).unwrap()));
+    let slot = Arc::new(ListenerSlot(Mutex::new(None)));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let listener = {
+        let (slot, calls) = (slot.clone(), calls.clone());
+        stream.listener(move |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let listener = slot.0.lock().unwrap().take();
+            drop(listener);
+        }).unwrap()
+    };
+    *slot.0.lock().unwrap() = Some(listener);
+    stream.start().unwrap();
+
+    assert!(eventually(|| slot.0.lock().unwrap().is_none()));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    // The next listener is still called
+    let _listener = {
+        let calls = calls.clone();
+        stream.listener(move |_| { calls.fetch_add(1, Ordering::SeqCst); }).unwrap()
+    };
+    assert!(eventually(|| calls.load(Ordering::SeqCst) > 1));
+}
+
+#[test]
+fn frame_set_reader_matches_sensors_with_offset_timestamps() {
+    let device = Device::from_backend(MockDevice::new()
+        .sensor(sensor(SensorType::DEPTH, PixelFormat::DEPTH_1_MM, 30))
+        .sensor(sensor(SensorType::COLOR, PixelFormat::RGB888, 30).timestamp_offset(5_000)));
+    let depth = device.create_stream(SensorType::DEPTH).unwrap();
+    let color = device.create_stream(SensorType::COLOR).unwrap();
+    depth.start().unwrap();
+    color.start().unwrap();
+
+    let mut reader = FrameSetReader::new(&[&depth, &color]).unwrap().tolerance(Duration::from_millis(10));
+    for n in 0..3 {
+        let set = reader.read(Duration::from_millis(1000)).unwrap();
+        let (depth, color) = (set.depth.unwrap(), set.color.unwrap());
+        assert_eq!(depth.timestamp(), n * 33_333);
+        assert_eq!(color.timestamp(), depth.timestamp() + 5_000);
+        assert_eq!(set.ir.map(|frame| frame.timestamp()), None);
+    }
+    assert_eq!(reader.stats().matched, 3);
+    assert_eq!(reader.stats().unmatched, 0);
+}
+
+#[test]
+fn frame_set_reader_discards_frames_too_far_apart() {
+    let device = Device::from_backend(MockDevice::new()
+        .sensor(sensor(SensorType::DEPTH, PixelFormat::DEPTH_1_MM, 30).frame_limit(5))
+        .sensor(sensor(SensorType::COLOR, PixelFormat::RGB888, 30).frame_limit(5).timestamp_offset(15_000)));
+    let depth = device.create_stream(SensorType::DEPTH).unwrap();
+    let color = device.create_stream(SensorType::COLOR).unwrap();
+    depth.start().unwrap();
+    color.start().unwrap();
+
+    let mut reader = FrameSetReader::new(&[&depth, &color]).unwrap().tolerance(Duration::from_millis(10));
+    assert!(matches!(reader.read(Duration::from_millis(100)), Err(Status::TimeOut)));
+    assert_eq!(reader.stats().matched, 0);
+    assert!(reader.stats().unmatched > 0);
+}
+
+#[test]
+fn failed_frames_are_skipped() {
+    let depth = sensor(SensorType::DEPTH, PixelFormat::DEPTH_1_MM, 30)
+        .fail_frame(1, Status::Error("Lost frame".to_owned()))
+        .fail_frame(2, Status::TimeOut);
+    let device = Device::from_backend(MockDevice::new().sensor(depth));
+    let stream = device.create_stream(SensorType::DEPTH).unwrap();
+    stream.start().unwrap();
+
+    assert_eq!(stream.read_frame::<OniDepthPixel>().unwrap().index(), 0);
+    match stream.read_frame_any() {
+        Err(Status::Error(message)) => assert_eq!(message, "Lost frame"),
+        other => panic!("Expected frame 1 to fail, got {:?}", other.map(|frame| frame.index())),
+    }
+    assert!(matches!(stream.read_frame::<OniDepthPixel>(), Err(Status::TimeOut)));
+    assert_eq!(stream.read_frame::<OniDepthPixel>().unwrap().index(), 3);
+}
+
+#[test]
+fn frames_are_mirrored_then_cropped() {
+    let device = Device::from_backend(MockDevice::new().sensor(sensor(SensorType::COLOR, PixelFormat::RGB888, 30)));
+    let stream = device.create_stream(SensorType::COLOR).unwrap();
+    let cropping = Cropping { width: 20, height: 10, origin_x: 5, origin_y: 7 };
+    stream.set_cropping(Some(cropping)).unwrap();
+    stream.start().unwrap();
+
+    // The test pattern's red is x and green is y
+    let frame = stream.read_frame::<OniRGB888Pixel>().unwrap();
+    assert_eq!((frame.width(), frame.height()), (20, 10));
+    assert_eq!(frame.cropping().map(|cropping| (cropping.origin_x, cropping.origin_y)), Some((5, 7)));
+    assert_eq!(frame.pixels().len(), 200);
+    assert_eq!((frame.pixels()[0].r, frame.pixels()[0].g), (5, 7));
+    assert_eq!((frame.pixels()[21].r, frame.pixels()[21].g), (6, 8));
+
+    stream.set_mirroring(true).unwrap();
+    assert!(stream.get_mirroring().unwrap());
+    let frame = stream.read_frame::<OniRGB888Pixel>().unwrap();
+    assert_eq!((frame.pixels()[0].r, frame.pixels()[0].g), (74, 7));
+    assert_eq!(frame.pixels()[19].r, 55);
+
+    assert!(matches!(stream.set_cropping(Some(Cropping { width: 80, ..cropping })), Err(Status::BadParameter)));
+    stream.set_cropping(None).unwrap();
+    let frame = stream.read_frame::<OniRGB888Pixel>().unwrap();
+    assert_eq!((frame.width(), frame.height()), (80, 60));
+    assert!(frame.cropping().is_none());
+    assert_eq!(frame.pixels()[0].r, 79);
+}
+
+#[test]
+fn yuv_frames_hold_a_pixel_per_pair_of_coordinates() {
+    for &(pixel_format, first_luma) in &[(PixelFormat::YUV422, 1), (PixelFormat::YUYV, 0)] {
+        let mode = VideoMode { pixel_format, resolution_x: 8, resolution_y: 4, fps: 30 };
+        let device = Device::from_backend(MockDevice::new().sensor(MockSensor::new(SensorType::COLOR, vec![mode])));
+        let stream = device.create_stream(SensorType::COLOR).unwrap();
+        stream.start().unwrap();
+
+        let frame = stream.read_frame_any().unwrap().downcast::<OniYUV422DoublePixel>().ok().unwrap().to_owned();
+        assert_eq!((frame.width, frame.height), (8, 4));
+        assert_eq!(frame.pixels.len(), 16);
+        // The test pattern's luma is x
+        assert_eq!((frame.data()[4 + first_luma], frame.data()[6 + first_luma]), (2, 3));
+        assert_eq!(stream.read_frame::<OniYUV422DoublePixel>().unwrap().pixels().len(), 16);
+    }
+}
+
+#[test]
+fn seeking_moves_every_stream_of_a_playback_device() {
+    let device = Device::from_backend(MockDevice::new()
+        .sensor(sensor(SensorType::DEPTH, PixelFormat::DEPTH_1_MM, 30).frame_limit(30))
+        .sensor(sensor(SensorType::COLOR, PixelFormat::RGB888, 60).frame_limit(60))
+        .playback(true));
+    let depth = device.create_stream(SensorType::DEPTH).unwrap();
+    let color = device.create_stream(SensorType::COLOR).unwrap();
+    depth.start().unwrap();
+    color.start().unwrap();
+    let playback = device.playback().unwrap();
+    assert_eq!(playback.number_of_frames(&depth).unwrap(), 30);
+
+    playback.seek(&depth, 10).unwrap();
+    let frame = playback.step::<OniDepthPixel>(&depth).unwrap();
+    assert_eq!((frame.index(), frame.timestamp()), (10, 333_333));
+    // Color moves to its first frame at or after the depth frame
+    let frame = playback.step::<OniRGB888Pixel>(&color).unwrap();
+    assert_eq!((frame.index(), frame.timestamp()), (20, 333_333));
+    assert_eq!(playback.step::<OniDepthPixel>(&depth).unwrap().index(), 11);
+
+    assert!(matches!(playback.seek(&depth, 30), Err(Status::BadParameter)));
+    assert!(matches!(playback.seek(&depth, -1), Err(Status::BadParameter)));
+
+    playback.seek(&depth, 29).unwrap();
+    assert_eq!(playback.step::<OniDepthPixel>(&depth).unwrap().index(), 29);
+    assert!(matches!(playback.step::<OniDepthPixel>(&depth), Err(Status::TimeOut)));
+    playback.set_repeat(true).unwrap();
+    assert_eq!(playback.step::<OniDepthPixel>(&depth).unwrap().index(), 0);
+}
+
+#[test]
+fn listeners_resume_after_seeking_back_from_the_end() {
+    let device = Device::from_backend(MockDevice::new()
+        .sensor(sensor(SensorType::DEPTH, PixelFormat::DEPTH_1_MM, 30).frame_limit(5))
+        .playback(true)
+        .realtime(true));
+    let stream = device.create_stream(SensorType::DEPTH).unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let _listener = {
+        let calls = calls.clone();
+        stream.listener(move |stream| {
+            if stream.read_frame::<OniDepthPixel>().is_ok() {
+                calls.fetch_add(1, Ordering::SeqCst);
+            }
+        }).unwrap()
+    };
+    stream.start().unwrap();
+    assert!(eventually(|| calls.load(Ordering::SeqCst) == 5));
+    // Without repeat the stream stays at its end
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+
+    device.playback().unwrap().seek(&stream, 0).unwrap();
+    assert!(eventually(|| calls.load(Ordering::SeqCst) == 10));
+}
+
+#[test]
+fn live_devices_cant_seek() {
+    let device = Device::from_backend(MockDevice::new());
+    assert!(matches!(device.playback(), Err(Status::NotSupported)));
+}
@@ -0,0 +1,132 @@
+extern crate openni2;
+
+use std::io::Cursor;
+
+use openni2::{oni, OwnedFrame, OniDepthPixel, OniRGB888Pixel, OniYUV422DoublePixel, Pixel, PixelFormat, SensorType, VideoMode};
+
+fn video_mode(pixel_format: PixelFormat) -> VideoMode {
+    VideoMode { pixel_format, resolution_x: 32, resolution_y: 24, fps: 30 }
+}
+
+fn depth_frame(n: usize) -> OwnedFrame<OniDepthPixel> {
+    // Zeros and large jumps between neighbors exercise the codecs' escapes
+    let pixels = (0..32 * 24)
+        .map(|i| match (i + n) % 7 {
+            0 => 0,
+            1 => 30_000 - i as u16,
+            _ => 500 + (i * 3 + n) as u16,
+        })
+        .collect();
+    OwnedFrame {
+        pixels,
+        width: 32,
+        height: 24,
+        timestamp: n as u64 * 33_333,
+        index: n,
+        video_mode: video_mode(PixelFormat::DEPTH_1_MM),
+        cropping: None,
+    }
+}
+
+fn read_back(bytes: Vec<u8>) -> oni::Reader<Cursor<Vec<u8>>> {
+    oni::Reader::new(Cursor::new(bytes)).expect("Couldn't read written ONI file")
+}
+
+#[test]
+fn depth_roundtrips_through_every_writable_codec() {
+    for &codec in &[oni::Codec::Uncompressed, oni::Codec::Depth16Z, oni::Codec::Depth16ZEmbeddedTable] {
+        let mut writer = oni::Writer::new(Cursor::new(Vec::new())).unwrap();
+        let node_id = writer.add_stream(SensorType::DEPTH, video_mode(PixelFormat::DEPTH_1_MM), codec).unwrap();
+        let frames: Vec<_> = (0..3).map(depth_frame).collect();
+        for frame in &frames {
+            writer.write_frame(node_id, frame).unwrap();
+        }
+        let mut reader = read_back(writer.finish().unwrap().into_inner());
+
+        let stream = reader.stream(node_id).unwrap().clone();
+        assert_eq!(stream.codec, codec);
+        assert_eq!(stream.sensor_type(), Some(SensorType::DEPTH));
+        assert_eq!(stream.number_of_frames, 3);
+        assert_eq!((stream.min_timestamp, stream.max_timestamp), (0, 66_666));
+        let seek_timestamps: Vec<u64> = stream.seek_table.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(seek_timestamps, vec![0, 33_333, 66_666]);
+
+        let read: Vec<_> = reader.frames::<OniDepthPixel>(node_id).collect::<Result<_, _>>().unwrap();
+        assert_eq!(read.len(), 3);
+        for (written, read) in frames.iter().zip(&read) {
+            assert_eq!(read.pixels, written.pixels, "{:?} frame {} differs", codec, written.index);
+            assert_eq!(read.timestamp, written.timestamp);
+            // Frames are numbered from 1, like OpenNI2's recorder does
+            assert_eq!(read.index, written.index + 1);
+        }
+    }
+}
+
+fn color_frame<P: Pixel>(pixel_format: PixelFormat, pixels: Vec<P>) -> OwnedFrame<P> {
+    OwnedFrame {
+        pixels,
+        width: 32,
+        height: 24,
+        timestamp: 1_000,
+        index: 0,
+        video_mode: video_mode(pixel_format),
+        cropping: None,
+    }
+}
+
+fn roundtrip_uncompressed<P: Pixel>(frame: &OwnedFrame<P>) {
+    let mut writer = oni::Writer::new(Cursor::new(Vec::new())).unwrap();
+    let node_id = writer.add_stream(SensorType::COLOR, frame.video_mode, oni::Codec::Uncompressed).unwrap();
+    writer.write_frame(node_id, frame).unwrap();
+    let mut reader = read_back(writer.finish().unwrap().into_inner());
+
+    assert_eq!(reader.stream(node_id).unwrap().video_mode().map(|mode| mode.pixel_format), Some(frame.video_mode.pixel_format));
+    let read = reader.read_frame::<P>(node_id, 0).unwrap();
+    assert_eq!(read.data(), frame.data());
+    assert_eq!(read.index, 1);
+}
+
+#[test]
+fn color_roundtrips_uncompressed() {
+    roundtrip_uncompressed(&color_frame(PixelFormat::RGB888, (0..32 * 24).map(|i| OniRGB888Pixel { r: i as u8, g: (i / 32) as u8, b: 7 }).collect()));
+    // Each pixel of a 4:2:2 frame holds two neighbouring pixels
+    let pixels = (0..32 * 24 / 2).map(|i| OniYUV422DoublePixel { u: 128, y1: i as u8, v: 64, y2: (i / 16) as u8 }).collect();
+    roundtrip_uncompressed(&color_frame(PixelFormat::YUV422, pixels));
+}
+
+#[test]
+fn depth16z_refuses_values_it_cant_store() {
+    let mut data = vec![0u8; 32 * 24 * 2];
+    data[2..4].copy_from_slice(&40_000u16.to_le_bytes());
+    assert!(oni::Codec::Depth16Z.encode(&data).is_err());
+    assert_eq!(oni::Codec::Depth16ZEmbeddedTable.decode(&oni::Codec::Depth16ZEmbeddedTable.encode(&data).unwrap()).unwrap(), data);
+}
+
+#[test]
+fn compressed_codecs_refuse_color() {
+    let mut writer = oni::Writer::new(Cursor::new(Vec::new())).unwrap();
+    for &codec in &[oni::Codec::Depth16Z, oni::Codec::Image8Z, oni::Codec::Jpeg] {
+        assert!(writer.add_stream(SensorType::COLOR, video_mode(PixelFormat::RGB888), codec).is_err());
+    }
+}
+
+#[test]
+fn streams_have_a_field_of_view_by_default() {
+    let mut writer = oni::Writer::new(Cursor::new(Vec::new())).unwrap();
+    let depth = writer.add_stream(SensorType::DEPTH, video_mode(PixelFormat::DEPTH_1_MM), oni::Codec::Uncompressed).unwrap();
+    let color = writer.add_stream(SensorType::COLOR, video_mode(PixelFormat::RGB888), oni::Codec::Uncompressed).unwrap();
+    writer.set_field_of_view(color, 1.0, 0.75).unwrap();
+    let reader = read_back(writer.finish().unwrap().into_inner());
+
+    let (horizontal, vertical) = reader.stream(depth).unwrap().field_of_view().unwrap();
+    assert!((horizontal - 1.0145).abs() < 1e-3 && (vertical - 0.7898).abs() < 1e-3);
+    assert_eq!(reader.stream(color).unwrap().field_of_view(), Some((1.0, 0.75)));
+}
+
+#[test]
+fn frames_must_match_the_video_mode() {
+    let mut writer = oni::Writer::new(Cursor::new(Vec::new())).unwrap();
+    let node_id = writer.add_stream(SensorType::DEPTH, video_mode(PixelFormat::DEPTH_1_MM), oni::Codec::Uncompressed).unwrap();
+    assert!(writer.write_frame_data(node_id, 0, &[0; 10]).is_err());
+    assert!(writer.write_frame_data(node_id + 1, 0, &[0; 32 * 24 * 2]).is_err());
+}
@@ -0,0 +1,53 @@
+extern crate openni2;
+
+use std::io::Cursor;
+
+use openni2::{oni, PixelFormat, SensorType, ShiftCalibration, ShiftPixel, ShiftToDepth, VideoMode};
+
+// The calibration a Kinect or Xtion typically reports.
+fn calibration() -> ShiftCalibration {
+    ShiftCalibration {
+        zero_plane_distance: 120,
+        zero_plane_pixel_size: 0.1042,
+        emitter_dcmos_distance: 7.5,
+        const_shift: 200,
+        param_coeff: 4,
+        shift_scale: 10,
+        pixel_size_factor: 1,
+        max_shift: 2047,
+        max_depth: 10_000,
+    }
+}
+
+#[test]
+fn shifts_convert_to_the_drivers_depths() {
+    let shift_to_depth = ShiftToDepth::new(&calibration()).unwrap();
+    assert_eq!(shift_to_depth.table().len(), 2047);
+    // Depths the PS1080 driver's formula gives, truncated to millimeters
+    for &(shift, depth) in &[(500, 586), (800, 1193), (1000, 3864), (1050, 8767)] {
+        assert_eq!(shift_to_depth.depth(ShiftPixel(shift)), depth, "shift {}", shift);
+    }
+    // Shift 0 and depths beyond max_depth or behind the sensor have no depth
+    for &shift in &[0, 1084, 2046, 4000] {
+        assert_eq!(shift_to_depth.depth(ShiftPixel(shift)), 0, "shift {}", shift);
+    }
+}
+
+#[test]
+fn calibration_roundtrips_through_oni_properties() {
+    let mode = VideoMode { pixel_format: PixelFormat::SHIFT_9_2, resolution_x: 32, resolution_y: 24, fps: 30 };
+    let mut writer = oni::Writer::new(Cursor::new(Vec::new())).unwrap();
+    let node_id = writer.add_stream(SensorType::DEPTH, mode, oni::Codec::Uncompressed).unwrap();
+    for property in calibration().to_properties() {
+        writer.set_property(node_id, &property).unwrap();
+    }
+    let reader = oni::Reader::new(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+
+    assert_eq!(ShiftCalibration::from_oni_stream(reader.stream(node_id).unwrap()).unwrap(), calibration());
+}
+
+#[test]
+fn overflowing_calibrations_are_refused() {
+    let calibration = ShiftCalibration { const_shift: u64::MAX, ..calibration() };
+    assert!(ShiftToDepth::new(&calibration).is_err());
+}